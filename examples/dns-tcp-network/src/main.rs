@@ -5,6 +5,7 @@ use inet::{
     dns::lookup_host,
     interface::{add_interface, Interface, NetworkDevice},
     routing::{set_default_gateway, RoutingInformation},
+    tcp::{set_tcp_cfg, TcpConfig},
     types::ip::IpMask,
     utils::LinkLayerSwitch,
     TcpListener, TcpStream,
@@ -42,8 +43,9 @@ impl AsyncModule for Client {
 
             for _ in 0..100 {
                 let domain = DOMAINS[random::<usize>() % DOMAINS.len()];
-                let mut stream = TcpStream::connect((domain, 80)).await.unwrap();
-                stream.write_all(domain.as_bytes()).await.unwrap();
+                let mut stream = TcpStream::connect_with_data((domain, 80), domain.as_bytes())
+                    .await
+                    .unwrap();
                 let mut buf = [0; 64];
                 let n = stream.read(&mut buf).await.unwrap();
                 assert_eq!(n, 1);
@@ -96,6 +98,12 @@ impl AsyncModule for Server {
             server.launch().await.unwrap();
         });
 
+        set_tcp_cfg(TcpConfig {
+            fast_open: true,
+            ..Default::default()
+        })
+        .unwrap();
+
         spawn(async move {
             let addr = par("addr").unwrap().parse::<Ipv4Addr>().unwrap();
             let list = TcpListener::bind("0.0.0.0:80").await.unwrap();
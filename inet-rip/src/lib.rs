@@ -7,7 +7,7 @@ use std::net::{IpAddr, Ipv4Addr};
 
 use inet::{
     interface::{add_interface, interface_status, Interface},
-    routing::add_routing_entry,
+    routing::{add_routing_entry, remove_routing_entry},
     Current, UdpSocket,
 };
 
@@ -243,8 +243,10 @@ impl RipRoutingDeamon {
                         let entry = self.vectors.get_mut(&addr).unwrap();
 
                         if SimTime::now() >= entry.deadline {
-                            // Timeout
+                            // Timeout: withdraw the route
                             tracing::info!("Timeout for DV");
+                            let entry = self.vectors.remove(&addr).unwrap();
+                            let _ = remove_routing_entry(entry.subnet, entry.mask);
                         } else if SimTime::now() >= entry.update_time {
                             // request update
                             updates.entry(entry.gateway).or_insert(Vec::new()).push(RipEntry {
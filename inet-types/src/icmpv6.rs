@@ -0,0 +1,653 @@
+//! Internet Control Message Protocol for IPv6 (RFC 4443), including the
+//! messages of the Neighbor Discovery Protocol (RFC 4861).
+
+use std::{
+    io::{Error, ErrorKind, Read, Write},
+    net::Ipv6Addr,
+};
+
+use bytepack::{
+    raw_enum, BytestreamReader, BytestreamWriter, FromBytestream, ReadBytesExt, ToBytestream,
+    WriteBytesExt, BE,
+};
+
+use crate::iface::MacAddress;
+
+pub const PROTO_ICMPV6: u8 = 58;
+
+/// The hop limit of all Neighbor Discovery messages. Receivers drop
+/// ND messages with any other hop limit, since they might have been
+/// forwarded by a router (RFC 4861).
+pub const NDP_HOP_LIMIT: u8 = 255;
+
+/// The link-local all-nodes multicast address `ff02::1`.
+pub const ALL_NODES_MULTICAST: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// The link-local all-routers multicast address `ff02::2`.
+pub const ALL_ROUTERS_MULTICAST: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// The solicited-node multicast address of an unicast address `ff02::1:ffXX:XXXX`,
+/// used as the destination of neighbor solicitations (RFC 4291).
+#[must_use]
+pub fn solicited_node_multicast(addr: Ipv6Addr) -> Ipv6Addr {
+    let octets = addr.octets();
+    Ipv6Addr::from([
+        0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0xff, octets[13], octets[14], octets[15],
+    ])
+}
+
+/// An ICMP packet for IPv6.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Icmpv6Packet {
+    DestinationUnreachable {
+        code: Icmpv6DestinationUnreachableCode,
+        content: Vec<u8>, // as much of the invoking packet as possible
+    } = 1,
+    PacketTooBig {
+        mtu: u32,
+        content: Vec<u8>,
+    } = 2,
+    TimeExceeded {
+        code: Icmpv6TimeExceededCode,
+        content: Vec<u8>,
+    } = 3,
+    ParameterProblem {
+        code: Icmpv6ParameterProblemCode,
+        pointer: u32, // offset of the erroneous field in the invoking packet
+        content: Vec<u8>,
+    } = 4,
+    EchoRequest {
+        identifier: u16,
+        sequence: u16,
+        data: Vec<u8>,
+    } = 128,
+    EchoReply {
+        identifier: u16,
+        sequence: u16,
+        data: Vec<u8>,
+    } = 129,
+    RouterSolicitation {
+        options: Vec<NdpOption>,
+    } = 133,
+    RouterAdvertisement {
+        cur_hop_limit: u8,
+        managed: bool,
+        other: bool,
+        router_lifetime: u16,
+        reachable_time: u32,
+        retrans_timer: u32,
+        options: Vec<NdpOption>,
+    } = 134,
+    NeighborSolicitation {
+        target: Ipv6Addr,
+        options: Vec<NdpOption>,
+    } = 135,
+    NeighborAdvertisement {
+        router: bool,
+        solicited: bool,
+        overrides: bool,
+        target: Ipv6Addr,
+        options: Vec<NdpOption>,
+    } = 136,
+}
+
+impl Icmpv6Packet {
+    /// Indicates whether the packet is a Neighbor Discovery message.
+    #[must_use]
+    pub fn is_ndp(&self) -> bool {
+        matches!(
+            self,
+            Self::RouterSolicitation { .. }
+                | Self::RouterAdvertisement { .. }
+                | Self::NeighborSolicitation { .. }
+                | Self::NeighborAdvertisement { .. }
+        )
+    }
+
+    /// The options attached to a Neighbor Discovery message.
+    #[must_use]
+    pub fn options(&self) -> &[NdpOption] {
+        match self {
+            Self::RouterSolicitation { options }
+            | Self::RouterAdvertisement { options, .. }
+            | Self::NeighborSolicitation { options, .. }
+            | Self::NeighborAdvertisement { options, .. } => options,
+            _ => &[],
+        }
+    }
+
+    /// The link-layer address of the sender, if provided as an option.
+    #[must_use]
+    pub fn source_link_layer_addr(&self) -> Option<MacAddress> {
+        self.options().iter().find_map(|opt| match opt {
+            NdpOption::SourceLinkLayerAddress(mac) => Some(*mac),
+            _ => None,
+        })
+    }
+
+    /// The link-layer address of the target, if provided as an option.
+    #[must_use]
+    pub fn target_link_layer_addr(&self) -> Option<MacAddress> {
+        self.options().iter().find_map(|opt| match opt {
+            NdpOption::TargetLinkLayerAddress(mac) => Some(*mac),
+            _ => None,
+        })
+    }
+}
+
+impl ToBytestream for Icmpv6Packet {
+    type Error = Error;
+    #[allow(clippy::too_many_lines)]
+    fn to_bytestream(&self, stream: &mut BytestreamWriter) -> Result<(), Self::Error> {
+        match self {
+            Self::DestinationUnreachable { code, content } => {
+                stream.write_u8(1)?;
+                stream.write_u8(code.to_raw_repr())?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(0)?; // unused
+                stream.write_all(content)?;
+            }
+            Self::PacketTooBig { mtu, content } => {
+                stream.write_u8(2)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(*mtu)?;
+                stream.write_all(content)?;
+            }
+            Self::TimeExceeded { code, content } => {
+                stream.write_u8(3)?;
+                stream.write_u8(code.to_raw_repr())?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(0)?; // unused
+                stream.write_all(content)?;
+            }
+            Self::ParameterProblem {
+                code,
+                pointer,
+                content,
+            } => {
+                stream.write_u8(4)?;
+                stream.write_u8(code.to_raw_repr())?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(*pointer)?;
+                stream.write_all(content)?;
+            }
+            Self::EchoRequest {
+                identifier,
+                sequence,
+                data,
+            } => {
+                stream.write_u8(128)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u16::<BE>(*identifier)?;
+                stream.write_u16::<BE>(*sequence)?;
+                stream.write_all(data)?;
+            }
+            Self::EchoReply {
+                identifier,
+                sequence,
+                data,
+            } => {
+                stream.write_u8(129)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u16::<BE>(*identifier)?;
+                stream.write_u16::<BE>(*sequence)?;
+                stream.write_all(data)?;
+            }
+            Self::RouterSolicitation { options } => {
+                stream.write_u8(133)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(0)?; // reserved
+                NdpOption::write_all(options, stream)?;
+            }
+            Self::RouterAdvertisement {
+                cur_hop_limit,
+                managed,
+                other,
+                router_lifetime,
+                reachable_time,
+                retrans_timer,
+                options,
+            } => {
+                stream.write_u8(134)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u8(*cur_hop_limit)?;
+                stream.write_u8(u8::from(*managed) << 7 | u8::from(*other) << 6)?;
+                stream.write_u16::<BE>(*router_lifetime)?;
+                stream.write_u32::<BE>(*reachable_time)?;
+                stream.write_u32::<BE>(*retrans_timer)?;
+                NdpOption::write_all(options, stream)?;
+            }
+            Self::NeighborSolicitation { target, options } => {
+                stream.write_u8(135)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                stream.write_u32::<BE>(0)?; // reserved
+                stream.write_all(&target.octets())?;
+                NdpOption::write_all(options, stream)?;
+            }
+            Self::NeighborAdvertisement {
+                router,
+                solicited,
+                overrides,
+                target,
+                options,
+            } => {
+                stream.write_u8(136)?;
+                stream.write_u8(0)?;
+                stream.write_u16::<BE>(0)?; // checksum
+                let flags = u32::from(*router) << 31
+                    | u32::from(*solicited) << 30
+                    | u32::from(*overrides) << 29;
+                stream.write_u32::<BE>(flags)?;
+                stream.write_all(&target.octets())?;
+                NdpOption::write_all(options, stream)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromBytestream for Icmpv6Packet {
+    type Error = Error;
+    #[allow(clippy::too_many_lines)]
+    fn from_bytestream(stream: &mut BytestreamReader) -> Result<Self, Self::Error> {
+        let typ = stream.read_u8()?;
+        let code = stream.read_u8()?;
+        let _checksum = stream.read_u16::<BE>()?;
+
+        match typ {
+            1 => {
+                let _ = stream.read_u32::<BE>()?;
+                let mut content = Vec::new();
+                stream.read_to_end(&mut content)?;
+                Ok(Self::DestinationUnreachable {
+                    code: Icmpv6DestinationUnreachableCode::from_raw_repr(code)?,
+                    content,
+                })
+            }
+            2 => {
+                let mtu = stream.read_u32::<BE>()?;
+                let mut content = Vec::new();
+                stream.read_to_end(&mut content)?;
+                Ok(Self::PacketTooBig { mtu, content })
+            }
+            3 => {
+                let _ = stream.read_u32::<BE>()?;
+                let mut content = Vec::new();
+                stream.read_to_end(&mut content)?;
+                Ok(Self::TimeExceeded {
+                    code: Icmpv6TimeExceededCode::from_raw_repr(code)?,
+                    content,
+                })
+            }
+            4 => {
+                let pointer = stream.read_u32::<BE>()?;
+                let mut content = Vec::new();
+                stream.read_to_end(&mut content)?;
+                Ok(Self::ParameterProblem {
+                    code: Icmpv6ParameterProblemCode::from_raw_repr(code)?,
+                    pointer,
+                    content,
+                })
+            }
+            128 | 129 => {
+                let identifier = stream.read_u16::<BE>()?;
+                let sequence = stream.read_u16::<BE>()?;
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data)?;
+                if typ == 128 {
+                    Ok(Self::EchoRequest {
+                        identifier,
+                        sequence,
+                        data,
+                    })
+                } else {
+                    Ok(Self::EchoReply {
+                        identifier,
+                        sequence,
+                        data,
+                    })
+                }
+            }
+            133 => {
+                let _ = stream.read_u32::<BE>()?;
+                Ok(Self::RouterSolicitation {
+                    options: NdpOption::read_all(stream)?,
+                })
+            }
+            134 => {
+                let cur_hop_limit = stream.read_u8()?;
+                let flags = stream.read_u8()?;
+                let router_lifetime = stream.read_u16::<BE>()?;
+                let reachable_time = stream.read_u32::<BE>()?;
+                let retrans_timer = stream.read_u32::<BE>()?;
+                Ok(Self::RouterAdvertisement {
+                    cur_hop_limit,
+                    managed: flags & 0x80 != 0,
+                    other: flags & 0x40 != 0,
+                    router_lifetime,
+                    reachable_time,
+                    retrans_timer,
+                    options: NdpOption::read_all(stream)?,
+                })
+            }
+            135 => {
+                let _ = stream.read_u32::<BE>()?;
+                let target = Ipv6Addr::from(stream.read_u128::<BE>()?);
+                Ok(Self::NeighborSolicitation {
+                    target,
+                    options: NdpOption::read_all(stream)?,
+                })
+            }
+            136 => {
+                let flags = stream.read_u32::<BE>()?;
+                let target = Ipv6Addr::from(stream.read_u128::<BE>()?);
+                Ok(Self::NeighborAdvertisement {
+                    router: flags & (1 << 31) != 0,
+                    solicited: flags & (1 << 30) != 0,
+                    overrides: flags & (1 << 29) != 0,
+                    target,
+                    options: NdpOption::read_all(stream)?,
+                })
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                "unknown icmpv6 message type",
+            )),
+        }
+    }
+}
+
+/// An option attached to a Neighbor Discovery message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NdpOption {
+    SourceLinkLayerAddress(MacAddress),
+    TargetLinkLayerAddress(MacAddress),
+    PrefixInformation {
+        prefix_len: u8,
+        on_link: bool,
+        autonomous: bool,
+        valid_lifetime: u32,
+        preferred_lifetime: u32,
+        prefix: Ipv6Addr,
+    },
+    Mtu(u32),
+}
+
+impl NdpOption {
+    fn write_all(options: &[NdpOption], stream: &mut BytestreamWriter) -> Result<(), Error> {
+        for option in options {
+            option.to_bytestream(stream)?;
+        }
+        Ok(())
+    }
+
+    /// Reads options until the end of the stream, skipping unknown options.
+    fn read_all(stream: &mut BytestreamReader) -> Result<Vec<NdpOption>, Error> {
+        let mut options = Vec::new();
+        while !stream.is_empty() {
+            let typ = stream.read_u8()?;
+            let len = stream.read_u8()?;
+            if len == 0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "ndp option with zero length",
+                ));
+            }
+
+            // The length includes type and length, in units of 8 bytes
+            let mut body = stream.extract(usize::from(len) * 8 - 2)?;
+            match typ {
+                1 => options.push(NdpOption::SourceLinkLayerAddress(
+                    MacAddress::from_bytestream(&mut body)?,
+                )),
+                2 => options.push(NdpOption::TargetLinkLayerAddress(
+                    MacAddress::from_bytestream(&mut body)?,
+                )),
+                3 => {
+                    let prefix_len = body.read_u8()?;
+                    let flags = body.read_u8()?;
+                    let valid_lifetime = body.read_u32::<BE>()?;
+                    let preferred_lifetime = body.read_u32::<BE>()?;
+                    let _ = body.read_u32::<BE>()?;
+                    let prefix = Ipv6Addr::from(body.read_u128::<BE>()?);
+                    options.push(NdpOption::PrefixInformation {
+                        prefix_len,
+                        on_link: flags & 0x80 != 0,
+                        autonomous: flags & 0x40 != 0,
+                        valid_lifetime,
+                        preferred_lifetime,
+                        prefix,
+                    });
+                }
+                5 => {
+                    let _ = body.read_u16::<BE>()?;
+                    options.push(NdpOption::Mtu(body.read_u32::<BE>()?));
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl ToBytestream for NdpOption {
+    type Error = Error;
+    fn to_bytestream(&self, stream: &mut BytestreamWriter) -> Result<(), Self::Error> {
+        match self {
+            Self::SourceLinkLayerAddress(mac) => {
+                stream.write_u8(1)?;
+                stream.write_u8(1)?;
+                mac.to_bytestream(stream)?;
+            }
+            Self::TargetLinkLayerAddress(mac) => {
+                stream.write_u8(2)?;
+                stream.write_u8(1)?;
+                mac.to_bytestream(stream)?;
+            }
+            Self::PrefixInformation {
+                prefix_len,
+                on_link,
+                autonomous,
+                valid_lifetime,
+                preferred_lifetime,
+                prefix,
+            } => {
+                stream.write_u8(3)?;
+                stream.write_u8(4)?;
+                stream.write_u8(*prefix_len)?;
+                stream.write_u8(u8::from(*on_link) << 7 | u8::from(*autonomous) << 6)?;
+                stream.write_u32::<BE>(*valid_lifetime)?;
+                stream.write_u32::<BE>(*preferred_lifetime)?;
+                stream.write_u32::<BE>(0)?; // reserved
+                stream.write_all(&prefix.octets())?;
+            }
+            Self::Mtu(mtu) => {
+                stream.write_u8(5)?;
+                stream.write_u8(1)?;
+                stream.write_u16::<BE>(0)?; // reserved
+                stream.write_u32::<BE>(*mtu)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// # Codes
+
+raw_enum! {
+    /// A reponse code to a ICMPv6 destination unreachable message.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Icmpv6DestinationUnreachableCode {
+        type Repr = u8 where BigEndian;
+        NoRouteToDestination = 0,
+        CommunicationProhibited = 1,
+        BeyondScopeOfSourceAddress = 2,
+        AddressUnreachable = 3,
+        PortUnreachable = 4,
+        SourceAddressFailedPolicy = 5,
+        RejectRouteToDestination = 6,
+    }
+}
+
+raw_enum! {
+    /// A reponse code to a ICMPv6 time exceeded message.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Icmpv6TimeExceededCode {
+        type Repr = u8 where BigEndian;
+        HopLimitExceeded = 0,
+        FragmentReassemblyTimeExceeded = 1,
+    }
+}
+
+raw_enum! {
+    /// A reponse code to a ICMPv6 parameter problem message.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Icmpv6ParameterProblemCode {
+        type Repr = u8 where BigEndian;
+        ErroneousHeaderField = 0,
+        UnrecognizedNextHeader = 1,
+        UnrecognizedIpv6Option = 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solicited_node_address() {
+        let addr: Ipv6Addr = "fe80::aa:1234:5678".parse().unwrap();
+        assert_eq!(
+            solicited_node_multicast(addr),
+            "ff02::1:ff34:5678".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(
+            MacAddress::ipv6_multicast(solicited_node_multicast(addr)),
+            [0x33, 0x33, 0xff, 0x34, 0x56, 0x78].into()
+        );
+    }
+
+    #[test]
+    fn neighbor_solicitation() {
+        let pkt = Icmpv6Packet::NeighborSolicitation {
+            target: "fe80::1".parse().unwrap(),
+            options: vec![NdpOption::SourceLinkLayerAddress([1, 2, 3, 4, 5, 6].into())],
+        };
+
+        let buf = pkt.to_vec().unwrap();
+        assert_eq!(buf.len(), 8 + 16 + 8);
+        assert_eq!(buf[0], 135);
+
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, pkt);
+        assert!(parsed.is_ndp());
+        assert_eq!(
+            parsed.source_link_layer_addr(),
+            Some([1, 2, 3, 4, 5, 6].into())
+        );
+    }
+
+    #[test]
+    fn neighbor_advertisement_flags() {
+        let pkt = Icmpv6Packet::NeighborAdvertisement {
+            router: false,
+            solicited: true,
+            overrides: true,
+            target: "fe80::2".parse().unwrap(),
+            options: vec![NdpOption::TargetLinkLayerAddress([6, 5, 4, 3, 2, 1].into())],
+        };
+
+        let buf = pkt.to_vec().unwrap();
+        assert_eq!(buf[4], 0b0110_0000);
+
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, pkt);
+        assert_eq!(
+            parsed.target_link_layer_addr(),
+            Some([6, 5, 4, 3, 2, 1].into())
+        );
+    }
+
+    #[test]
+    fn router_advertisement() {
+        let pkt = Icmpv6Packet::RouterAdvertisement {
+            cur_hop_limit: 64,
+            managed: false,
+            other: true,
+            router_lifetime: 1800,
+            reachable_time: 0,
+            retrans_timer: 0,
+            options: vec![
+                NdpOption::SourceLinkLayerAddress([1, 2, 3, 4, 5, 6].into()),
+                NdpOption::Mtu(1500),
+                NdpOption::PrefixInformation {
+                    prefix_len: 64,
+                    on_link: true,
+                    autonomous: true,
+                    valid_lifetime: 86400,
+                    preferred_lifetime: 14400,
+                    prefix: "2001:db8::".parse().unwrap(),
+                },
+            ],
+        };
+
+        let buf = pkt.to_vec().unwrap();
+        assert_eq!(buf.len(), 16 + 8 + 8 + 32);
+
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, pkt);
+    }
+
+    #[test]
+    fn unknown_options_are_skipped() {
+        let mut buf = Icmpv6Packet::RouterSolicitation { options: vec![] }
+            .to_vec()
+            .unwrap();
+        buf.extend([42, 1, 0, 0, 0, 0, 0, 0]);
+        NdpOption::SourceLinkLayerAddress([1, 2, 3, 4, 5, 6].into())
+            .append_to_vec(&mut buf)
+            .unwrap();
+
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(
+            parsed,
+            Icmpv6Packet::RouterSolicitation {
+                options: vec![NdpOption::SourceLinkLayerAddress([1, 2, 3, 4, 5, 6].into())]
+            }
+        );
+    }
+
+    #[test]
+    fn echo_request() {
+        let pkt = Icmpv6Packet::EchoRequest {
+            identifier: 1,
+            sequence: 2,
+            data: vec![42; 16],
+        };
+        let buf = pkt.to_vec().unwrap();
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, pkt);
+    }
+
+    #[test]
+    fn parameter_problem() {
+        let pkt = Icmpv6Packet::ParameterProblem {
+            code: Icmpv6ParameterProblemCode::UnrecognizedNextHeader,
+            pointer: 6,
+            content: vec![0x60; 40],
+        };
+        let buf = pkt.to_vec().unwrap();
+        assert_eq!(buf[..2], [4, 1]);
+        assert_eq!(buf[4..8], [0, 0, 0, 6]);
+
+        let parsed = Icmpv6Packet::read_from_slice(&mut &buf[..]).unwrap();
+        assert_eq!(parsed, pkt);
+    }
+}
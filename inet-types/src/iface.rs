@@ -4,6 +4,7 @@ use des::runtime::random;
 use std::{
     fmt::Display,
     io::{Read, Write},
+    net::Ipv6Addr,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -34,6 +35,20 @@ impl MacAddress {
     pub fn is_broadcast(&self) -> bool {
         *self == MacAddress::BROADCAST
     }
+
+    /// Indicates whether the address is a group address,
+    /// including the broadcast address.
+    #[must_use]
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0b1 != 0
+    }
+
+    /// The link-layer address of an IPv6 multicast group `33:33:XX:XX:XX:XX` (RFC 2464).
+    #[must_use]
+    pub fn ipv6_multicast(addr: Ipv6Addr) -> MacAddress {
+        let octets = addr.octets();
+        MacAddress([0x33, 0x33, octets[12], octets[13], octets[14], octets[15]])
+    }
 }
 
 impl From<[u8; 6]> for MacAddress {
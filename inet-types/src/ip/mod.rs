@@ -18,6 +18,15 @@ mod tests;
 pub const KIND_IPV4: MessageKind = 0x0800;
 pub const KIND_IPV6: MessageKind = 0x86DD;
 
+/// The ECN codepoint of a packet not using ECN (RFC 3168).
+pub const ECN_NOT_ECT: u8 = 0b00;
+/// The ECN codepoint ECT(1) of an ECN capable transport.
+pub const ECN_ECT1: u8 = 0b01;
+/// The ECN codepoint ECT(0) of an ECN capable transport.
+pub const ECN_ECT0: u8 = 0b10;
+/// The ECN codepoint of a packet that experienced congestion.
+pub const ECN_CE: u8 = 0b11;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum IpVersion {
@@ -54,6 +63,15 @@ impl IpPacketRef<'_, '_> {
         }
     }
 
+    /// Returns the ECN codepoint of the packet.
+    #[must_use]
+    pub fn ecn(&self) -> u8 {
+        match self {
+            Self::V4(v4) => v4.enc & 0b11,
+            Self::V6(v6) => v6.traffic_class & 0b11,
+        }
+    }
+
     #[must_use]
     pub fn src(&self) -> IpAddr {
         match self {
@@ -141,6 +159,23 @@ impl IpPacket {
         }
     }
 
+    /// Returns the ECN codepoint of the packet.
+    #[must_use]
+    pub fn ecn(&self) -> u8 {
+        match self {
+            Self::V4(v4) => v4.enc & 0b11,
+            Self::V6(v6) => v6.traffic_class & 0b11,
+        }
+    }
+
+    /// Sets the ECN codepoint of the packet, e.g. to mark congestion.
+    pub fn set_ecn(&mut self, ecn: u8) {
+        match self {
+            Self::V4(v4) => v4.enc = ecn & 0b11,
+            Self::V6(v6) => v6.traffic_class = (v6.traffic_class & !0b11) | (ecn & 0b11),
+        }
+    }
+
     #[must_use]
     pub fn new(src: IpAddr, dest: IpAddr, content: Vec<u8>) -> Self {
         use IpAddr::{V4, V6};
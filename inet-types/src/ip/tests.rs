@@ -356,6 +356,33 @@ fn v6_with_content() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn ecn_codepoints() {
+    let mut v4 = IpPacket::new(
+        IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+        IpAddr::V4(Ipv4Addr::new(9, 8, 7, 6)),
+        Vec::new(),
+    );
+    assert_eq!(v4.ecn(), ECN_NOT_ECT);
+    v4.set_ecn(ECN_CE);
+    assert_eq!(v4.ecn(), ECN_CE);
+
+    let mut v6 = IpPacket::new(
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        Vec::new(),
+    );
+    if let IpPacket::V6(ref mut pkt) = v6 {
+        pkt.traffic_class = 0b1010_1000;
+    }
+    v6.set_ecn(ECN_ECT0);
+    assert_eq!(v6.ecn(), ECN_ECT0);
+    let IpPacket::V6(pkt) = v6 else {
+        unreachable!()
+    };
+    assert_eq!(pkt.traffic_class, 0b1010_1010);
+}
+
 #[test]
 fn ipv4_addr() {
     let addr = Ipv4Addr::new(1, 2, 3, 4);
@@ -10,6 +10,7 @@ mod macros;
 
 pub mod arp;
 pub mod icmp;
+pub mod icmpv6;
 pub mod iface;
 pub mod ip;
 // pub mod ipv2;
@@ -40,11 +40,19 @@ pub struct TcpFlags {
 }
 
 /// Options of a [`TcpPacket`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TcpOption {
     MaximumSegmentSize(u16),
     WindowScaling(u8),
     Timestamp(u32, u32),
+    /// Indicates that selective acknowledgements may be used (RFC 2018).
+    SackPermitted(),
+    /// Selective acknowledgements, each block describing a received
+    /// range `left..right` of sequence numbers (RFC 2018).
+    Sack(Vec<(u32, u32)>),
+    /// A TCP Fast Open cookie (RFC 7413). An empty cookie requests
+    /// a new cookie from the server.
+    FastOpenCookie(Vec<u8>),
     EndOfOptionsList(),
 }
 
@@ -73,6 +81,15 @@ impl TcpPacket {
             content: Vec::new(),
         }
     }
+
+    /// Returns the `(TSval, TSecr)` pair of the timestamp option, if present.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<(u32, u32)> {
+        self.options.iter().find_map(|option| match option {
+            TcpOption::Timestamp(ts_val, ts_ecr) => Some((*ts_val, *ts_ecr)),
+            _ => None,
+        })
+    }
 }
 
 impl TcpFlags {
@@ -222,6 +239,29 @@ impl ToBytestream for TcpOption {
                 stream.write_u32::<BE>(*send)?;
                 stream.write_u32::<BE>(*recv)
             }
+            Self::SackPermitted() => {
+                stream.write_u8(4)?;
+                stream.write_u8(2)
+            }
+            Self::Sack(blocks) => {
+                let len = u8::try_from(2 + 8 * blocks.len())
+                    .map_err(|_| Error::new(ErrorKind::InvalidInput, "too many sack blocks"))?;
+                stream.write_u8(5)?;
+                stream.write_u8(len)?;
+                for (left, right) in blocks {
+                    stream.write_u32::<BE>(*left)?;
+                    stream.write_u32::<BE>(*right)?;
+                }
+                Ok(())
+            }
+            Self::FastOpenCookie(cookie) => {
+                let len = u8::try_from(2 + cookie.len()).map_err(|_| {
+                    Error::new(ErrorKind::InvalidInput, "fast open cookie too long")
+                })?;
+                stream.write_u8(34)?;
+                stream.write_u8(len)?;
+                stream.write_all(cookie)
+            }
             Self::EndOfOptionsList() => stream.write_u8(0),
         }
     }
@@ -249,8 +289,9 @@ impl FromBytestream for TcpPacket {
         let mut options = Vec::new();
         while !substream.is_empty() {
             let option = TcpOption::from_bytestream(&mut substream)?;
+            let eol = option == TcpOption::EndOfOptionsList();
             options.push(option);
-            if option == TcpOption::EndOfOptionsList() {
+            if eol {
                 break;
             }
         }
@@ -318,12 +359,86 @@ impl FromBytestream for TcpOption {
                 let cnt = substream.read_u8()?;
                 Ok(Self::WindowScaling(cnt))
             }
+            4 => Ok(Self::SackPermitted()),
+            5 => {
+                let mut blocks = Vec::new();
+                while !substream.is_empty() {
+                    let left = substream.read_u32::<BE>()?;
+                    let right = substream.read_u32::<BE>()?;
+                    blocks.push((left, right));
+                }
+                Ok(Self::Sack(blocks))
+            }
             8 => {
                 let send = substream.read_u32::<BE>()?;
                 let recv = substream.read_u32::<BE>()?;
                 Ok(Self::Timestamp(send, recv))
             }
+            34 => {
+                let mut cookie = Vec::new();
+                substream.read_to_end(&mut cookie)?;
+                Ok(Self::FastOpenCookie(cookie))
+            }
             _ => Err(Error::new(ErrorKind::Other, "invalid tcp options kind")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sack_options() -> std::io::Result<()> {
+        let input = TcpPacket {
+            src_port: 80,
+            dest_port: 1024,
+            seq_no: 1000,
+            ack_no: 2000,
+            flags: TcpFlags::new().ack(true),
+            window: 4096,
+            urgent_ptr: 0,
+            options: vec![
+                TcpOption::SackPermitted(),
+                TcpOption::Sack(vec![(3000, 4000), (5000, 5500)]),
+                TcpOption::EndOfOptionsList(),
+            ],
+            content: vec![1, 2, 3],
+        };
+
+        let output = TcpPacket::from_slice(&input.to_vec()?)?;
+        assert_eq!(input, output);
+        Ok(())
+    }
+
+    #[test]
+    fn fast_open_options() -> std::io::Result<()> {
+        let input = TcpPacket {
+            src_port: 1024,
+            dest_port: 80,
+            seq_no: 1000,
+            ack_no: 0,
+            flags: TcpFlags::new().syn(true),
+            window: 4096,
+            urgent_ptr: 0,
+            options: vec![
+                TcpOption::MaximumSegmentSize(1460),
+                TcpOption::FastOpenCookie(vec![1, 2, 3, 4, 5, 6, 7, 8]),
+                TcpOption::EndOfOptionsList(),
+            ],
+            content: vec![42; 100],
+        };
+
+        let output = TcpPacket::from_slice(&input.to_vec()?)?;
+        assert_eq!(input, output);
+
+        // A cookie request carries no cookie
+        let mut input = input;
+        input.options[1] = TcpOption::FastOpenCookie(Vec::new());
+        input.content.clear();
+
+        let output = TcpPacket::from_slice(&input.to_vec()?)?;
+        assert_eq!(input, output);
+        Ok(())
+    }
+}
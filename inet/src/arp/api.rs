@@ -64,6 +64,8 @@ pub fn arpa() -> Result<Vec<ArpEntry>> {
 }
 
 /// Adds a permantent entry to the IP network neighbor table
+///
+/// IPv6 entries are added to the neighbor cache of NDP.
 pub fn set_arp_entry(ip: IpAddr, mac: MacAddress, if_name: InterfaceName) -> Result<()> {
     IOContext::failable_api(|ctx| ctx.set_arp_entry(ip, mac, if_name))
 }
@@ -105,6 +107,12 @@ impl IOContext {
     }
 
     fn set_arp_entry(&mut self, ip: IpAddr, mac: MacAddress, if_name: InterfaceName) -> Result<()> {
+        // IPv6 addresses are resolved using the neighbor cache
+        if let IpAddr::V6(ip) = ip {
+            self.ndp_set_permanent(ip, mac, if_name.id);
+            return Ok(());
+        }
+
         let sendable = self.arp.update(super::ArpEntryInternal {
             negated: false,
             hostname: None,
@@ -1,8 +1,8 @@
 //! The Address Resoloution Protocol (ARP)
 //!
-//! ARP is used to resolve IP addresses to corresponding MAC
-//! addresses of hosts. Note that for simplicities sake
-//! Ipv6-over-ARP is temporarily allowed.
+//! ARP is used to resolve IPv4 addresses to corresponding MAC
+//! addresses of hosts. IPv6 addresses are resolved using
+//! the Neighbor Discovery Protocol (see `ndp`).
 //!
 //! The user may interact with the ARP deamon by either
 //! requesting the all `ArpEntry` using `arpa`.
@@ -15,14 +15,15 @@
 use std::io::{self, Error, ErrorKind};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
-use crate::routing::IpGateway;
+use crate::firewall::{FirewallChain, Verdict};
+use crate::routing::{IpGateway, RouteKey};
 use crate::socket::SocketIfaceBinding;
 use crate::{interface::*, IOContext};
 use des::prelude::{schedule_in, Message};
 use des::time::SimTime;
 use inet_types::arp::{ARPOperation, ArpPacket, KIND_ARP};
 use inet_types::iface::MacAddress;
-use inet_types::ip::{IpPacket, KIND_IPV4, KIND_IPV6};
+use inet_types::ip::{IpPacket, IpPacketRef, KIND_IPV4, KIND_IPV6};
 
 mod table;
 pub use self::table::*;
@@ -220,43 +221,109 @@ impl IOContext {
         ifid: SocketIfaceBinding,
         pkt: IpPacket,
         buffered: bool,
+    ) -> io::Result<()> {
+        self.send_ip_packet_with(ifid, pkt, buffered, None, 0)
+    }
+
+    /// Sends an IP packet, providing the ingress interface of forwarded
+    /// packets and the mark of the sending socket to the routing rules.
+    /// Forwarded packets traverse the `Forward` firewall chain, all
+    /// others the `Output` chain.
+    pub(crate) fn send_ip_packet_with(
+        &mut self,
+        ifid: SocketIfaceBinding,
+        pkt: IpPacket,
+        buffered: bool,
+        iif: Option<IfId>,
+        mark: u32,
     ) -> io::Result<()> {
         // (0) Routing table destintation lookup
 
         let (route, rifid): (IpGateway, IfId) = match &pkt {
             IpPacket::V4(pkt) => {
-                let Some((route, rifid)) = self.ipv4_fwd.lookup(pkt.dest) else {
+                let key = RouteKey {
+                    src: pkt.src,
+                    dscp: pkt.dscp,
+                    iif,
+                    mark,
+                };
+                let Some((route, rifid)) = self.ipv4_fwd.lookup_multipath(pkt, &key) else {
                     return Err(Error::new(
                         ErrorKind::ConnectionRefused,
                         "no gateway network reachable"
                     ))
                 };
-                (route.clone().into(), rifid.id)
+                (route.into(), rifid.id)
             }
             IpPacket::V6(pkt) => {
-                let Some((route, rifid)) = self.ipv6router.loopuk_gateway(pkt.dest) else {
+                let Some((route, rifid)) = self.ipv6_fwd.lookup(pkt.dest) else {
                     return Err(Error::new(
                         ErrorKind::ConnectionRefused,
                         "no gateway network reachable"
                     ))
                 };
-                (route.clone().into(), *rifid)
+                (route.clone().into(), rifid.id)
+            }
+        };
+
+        // (1) Filter outgoing packets, once the egress interface is known.
+        // Rules in POSTROUTING cannot reject packets, so the missing
+        // ingress interface only affects locally generated packets
+        let chain = match iif {
+            Some(_) => FirewallChain::Forward,
+            None => FirewallChain::Output,
+        };
+        for (chain, iif) in [(chain, iif), (FirewallChain::Postrouting, None)] {
+            let ip = match &pkt {
+                IpPacket::V4(pkt) => IpPacketRef::V4(pkt),
+                IpPacket::V6(pkt) => IpPacketRef::V6(pkt),
+            };
+            match (self.firewall_filter(chain, &ip, iif, Some(rifid)), iif) {
+                (Verdict::Accept, _) => {}
+                (Verdict::Drop, _) => return Ok(()),
+                // Forwarded packets are answered, while local senders are notified directly
+                (Verdict::Reject, Some(iif)) => {
+                    self.firewall_reject(iif, ip);
+                    return Ok(());
+                }
+                (Verdict::Reject, None) => {
+                    return Err(Error::new(
+                        ErrorKind::PermissionDenied,
+                        "packet rejected by firewall",
+                    ))
+                }
             }
+        }
+
+        // (2) Fragment packets that exceed the MTU of the outgoing link
+        let pkts = match pkt {
+            IpPacket::V4(pkt) => self
+                .ipv4_fragment(pkt, rifid)?
+                .into_iter()
+                .map(IpPacket::V4)
+                .collect(),
+            IpPacket::V6(pkt) => vec![IpPacket::V6(pkt)],
         };
 
-        match route {
-            IpGateway::Local => self.send_lan_local_ip_packet(
-                SocketIfaceBinding::Bound(rifid),
-                pkt.dest(),
-                pkt,
-                buffered,
-            ),
-            IpGateway::Gateway(gw) => {
-                self.send_lan_local_ip_packet(SocketIfaceBinding::Bound(rifid), gw, pkt, buffered)
+        for pkt in pkts {
+            match &route {
+                IpGateway::Local => self.send_lan_local_ip_packet(
+                    SocketIfaceBinding::Bound(rifid),
+                    pkt.dest(),
+                    pkt,
+                    buffered,
+                )?,
+                IpGateway::Gateway(gw) => self.send_lan_local_ip_packet(
+                    SocketIfaceBinding::Bound(rifid),
+                    *gw,
+                    pkt,
+                    buffered,
+                )?,
+                // TODO: move logic to extra, non-arp fn
+                IpGateway::Broadcast => self.broadcast_ip_packet(ifid.clone(), pkt, buffered)?,
             }
-            // TODO: move logic to extra, non-arp fn
-            IpGateway::Broadcast => self.broadcast_ip_packet(ifid, pkt, buffered),
         }
+        Ok(())
     }
 
     pub fn broadcast_ip_packet(
@@ -292,7 +359,7 @@ impl IOContext {
                         }
                         IpPacket::V6(mut pkt) => {
                             if pkt.src.is_unspecified() {
-                                pkt.src = iface.ipv6_subnet().unwrap().0;
+                                pkt.src = iface.ipv6_src_addr(pkt.dest).unwrap();
                             }
                             let msg = Message::new()
                                 .kind(KIND_IPV6)
@@ -321,8 +388,15 @@ impl IOContext {
         pkt: IpPacket,
         buffered: bool,
     ) -> io::Result<()> {
-        let Some((negated, mac, ifid)) = self.arp_lookup(dest, &ifid) else {
-            self.arp_missing_addr_mapping(ifid, pkt, dest)?;
+        let lookup = match dest {
+            IpAddr::V4(_) => self.arp_lookup(dest, &ifid),
+            IpAddr::V6(dest) => self.ndp_lookup(dest, &ifid),
+        };
+        let Some((negated, mac, ifid)) = lookup else {
+            match dest {
+                IpAddr::V4(_) => self.arp_missing_addr_mapping(ifid, pkt, dest)?,
+                IpAddr::V6(dest) => self.ndp_missing_addr_mapping(ifid, pkt, dest)?,
+            }
             return Ok(())
         };
 
@@ -364,7 +438,7 @@ impl IOContext {
             }
             IpPacket::V6(mut pkt) => {
                 if pkt.src.is_unspecified() {
-                    pkt.src = iface.ipv6_subnet().unwrap().0;
+                    pkt.src = iface.ipv6_src_addr(pkt.dest).unwrap();
                 }
                 let msg = Message::new()
                     .kind(KIND_IPV6)
@@ -390,36 +464,46 @@ impl IOContext {
         self.arp
             .lookup(&dest)
             .map(|e| (e.negated, e.mac, e.iface))
-            .or_else(|| match preferred_iface {
-                SocketIfaceBinding::Bound(ifid) => {
+            .or_else(|| self.self_addr_lookup(dest, preferred_iface))
+        // .map(|(addr, ifid)| (addr, self.map_to_valid_ifid(ifid)))
+    }
+
+    /// Resolves addresses of the local machine, that are not present
+    /// in the ARP table or neighbor cache.
+    pub(crate) fn self_addr_lookup(
+        &self,
+        dest: IpAddr,
+        preferred_iface: &SocketIfaceBinding,
+    ) -> Option<(bool, MacAddress, IfId)> {
+        match preferred_iface {
+            SocketIfaceBinding::Bound(ifid) => {
+                let Some(iface) = self.ifaces.get(&ifid) else {
+                    return None;
+                };
+                let looback = iface.flags.loopback && dest.is_loopback();
+                let self_addr = iface.addrs.iter().any(|addr| addr.matches_ip(dest));
+                if looback || self_addr {
+                    Some((false, iface.device.addr, iface.name.id))
+                } else {
+                    None
+                }
+            }
+            SocketIfaceBinding::Any(ifids) => {
+                for ifid in ifids {
                     let Some(iface) = self.ifaces.get(&ifid) else {
-                        return None;
+                        continue;
                     };
                     let looback = iface.flags.loopback && dest.is_loopback();
                     let self_addr = iface.addrs.iter().any(|addr| addr.matches_ip(dest));
                     if looback || self_addr {
-                        Some((false, iface.device.addr, iface.name.id))
-                    } else {
-                        None
+                        return Some((false, iface.device.addr, iface.name.id));
                     }
                 }
-                SocketIfaceBinding::Any(ifids) => {
-                    for ifid in ifids {
-                        let Some(iface) = self.ifaces.get(&ifid) else {
-                            continue;
-                        };
-                        let looback = iface.flags.loopback && dest.is_loopback();
-                        let self_addr = iface.addrs.iter().any(|addr| addr.matches_ip(dest));
-                        if looback || self_addr {
-                            return Some((false, iface.device.addr, iface.name.id));
-                        }
-                    }
-                    None
-                }
+                None
+            }
 
-                _ => panic!("not yet implemented: {} {:?}", dest, preferred_iface),
-            })
-        // .map(|(addr, ifid)| (addr, self.map_to_valid_ifid(ifid)))
+            _ => panic!("not yet implemented: {} {:?}", dest, preferred_iface),
+        }
     }
 
     fn arp_missing_addr_mapping(
@@ -2,9 +2,12 @@ use crate::{
     arp::ArpTable,
     dns::{default_dns_resolve, DnsResolver},
     extensions::Extensions,
+    firewall::{Firewall, FirewallChain},
     icmp::Icmp,
     interface::{IfId, Interface, LinkLayerResult, KIND_LINK_UPDATE},
-    routing::{FwdV4, Ipv6RoutingTable},
+    ip::{Ipv4Reassembly, PathMtuCache},
+    ndp::NeighborCache,
+    routing::{FwdV4, FwdV6, IpForwarding},
     IOPlugin, Udp,
 };
 use des::{
@@ -14,7 +17,8 @@ use des::{
 use fxhash::{FxBuildHasher, FxHashMap};
 use inet_types::{
     icmp::PROTO_ICMP,
-    ip::{IpPacket, IpPacketRef, Ipv4Packet, Ipv6Packet, KIND_IPV4, KIND_IPV6},
+    icmpv6::PROTO_ICMPV6,
+    ip::{IpPacket, IpPacketRef, IpVersion, Ipv4Packet, Ipv6Packet, KIND_IPV4, KIND_IPV6},
 };
 use std::{
     cell::RefCell,
@@ -39,9 +43,14 @@ pub(crate) struct IOContext {
     pub(super) ifaces: FxHashMap<IfId, Interface>,
 
     pub(super) arp: ArpTable,
+    pub(super) ndp: NeighborCache,
     pub(super) ipv4_fwd: FwdV4,
-    pub(super) ipv6router: Ipv6RoutingTable,
+    pub(super) ipv6_fwd: FwdV6,
+    pub(super) ip_forward: IpForwarding,
     pub(super) icmp: Icmp,
+    pub(super) ipv4_reassembly: Ipv4Reassembly,
+    pub(super) pmtu: PathMtuCache,
+    pub(super) firewall: Firewall,
 
     pub(super) dns: DnsResolver,
 
@@ -78,9 +87,14 @@ impl IOContext {
             ifaces: FxHashMap::with_hasher(FxBuildHasher::default()),
 
             arp: ArpTable::new(),
+            ndp: NeighborCache::new(),
             ipv4_fwd: FwdV4::new(),
-            ipv6router: Ipv6RoutingTable::new(),
+            ipv6_fwd: FwdV6::new(),
+            ip_forward: IpForwarding::new(),
             icmp: Icmp::new(),
+            ipv4_reassembly: Ipv4Reassembly::new(),
+            pmtu: PathMtuCache::new(),
+            firewall: Firewall::new(),
 
             dns: default_dns_resolve,
 
@@ -144,13 +158,14 @@ impl IOContext {
 
 impl IOContext {
     pub fn recv(&mut self, msg: Message) -> Option<Message> {
+        use FirewallChain::{Input, Prerouting};
         use LinkLayerResult::*;
 
         // Packets that are passed to the networking layer, are
         // not nessecarily addressed to any valid ip addr, but are valid for
         // the local MAC addr
         let l2 = self.recv_linklayer(msg);
-        let (msg, ifid) = match l2 {
+        let (mut msg, ifid) = match l2 {
             PassThrough(msg) => return Some(msg),
             Consumed() => return None,
             NetworkingPacket(msg, ifid) => (msg, ifid),
@@ -167,6 +182,10 @@ impl IOContext {
                     return Some(msg)
                 };
 
+                if !self.firewall_filter_recv(Prerouting, IpPacketRef::V4(ip), ifid, None) {
+                    return None;
+                }
+
                 let iface = self.ifaces.get(&ifid).unwrap();
 
                 // (0) Check whether the received ip packet is addressed for the local machine
@@ -177,6 +196,12 @@ impl IOContext {
                         .any(|addr| addr.matches_ip(IpAddr::V4(ip.dest)));
 
                 if !local_dest {
+                    // Hosts drop packets, that are not addressed to them
+                    if !self.ip_forward.enabled(ifid, IpVersion::V4) {
+                        tracing::trace!("dropping packet to {}, forwarding is disabled", ip.dest);
+                        return None;
+                    }
+
                     // (0) Check TTL
                     let mut pkt = ip.clone();
                     pkt.ttl = pkt.ttl.saturating_sub(1);
@@ -188,10 +213,12 @@ impl IOContext {
                     }
 
                     // (2) Reroute packet.
-                    match self.send_ip_packet(
+                    match self.send_ip_packet_with(
                         SocketIfaceBinding::Any(self.ifaces.keys().copied().collect()),
                         IpPacket::V4(pkt),
                         true,
+                        Some(ifid),
+                        0,
                     ) {
                         Ok(()) => return None,
                         Err(e) => {
@@ -203,6 +230,17 @@ impl IOContext {
                     };
                 }
 
+                // (1) Reassemble fragmented packets
+                if ip.flags.mf || ip.fragment_offset != 0 {
+                    let pkt = self.ipv4_reassemble(ip.clone(), ifid)?;
+                    *msg.content_mut::<Ipv4Packet>() = pkt;
+                }
+                let ip = msg.content::<Ipv4Packet>();
+
+                if !self.firewall_filter_recv(Input, IpPacketRef::V4(ip), ifid, None) {
+                    return None;
+                }
+
                 match ip.proto {
                     0 => Some(msg),
                     PROTO_ICMP => {
@@ -235,7 +273,13 @@ impl IOContext {
                             let _ = handle.1.try_send(IpPacket::V4(ip.clone()));
                             return None;
                         }
-                        panic!("internal error: unreachable code :: proto = {k}");
+
+                        let consumed = self.recv_unknown_proto(IpPacketRef::V4(ip), ifid);
+                        if consumed {
+                            None
+                        } else {
+                            Some(msg)
+                        }
                     }
                 }
             }
@@ -245,37 +289,71 @@ impl IOContext {
                     return Some(msg)
                 };
 
+                if !self.firewall_filter_recv(Prerouting, IpPacketRef::V6(ip), ifid, None) {
+                    return None;
+                }
+
                 let iface = self.ifaces.get(&ifid).unwrap();
 
                 // (0) Check whether the received ip packet is addressed for the local machine
-                let local_dest = /*ip.dest ==  Ipv6Addr::BROADCAST
-                    || */iface
-                        .addrs
-                        .iter()
-                        .any(|addr| addr.matches_ip(IpAddr::V6(ip.dest)));
+                let local_dest = iface
+                    .addrs
+                    .iter()
+                    .any(|addr| addr.matches_ip(IpAddr::V6(ip.dest)))
+                    || iface.ipv6_multicast_groups().any(|group| group == ip.dest);
                 if !local_dest {
+                    // Multicast packets are not forwarded
+                    if ip.dest.is_multicast() {
+                        return None;
+                    }
+
+                    // Hosts drop packets, that are not addressed to them
+                    if !self.ip_forward.enabled(ifid, IpVersion::V6) {
+                        tracing::trace!("dropping packet to {}, forwarding is disabled", ip.dest);
+                        return None;
+                    }
+
                     // (0) Check TTL
                     let mut pkt = ip.clone();
                     pkt.hop_limit = pkt.hop_limit.saturating_sub(1);
 
                     if pkt.hop_limit == 0 {
                         tracing::warn!("dropping packet due to ttl");
+                        self.icmpv6_hop_limit_exceeded(ifid, ip);
                         return None;
                     }
 
                     // (2) Reroute packet.
-                    match self.send_ip_packet(
+                    match self.send_ip_packet_with(
                         SocketIfaceBinding::Any(self.ifaces.keys().copied().collect()),
                         IpPacket::V6(pkt),
                         true,
+                        Some(ifid),
+                        0,
                     ) {
                         Ok(()) => return None,
-                        Err(e) => panic!("not yet impl: forwarding without route: {}", e),
+                        Err(e) => {
+                            tracing::error!("Failed to forward packet due to internal err: {e}");
+                            self.icmpv6_routing_failed(ifid, e, ip);
+                            return None;
+                        }
                     };
                 }
 
+                if !self.firewall_filter_recv(Input, IpPacketRef::V6(ip), ifid, None) {
+                    return None;
+                }
+
                 match ip.next_header {
                     0 => return Some(msg),
+                    PROTO_ICMPV6 => {
+                        let consumed = self.recv_icmpv6_packet(ip, ifid);
+                        if consumed {
+                            None
+                        } else {
+                            Some(msg)
+                        }
+                    }
                     PROTO_UDP => {
                         let consumed = self.recv_udp_packet(IpPacketRef::V6(ip), ifid);
                         if consumed {
@@ -299,7 +377,13 @@ impl IOContext {
                             let _ = handle.1.try_send(IpPacket::V6(ip.clone()));
                             return None;
                         }
-                        panic!("internal error: unreachable code :: proto = {k}");
+
+                        let consumed = self.recv_unknown_proto(IpPacketRef::V6(ip), ifid);
+                        if consumed {
+                            None
+                        } else {
+                            Some(msg)
+                        }
                     }
                 }
             }
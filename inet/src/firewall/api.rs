@@ -0,0 +1,120 @@
+use std::io::{Error, ErrorKind, Result};
+
+use super::{FirewallAction, FirewallChain, FirewallCounter, FirewallRule};
+use crate::IOContext;
+
+/// Appends a rule to a firewall chain
+///
+/// This function is roughly equivalent to `iptables -A`. Rules are
+/// evaluated in the order they were added, each rule counting the packets
+/// it matched. Rules that match on ports or TCP flags must specify the
+/// transport protocol.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::firewall::*;
+/// use inet::interface::InterfaceName;
+/// use inet::types::tcp::PROTO_TCP;
+///
+/// # fn main() -> std::io::Result<()> {
+/// // Only allow HTTP traffic from the WAN
+/// add_firewall_rule(FirewallChain::Forward, FirewallRule {
+///     iif: Some(InterfaceName::new("wan0")),
+///     proto: Some(PROTO_TCP),
+///     dest_ports: Some(80..=80),
+///     ..FirewallRule::new(FirewallAction::Accept)
+/// })?;
+/// add_firewall_rule(FirewallChain::Forward, FirewallRule {
+///     iif: Some(InterfaceName::new("wan0")),
+///     ..FirewallRule::new(FirewallAction::Reject)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn add_firewall_rule(chain: FirewallChain, rule: FirewallRule) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        rule.validate(chain)?;
+        ctx.firewall.chain(chain).rules.push(FirewallCounter {
+            rule,
+            packets: 0,
+            bytes: 0,
+        });
+        Ok(())
+    })
+}
+
+/// Inserts a rule into a firewall chain, at the given position.
+///
+/// This function is roughly equivalent to `iptables -I`.
+pub fn insert_firewall_rule(chain: FirewallChain, index: usize, rule: FirewallRule) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        rule.validate(chain)?;
+        let rules = &mut ctx.firewall.chain(chain).rules;
+        if index > rules.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "index out of bounds of the chain",
+            ));
+        }
+        rules.insert(
+            index,
+            FirewallCounter {
+                rule,
+                packets: 0,
+                bytes: 0,
+            },
+        );
+        Ok(())
+    })
+}
+
+/// Removes the first rule of a firewall chain, that is equal to the provided rule.
+pub fn remove_firewall_rule(chain: FirewallChain, rule: &FirewallRule) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        let rules = &mut ctx.firewall.chain(chain).rules;
+        let Some(i) = rules.iter().position(|c| c.rule == *rule) else {
+            return Err(Error::new(ErrorKind::NotFound, "no such rule in the chain"));
+        };
+        rules.remove(i);
+        Ok(())
+    })
+}
+
+/// Removes all rules from a firewall chain. The policy of
+/// the chain remains unchanged.
+pub fn flush_firewall_chain(chain: FirewallChain) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.firewall.chain(chain).rules.clear();
+        Ok(())
+    })
+}
+
+/// Returns the rules of a firewall chain, in the order of their
+/// evaluation, with the number of packets they matched.
+pub fn firewall_rules(chain: FirewallChain) -> Result<Vec<FirewallCounter>> {
+    IOContext::failable_api(|ctx| Ok(ctx.firewall.chain(chain).rules.clone()))
+}
+
+/// Sets the policy of a firewall chain, applied to all packets
+/// that are neither accepted, dropped nor rejected by a rule.
+///
+/// This function is roughly equivalent to `iptables -P`. Only `Accept`,
+/// the default, and `Drop` are valid policies.
+pub fn set_firewall_policy(chain: FirewallChain, policy: FirewallAction) -> Result<()> {
+    if !matches!(policy, FirewallAction::Accept | FirewallAction::Drop) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "policy must be either accept or drop",
+        ));
+    }
+    IOContext::failable_api(|ctx| {
+        ctx.firewall.chain(chain).policy = policy;
+        Ok(())
+    })
+}
+
+/// Returns the policy of a firewall chain.
+pub fn firewall_policy(chain: FirewallChain) -> Result<FirewallAction> {
+    IOContext::failable_api(|ctx| Ok(ctx.firewall.chain(chain).policy.clone()))
+}
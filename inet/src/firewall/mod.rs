@@ -0,0 +1,368 @@
+//! Packet filtering at the hook points of the networking layer.
+//!
+//! The firewall is modelled after netfilter. Each chain contains a list of
+//! rules, that are evaluated for every packet traversing the chain.
+use inet_types::{
+    icmp::PROTO_ICMP,
+    ip::IpPacketRef,
+    tcp::{TcpFlags, TcpPacket, PROTO_TCP},
+};
+
+use crate::{interface::IfId, socket::SocketIfaceBinding, IOContext};
+use bytepack::{FromBytestream, ToBytestream};
+
+mod api;
+pub use self::api::*;
+
+mod rule;
+pub use self::rule::*;
+
+/// The outcome of a chain traversal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Accept,
+    Drop,
+    Reject,
+}
+
+#[derive(Debug)]
+pub(crate) struct Firewall {
+    chains: [Chain; 5],
+}
+
+#[derive(Debug)]
+struct Chain {
+    policy: FirewallAction,
+    rules: Vec<FirewallCounter>,
+}
+
+impl Firewall {
+    pub(crate) fn new() -> Self {
+        Self {
+            chains: FirewallChain::ALL.map(|_| Chain {
+                policy: FirewallAction::Accept,
+                rules: Vec::new(),
+            }),
+        }
+    }
+
+    fn chain(&mut self, chain: FirewallChain) -> &mut Chain {
+        &mut self.chains[chain as usize]
+    }
+
+    /// Evaluates the rules of a chain, counting the packet towards all
+    /// matching rules.
+    pub(crate) fn filter(&mut self, chain: FirewallChain, pkt: &PacketMeta) -> Verdict {
+        let chain = self.chain(chain);
+        for counter in &mut chain.rules {
+            if !counter.rule.matches(pkt) {
+                continue;
+            }
+
+            counter.packets += 1;
+            counter.bytes += pkt.len as u64;
+            match &counter.rule.action {
+                FirewallAction::Accept => return Verdict::Accept,
+                FirewallAction::Drop => return Verdict::Drop,
+                FirewallAction::Reject => return Verdict::Reject,
+                FirewallAction::Log(prefix) => tracing::info!("{prefix}{pkt}"),
+            }
+        }
+
+        match chain.policy {
+            FirewallAction::Drop => Verdict::Drop,
+            _ => Verdict::Accept,
+        }
+    }
+}
+
+impl IOContext {
+    /// Passes a packet through a firewall chain, returning the verdict.
+    pub(crate) fn firewall_filter(
+        &mut self,
+        chain: FirewallChain,
+        pkt: &IpPacketRef,
+        iif: Option<IfId>,
+        oif: Option<IfId>,
+    ) -> Verdict {
+        let iif = iif.and_then(|ifid| self.ifaces.get(&ifid)).map(|i| &i.name);
+        let oif = oif.and_then(|ifid| self.ifaces.get(&ifid)).map(|i| &i.name);
+
+        let meta = PacketMeta::new(pkt, iif, oif);
+        let verdict = self.firewall.filter(chain, &meta);
+        if verdict != Verdict::Accept {
+            tracing::trace!("{chain}: {verdict:?} {meta}");
+        }
+        verdict
+    }
+
+    /// Passes a received packet through a firewall chain, answering
+    /// rejected packets. Returns whether the packet was accepted.
+    pub(crate) fn firewall_filter_recv(
+        &mut self,
+        chain: FirewallChain,
+        pkt: IpPacketRef,
+        ifid: IfId,
+        oif: Option<IfId>,
+    ) -> bool {
+        match self.firewall_filter(chain, &pkt, Some(ifid), oif) {
+            Verdict::Accept => true,
+            Verdict::Drop => false,
+            Verdict::Reject => {
+                self.firewall_reject(ifid, pkt);
+                false
+            }
+        }
+    }
+
+    /// Answers a rejected packet received on the given interface.
+    pub(crate) fn firewall_reject(&mut self, ifid: IfId, pkt: IpPacketRef) {
+        let (proto, content) = match pkt {
+            IpPacketRef::V4(ip) => (ip.proto, &ip.content),
+            IpPacketRef::V6(ip) => (ip.next_header, &ip.content),
+        };
+
+        if proto == PROTO_TCP {
+            let Ok(tcp) = TcpPacket::from_slice(content) else {
+                return;
+            };
+            // Resets are never answered
+            if tcp.flags.rst {
+                return;
+            }
+
+            let rst = if tcp.flags.syn && !tcp.flags.ack {
+                TcpPacket::rst_for_syn(&tcp)
+            } else {
+                rst_for(&tcp)
+            };
+            let rst = pkt.response(rst.to_vec().expect("Failed to parse TCP"));
+            let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), rst, true);
+            return;
+        }
+
+        if let IpPacketRef::V4(ip) = pkt {
+            // Errors are never send in response to ICMP errors,
+            // or to broadcasts and multicasts (RFC 1122)
+            let error = ip.proto == PROTO_ICMP && ip.content.first().is_some_and(|t| *t != 8);
+            if error || self.icmp_error_forbidden(ip) {
+                return;
+            }
+        }
+        self.icmp_port_unreachable(ifid, pkt);
+    }
+}
+
+/// Creates a RST in response to a segment, as specified by RFC 9293.
+fn rst_for(tcp: &TcpPacket) -> TcpPacket {
+    let (seq_no, ack_no, ack) = if tcp.flags.ack {
+        (tcp.ack_no, 0, false)
+    } else {
+        let len = tcp.content.len() as u32 + u32::from(tcp.flags.syn) + u32::from(tcp.flags.fin);
+        (0, tcp.seq_no.wrapping_add(len), true)
+    };
+    TcpPacket {
+        src_port: tcp.dest_port,
+        dest_port: tcp.src_port,
+        seq_no,
+        ack_no,
+        flags: TcpFlags::new().rst(true).ack(ack),
+        window: 0,
+        urgent_ptr: 0,
+        options: Vec::new(),
+        content: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::InterfaceName;
+    use inet_types::{
+        ip::{IpPacket, Ipv4Packet},
+        udp::PROTO_UDP,
+    };
+    use std::net::Ipv4Addr;
+
+    fn tcp(dest_port: u16, flags: TcpFlags) -> IpPacket {
+        let tcp = TcpPacket {
+            src_port: 4000,
+            dest_port,
+            seq_no: 0,
+            ack_no: 0,
+            flags,
+            window: 0,
+            urgent_ptr: 0,
+            options: Vec::new(),
+            content: Vec::new(),
+        };
+        let mut ip = IpPacket::new(
+            Ipv4Addr::new(10, 0, 1, 2).into(),
+            Ipv4Addr::new(10, 0, 2, 2).into(),
+            tcp.to_vec().unwrap(),
+        );
+        if let IpPacket::V4(ip) = &mut ip {
+            ip.proto = PROTO_TCP;
+        }
+        ip
+    }
+
+    fn filter(fw: &mut Firewall, chain: FirewallChain, pkt: &IpPacket, iif: &str) -> Verdict {
+        let iif = InterfaceName::new(iif);
+        let IpPacket::V4(pkt) = pkt else {
+            unreachable!()
+        };
+        fw.filter(
+            chain,
+            &PacketMeta::new(&IpPacketRef::V4(pkt), Some(&iif), None),
+        )
+    }
+
+    #[test]
+    fn rule_evaluation_order() {
+        let mut fw = Firewall::new();
+        let chain = fw.chain(FirewallChain::Input);
+        chain.policy = FirewallAction::Drop;
+        for rule in [
+            FirewallRule::new(FirewallAction::Log("in: ".to_string())),
+            FirewallRule {
+                proto: Some(PROTO_TCP),
+                dest_ports: Some(80..=89),
+                iif: Some(InterfaceName::new("en0")),
+                ..FirewallRule::new(FirewallAction::Accept)
+            },
+            FirewallRule {
+                proto: Some(PROTO_TCP),
+                ..FirewallRule::new(FirewallAction::Reject)
+            },
+        ] {
+            chain.rules.push(FirewallCounter {
+                rule,
+                packets: 0,
+                bytes: 0,
+            });
+        }
+
+        let syn = TcpFlags::new().syn(true);
+        let input = FirewallChain::Input;
+        assert_eq!(
+            filter(&mut fw, input, &tcp(80, syn), "en0"),
+            Verdict::Accept
+        );
+        assert_eq!(
+            filter(&mut fw, input, &tcp(89, syn), "en1"),
+            Verdict::Reject
+        );
+        assert_eq!(
+            filter(&mut fw, input, &tcp(90, syn), "en0"),
+            Verdict::Reject
+        );
+
+        let mut udp = tcp(80, syn);
+        if let IpPacket::V4(ip) = &mut udp {
+            ip.proto = PROTO_UDP;
+        }
+        assert_eq!(filter(&mut fw, input, &udp, "en0"), Verdict::Drop);
+
+        // Other chains are unaffected
+        let output = FirewallChain::Output;
+        assert_eq!(filter(&mut fw, output, &udp, "en0"), Verdict::Accept);
+
+        let counters = fw
+            .chain(input)
+            .rules
+            .iter()
+            .map(|c| (c.packets, c.bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(counters, [(4, 4 * 40), (1, 40), (2, 80)]);
+    }
+
+    #[test]
+    fn tcp_flags_and_fragments() {
+        let rule = FirewallRule {
+            proto: Some(PROTO_TCP),
+            dest_ports: Some(22..=22),
+            tcp_flags: Some((
+                TcpFlags::new().syn(true).ack(true),
+                TcpFlags::new().syn(true),
+            )),
+            ..FirewallRule::new(FirewallAction::Drop)
+        };
+        let matches = |pkt: &IpPacket| {
+            let IpPacket::V4(pkt) = pkt else {
+                unreachable!()
+            };
+            rule.matches(&PacketMeta::new(&IpPacketRef::V4(pkt), None, None))
+        };
+
+        assert!(matches(&tcp(22, TcpFlags::new().syn(true))));
+        assert!(!matches(&tcp(22, TcpFlags::new().syn(true).ack(true))));
+        assert!(!matches(&tcp(22, TcpFlags::new().ack(true))));
+        assert!(!matches(&tcp(23, TcpFlags::new().syn(true))));
+
+        // Non-first fragments carry no transport header
+        let mut fragment = tcp(22, TcpFlags::new().syn(true));
+        if let IpPacket::V4(Ipv4Packet {
+            fragment_offset, ..
+        }) = &mut fragment
+        {
+            *fragment_offset = 185;
+        }
+        assert!(!matches(&fragment));
+    }
+
+    #[test]
+    fn rst_sequence_numbers() {
+        let mut segment = TcpPacket {
+            src_port: 4000,
+            dest_port: 80,
+            seq_no: 100,
+            ack_no: 500,
+            flags: TcpFlags::new().ack(true).psh(true),
+            window: 0,
+            urgent_ptr: 0,
+            options: Vec::new(),
+            content: vec![0; 10],
+        };
+        let rst = rst_for(&segment);
+        assert_eq!((rst.seq_no, rst.ack_no), (500, 0));
+        assert!(rst.flags.rst && !rst.flags.ack);
+
+        // Without an ACK, the segment length is acknowledged
+        segment.flags = TcpFlags::new().fin(true);
+        let rst = rst_for(&segment);
+        assert_eq!((rst.seq_no, rst.ack_no), (0, 111));
+        assert!(rst.flags.rst && rst.flags.ack);
+        assert_eq!((rst.src_port, rst.dest_port), (80, 4000));
+    }
+
+    #[test]
+    fn rule_validation() {
+        let ports = FirewallRule {
+            dest_ports: Some(80..=80),
+            ..FirewallRule::new(FirewallAction::Accept)
+        };
+        assert!(ports.validate(FirewallChain::Input).is_err());
+
+        let oif = FirewallRule {
+            oif: Some(InterfaceName::new("en0")),
+            ..FirewallRule::new(FirewallAction::Accept)
+        };
+        assert!(oif.validate(FirewallChain::Input).is_err());
+        assert!(oif.validate(FirewallChain::Forward).is_ok());
+
+        let mixed = FirewallRule {
+            src: Some((
+                Ipv4Addr::new(10, 0, 0, 0).into(),
+                std::net::Ipv6Addr::UNSPECIFIED.into(),
+            )),
+            ..FirewallRule::new(FirewallAction::Accept)
+        };
+        assert!(mixed.validate(FirewallChain::Input).is_err());
+
+        let reject = FirewallRule::new(FirewallAction::Reject);
+        assert!(reject.validate(FirewallChain::Forward).is_ok());
+        assert!(reject.validate(FirewallChain::Prerouting).is_err());
+        assert!(reject.validate(FirewallChain::Postrouting).is_err());
+    }
+}
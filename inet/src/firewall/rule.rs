@@ -0,0 +1,371 @@
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind, Result},
+    net::IpAddr,
+    ops::RangeInclusive,
+};
+
+use inet_types::{
+    ip::{ipv4_matches_subnet, ipv6_matches_subnet, IpPacketRef},
+    tcp::{TcpFlags, PROTO_TCP},
+    udp::PROTO_UDP,
+};
+
+use crate::interface::InterfaceName;
+
+/// The points in the networking layer, at which packets are filtered.
+///
+/// Received packets traverse `Prerouting`, followed by either `Input`
+/// for packets addressed to the local node, or `Forward` for routed packets.
+/// Locally generated packets traverse `Output`. All outgoing packets,
+/// both forwarded and locally generated, traverse `Postrouting` last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FirewallChain {
+    /// All received packets, before the routing decision.
+    Prerouting,
+    /// Received packets addressed to the local node.
+    Input,
+    /// Received packets that are routed to another node.
+    Forward,
+    /// Locally generated packets, after the routing decision.
+    Output,
+    /// All outgoing packets, before they are send onto the link.
+    Postrouting,
+}
+
+impl FirewallChain {
+    pub(super) const ALL: [FirewallChain; 5] = [
+        FirewallChain::Prerouting,
+        FirewallChain::Input,
+        FirewallChain::Forward,
+        FirewallChain::Output,
+        FirewallChain::Postrouting,
+    ];
+
+    fn has_iif(self) -> bool {
+        !matches!(self, FirewallChain::Output | FirewallChain::Postrouting)
+    }
+
+    fn has_oif(self) -> bool {
+        !matches!(self, FirewallChain::Prerouting | FirewallChain::Input)
+    }
+}
+
+impl Display for FirewallChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Prerouting => write!(f, "PREROUTING"),
+            Self::Input => write!(f, "INPUT"),
+            Self::Forward => write!(f, "FORWARD"),
+            Self::Output => write!(f, "OUTPUT"),
+            Self::Postrouting => write!(f, "POSTROUTING"),
+        }
+    }
+}
+
+/// The action applied to packets matching a firewall rule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FirewallAction {
+    /// The packet passes the chain, no further rules are evaluated.
+    Accept,
+    /// The packet is discarded silently.
+    Drop,
+    /// The packet is discarded, and the sender is notified. TCP segments
+    /// are answered with a RST, all other packets with an ICMP port
+    /// unreachable error. Locally generated packets fail to send,
+    /// aborting TCP connections.
+    /// Only valid in `Input`, `Forward` and `Output`.
+    Reject,
+    /// The packet is logged with the given prefix, and evaluation
+    /// continues with the next rule.
+    Log(String),
+}
+
+impl Display for FirewallAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Accept => write!(f, "ACCEPT"),
+            Self::Drop => write!(f, "DROP"),
+            Self::Reject => write!(f, "REJECT"),
+            Self::Log(prefix) => write!(f, "LOG prefix {prefix:?}"),
+        }
+    }
+}
+
+/// A firewall rule, that applies an action to matching packets.
+///
+/// A rule matches a packet, if all of its selectors match. Selectors
+/// that are `None` match all packets. Rules are evaluated in the order
+/// they were added to a chain, until a rule accepts, drops or
+/// rejects the packet. Should no rule do so, the policy of the chain
+/// is applied.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FirewallRule {
+    /// The source subnet and its netmask.
+    pub src: Option<(IpAddr, IpAddr)>,
+    /// The destination subnet and its netmask.
+    pub dest: Option<(IpAddr, IpAddr)>,
+    /// The transport protocol, like `PROTO_TCP`.
+    pub proto: Option<u8>,
+    /// The range of source ports. Requires either TCP or UDP as `proto`.
+    pub src_ports: Option<RangeInclusive<u16>>,
+    /// The range of destination ports. Requires either TCP or UDP as `proto`.
+    pub dest_ports: Option<RangeInclusive<u16>>,
+    /// The interface a packet was received on. Not available
+    /// in `Output` and `Postrouting`.
+    pub iif: Option<InterfaceName>,
+    /// The interface a packet will be send on. Not available
+    /// in `Prerouting` and `Input`.
+    pub oif: Option<InterfaceName>,
+    /// The TCP flags, given as a mask of the examined flags and the flags
+    /// that must be set, like `--tcp-flags` of iptables. Requires TCP as `proto`.
+    pub tcp_flags: Option<(TcpFlags, TcpFlags)>,
+    /// The action applied to matching packets.
+    pub action: FirewallAction,
+}
+
+impl FirewallRule {
+    /// Creates a new rule, that applies the action to all packets.
+    pub fn new(action: FirewallAction) -> Self {
+        Self {
+            src: None,
+            dest: None,
+            proto: None,
+            src_ports: None,
+            dest_ports: None,
+            iif: None,
+            oif: None,
+            tcp_flags: None,
+            action,
+        }
+    }
+
+    pub(super) fn validate(&self, chain: FirewallChain) -> Result<()> {
+        for (addr, mask) in self.src.iter().chain(&self.dest) {
+            if addr.is_ipv4() != mask.is_ipv4() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "subnet and mask must be of the same address family",
+                ));
+            }
+        }
+        if let (Some((src, _)), Some((dest, _))) = (self.src, self.dest) {
+            if src.is_ipv4() != dest.is_ipv4() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "source and destination must be of the same address family",
+                ));
+            }
+        }
+
+        let ports = self.src_ports.is_some() || self.dest_ports.is_some();
+        if ports && !matches!(self.proto, Some(PROTO_TCP | PROTO_UDP)) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "port selectors require either TCP or UDP as protocol",
+            ));
+        }
+        if self.tcp_flags.is_some() && self.proto != Some(PROTO_TCP) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "tcp flag selectors require TCP as protocol",
+            ));
+        }
+
+        if self.iif.is_some() && !chain.has_iif() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("ingress interfaces cannot be matched in {chain}"),
+            ));
+        }
+        if self.oif.is_some() && !chain.has_oif() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("egress interfaces cannot be matched in {chain}"),
+            ));
+        }
+        let routing = matches!(
+            chain,
+            FirewallChain::Prerouting | FirewallChain::Postrouting
+        );
+        if self.action == FirewallAction::Reject && routing {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("packets cannot be rejected in {chain}"),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) fn matches(&self, pkt: &PacketMeta) -> bool {
+        let src = self
+            .src
+            .is_none_or(|(subnet, mask)| matches_subnet(pkt.src, subnet, mask));
+        let dest = self
+            .dest
+            .is_none_or(|(subnet, mask)| matches_subnet(pkt.dest, subnet, mask));
+        let iif = self
+            .iif
+            .as_ref()
+            .is_none_or(|iif| pkt.iif.is_some_and(|i| i.id == iif.id));
+        let oif = self
+            .oif
+            .as_ref()
+            .is_none_or(|oif| pkt.oif.is_some_and(|o| o.id == oif.id));
+
+        // Packets without ports, like fragments, never match port selectors
+        let src_ports = self
+            .src_ports
+            .as_ref()
+            .is_none_or(|r| pkt.ports.is_some_and(|(port, _)| r.contains(&port)));
+        let dest_ports = self
+            .dest_ports
+            .as_ref()
+            .is_none_or(|r| pkt.ports.is_some_and(|(_, port)| r.contains(&port)));
+        let tcp_flags = self.tcp_flags.is_none_or(|(mask, set)| {
+            let mask = flag_bits(mask);
+            pkt.tcp_flags
+                .is_some_and(|flags| flags & mask == flag_bits(set) & mask)
+        });
+
+        src && dest
+            && iif
+            && oif
+            && self.proto.is_none_or(|proto| proto == pkt.proto)
+            && src_ports
+            && dest_ports
+            && tcp_flags
+    }
+}
+
+impl Display for FirewallRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((subnet, mask)) = self.src {
+            write!(f, "src {subnet} ({mask}) ")?;
+        }
+        if let Some((subnet, mask)) = self.dest {
+            write!(f, "dst {subnet} ({mask}) ")?;
+        }
+        if let Some(proto) = self.proto {
+            write!(f, "proto {proto} ")?;
+        }
+        if let Some(ports) = &self.src_ports {
+            write!(f, "sport {}:{} ", ports.start(), ports.end())?;
+        }
+        if let Some(ports) = &self.dest_ports {
+            write!(f, "dport {}:{} ", ports.start(), ports.end())?;
+        }
+        if let Some(iif) = &self.iif {
+            write!(f, "iif {iif} ")?;
+        }
+        if let Some(oif) = &self.oif {
+            write!(f, "oif {oif} ")?;
+        }
+        if let Some((mask, set)) = self.tcp_flags {
+            write!(f, "flags {set}/{mask} ")?;
+        }
+        write!(f, "{}", self.action)
+    }
+}
+
+/// A firewall rule, and the packets it matched so far.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FirewallCounter {
+    /// The rule.
+    pub rule: FirewallRule,
+    /// The number of packets matched by the rule.
+    pub packets: u64,
+    /// The number of bytes matched by the rule, including the IP header.
+    pub bytes: u64,
+}
+
+/// The properties of a packet, that firewall rules may select on.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PacketMeta<'a> {
+    pub src: IpAddr,
+    pub dest: IpAddr,
+    pub proto: u8,
+    pub ports: Option<(u16, u16)>,
+    pub tcp_flags: Option<u8>,
+    pub iif: Option<&'a InterfaceName>,
+    pub oif: Option<&'a InterfaceName>,
+    pub len: usize,
+}
+
+impl<'a> PacketMeta<'a> {
+    pub(crate) fn new(
+        pkt: &IpPacketRef,
+        iif: Option<&'a InterfaceName>,
+        oif: Option<&'a InterfaceName>,
+    ) -> Self {
+        let (proto, first, len) = match pkt {
+            IpPacketRef::V4(pkt) => (pkt.proto, pkt.fragment_offset == 0, 20),
+            IpPacketRef::V6(pkt) => (pkt.next_header, true, 40),
+        };
+        let content = pkt.content();
+
+        // Only the first fragment contains the transport header
+        let ports = match proto {
+            PROTO_TCP | PROTO_UDP if first && content.len() >= 4 => Some((
+                u16::from_be_bytes([content[0], content[1]]),
+                u16::from_be_bytes([content[2], content[3]]),
+            )),
+            _ => None,
+        };
+        let tcp_flags = match proto {
+            PROTO_TCP if first => content.get(13).copied(),
+            _ => None,
+        };
+
+        Self {
+            src: pkt.src(),
+            dest: pkt.dest(),
+            proto,
+            ports,
+            tcp_flags,
+            iif,
+            oif,
+            len: len + content.len(),
+        }
+    }
+}
+
+impl Display for PacketMeta<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |iface: Option<&InterfaceName>| iface.map(|i| i.name.clone());
+        write!(
+            f,
+            "IN={} OUT={} SRC={} DST={} LEN={} PROTO={}",
+            name(self.iif).unwrap_or_default(),
+            name(self.oif).unwrap_or_default(),
+            self.src,
+            self.dest,
+            self.len,
+            self.proto
+        )?;
+        if let Some((src, dest)) = self.ports {
+            write!(f, " SPT={src} DPT={dest}")?;
+        }
+        Ok(())
+    }
+}
+
+fn matches_subnet(ip: IpAddr, subnet: IpAddr, mask: IpAddr) -> bool {
+    match (ip, subnet, mask) {
+        (IpAddr::V4(ip), IpAddr::V4(subnet), IpAddr::V4(mask)) => {
+            ipv4_matches_subnet(ip, subnet, mask)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(subnet), IpAddr::V6(mask)) => {
+            ipv6_matches_subnet(ip, subnet, mask)
+        }
+        _ => false,
+    }
+}
+
+fn flag_bits(flags: TcpFlags) -> u8 {
+    [
+        flags.cwr, flags.ece, flags.urg, flags.ack, flags.psh, flags.rst, flags.syn, flags.fin,
+    ]
+    .into_iter()
+    .fold(0, |bits, flag| bits << 1 | u8::from(flag))
+}
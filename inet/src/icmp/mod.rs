@@ -7,7 +7,10 @@
 //! represent realtity.
 //!
 //! This module provides some ICMP associated
-//! utility function for network debugging.
+//! utility function for network debugging, that work for
+//! both IPv4 and IPv6 targets. ICMPv6 echo requests are
+//! answered as well, while the ICMPv6 messages of the
+//! Neighbor Discovery Protocol are handled by `ndp`.
 use fxhash::{FxBuildHasher, FxHashMap};
 use std::{
     io::{Error, ErrorKind},
@@ -24,7 +27,12 @@ use inet_types::{
 };
 
 use self::ping::PingCB;
-use crate::{interface::IfId, socket::SocketIfaceBinding, IOContext};
+use crate::{
+    interface::{IfId, InterfaceAddr},
+    ip::FragmentationNeeded,
+    socket::SocketIfaceBinding,
+    IOContext,
+};
 
 mod ping;
 pub use self::ping::*;
@@ -32,9 +40,11 @@ pub use self::ping::*;
 mod traceroute;
 pub use self::traceroute::*;
 
+mod v6;
+
 pub(crate) struct Icmp {
     pings: FxHashMap<u16, PingCB>,
-    traceroutes: FxHashMap<Ipv4Addr, TracerouteCB>,
+    traceroutes: FxHashMap<IpAddr, TracerouteCB>,
 }
 
 impl Icmp {
@@ -83,35 +93,32 @@ impl IOContext {
                     dest: ip_icmp.src,
                     content: icmp.to_vec().expect("Failed to parse ICMP"),
                 };
-                self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true)
-                    .expect("Failed to send");
+                let _ =
+                    self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true);
             }
             IcmpType::EchoReply {
                 identifier,
                 sequence,
             } => {
-                let Some(ping) = self.icmp.pings.get_mut(&identifier) else {
-                    tracing::warn!("missguided icmp echo reply");
-                    return false;
-                };
-
-                assert_eq!(ping.addr, ip_icmp.src);
-
-                let more = ping.recv_echo_reply(identifier, sequence);
-                if more {
-                    ping.current_seq_no += 1;
-                    self.icmp_send_ping(ip_icmp.src, identifier, sequence + 1)
-                } else {
-                    self.icmp.pings.remove(&identifier);
-                }
+                let src = IpAddr::V4(ip_icmp.src);
+                return self.icmp_recv_echo_reply(src, ip_icmp.ttl, identifier, sequence);
             }
             IcmpType::DestinationUnreachable { next_hop_mtu, code } => {
                 let ip = pkt.contained();
                 let unreachable = ip.dest;
 
+                // The packet was to big for the next hop, update the path MTU
+                if code == IcmpDestinationUnreachableCode::DatagramToBig {
+                    self.path_mtu_update(IpAddr::V4(unreachable), next_hop_mtu);
+                    return true;
+                }
+
                 // (0) check for recent pings
-                if let Some((ident, ping)) =
-                    self.icmp.pings.iter_mut().find(|p| p.1.addr == unreachable)
+                if let Some((ident, ping)) = self
+                    .icmp
+                    .pings
+                    .iter_mut()
+                    .find(|p| p.1.addr == IpAddr::V4(unreachable))
                 {
                     ping.publish.take().map(|s| {
                         s.send(Err(Error::new(
@@ -142,23 +149,21 @@ impl IOContext {
                         (AF_INET, SOCK_DGRAM) => self.udp_icmp_error(
                             *fd,
                             Error::new(ErrorKind::ConnectionRefused, format!("{code:?}")),
-                            ip,
+                            IpAddr::V4(unreachable),
                         ),
                         _ => todo!(),
                     }
 
                     return true;
                 }
-
-                let _ = next_hop_mtu;
             }
             IcmpType::TimeExceeded { code } => {
                 let ip = pkt.contained();
                 let unreachable = ip.dest;
 
-                if let Some(trace) = self.icmp.traceroutes.get_mut(&unreachable) {
+                if let Some(trace) = self.icmp.traceroutes.get_mut(&IpAddr::V4(unreachable)) {
                     let dur = SimTime::now() - trace.last_send;
-                    let _ = trace.recent_err.replace((ip_icmp.src, dur));
+                    let _ = trace.recent_err.replace((IpAddr::V4(ip_icmp.src), dur));
                     // Contimue to let UDP socket handlers forward the error
                 }
 
@@ -177,7 +182,7 @@ impl IOContext {
                             self.udp_icmp_error(
                                 *fd,
                                 Error::new(ErrorKind::Other, format!("{code:?}")),
-                                ip,
+                                IpAddr::V4(unreachable),
                             );
                         }
                         _ => todo!(),
@@ -195,6 +200,11 @@ impl IOContext {
     }
 
     pub(super) fn icmp_routing_failed(&mut self, e: Error, pkt: &Ipv4Packet) {
+        if let Some(FragmentationNeeded { mtu }) = FragmentationNeeded::from_io(&e) {
+            self.icmp_fragmentation_needed(mtu, pkt);
+            return;
+        }
+
         match e.kind() {
             ErrorKind::ConnectionRefused => {
                 // Gateway error
@@ -211,8 +221,7 @@ impl IOContext {
                 ip.proto = PROTO_ICMP;
                 ip.content = icmp.to_vec().expect("Failed to parse ICMP");
 
-                self.send_ip_packet(SocketIfaceBinding::NotBound, IpPacket::V4(ip), true)
-                    .unwrap()
+                let _ = self.send_ip_packet(SocketIfaceBinding::NotBound, IpPacket::V4(ip), true);
             }
             ErrorKind::NotConnected => {
                 // Gateway error
@@ -235,6 +244,37 @@ impl IOContext {
         }
     }
 
+    fn icmp_fragmentation_needed(&mut self, mtu: u16, pkt: &Ipv4Packet) {
+        let icmp = IcmpPacket::new(
+            IcmpType::DestinationUnreachable {
+                next_hop_mtu: mtu,
+                code: IcmpDestinationUnreachableCode::DatagramToBig,
+            },
+            pkt,
+        );
+
+        let mut ip = pkt.reverse();
+        ip.src = Ipv4Addr::UNSPECIFIED;
+        ip.proto = PROTO_ICMP;
+        ip.content = icmp.to_vec().expect("Failed to parse ICMP");
+
+        let _ = self.send_ip_packet(SocketIfaceBinding::NotBound, IpPacket::V4(ip), true);
+    }
+
+    pub(super) fn icmp_reassembly_timeout(&mut self, ifid: IfId, pkt: &Ipv4Packet) {
+        let icmp = IcmpPacket::new(
+            IcmpType::TimeExceeded {
+                code: IcmpTimeExceededCode::FragmentReassemblyTimeExceeded,
+            },
+            pkt,
+        );
+        let mut ip = pkt.reverse();
+        ip.src = Ipv4Addr::UNSPECIFIED;
+        ip.proto = PROTO_ICMP;
+        ip.content = icmp.to_vec().expect("Failed to parse ICMP");
+        let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true);
+    }
+
     pub(super) fn icmp_ttl_expired(&mut self, ifid: IfId, pkt: &Ipv4Packet) {
         let icmp = IcmpPacket::new(
             IcmpType::TimeExceeded {
@@ -246,11 +286,13 @@ impl IOContext {
         ip.src = Ipv4Addr::UNSPECIFIED;
         ip.proto = PROTO_ICMP;
         ip.content = icmp.to_vec().expect("Failed to parse ICMP");
-        self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true)
-            .unwrap();
+        let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true);
     }
 
     pub(super) fn icmp_port_unreachable(&mut self, ifid: IfId, pkt: IpPacketRef) {
+        if let IpPacketRef::V6(pkt) = pkt {
+            self.icmpv6_port_unreachable(ifid, pkt);
+        }
         if let IpPacketRef::V4(pkt) = pkt {
             let icmp = IcmpPacket::new(
                 IcmpType::DestinationUnreachable {
@@ -263,8 +305,52 @@ impl IOContext {
             ip.src = Ipv4Addr::UNSPECIFIED;
             ip.proto = PROTO_ICMP;
             ip.content = icmp.to_vec().expect("Failed to parse ICMP");
-            self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true)
-                .unwrap();
+            let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true);
         }
     }
+
+    /// Whether ICMP errors must not be send in response to a packet, because
+    /// it was addressed to a group of hosts, or its source is unknown (RFC 1122).
+    /// Directed broadcasts are detected for all attached subnets, since
+    /// forwarded packets may be addressed to any of them.
+    pub(crate) fn icmp_error_forbidden(&self, pkt: &Ipv4Packet) -> bool {
+        let directed_broadcast = self.ifaces.values().any(|iface| {
+            iface.addrs.iter().any(|addr| match addr {
+                // Point-to-point subnets have no broadcast address (RFC 3021)
+                InterfaceAddr::Inet { addr, netmask } if u32::from(*netmask).count_ones() < 31 => {
+                    pkt.dest == (*addr | !*netmask)
+                }
+                _ => false,
+            })
+        });
+        pkt.dest.is_broadcast()
+            || pkt.dest.is_multicast()
+            || directed_broadcast
+            || pkt.src.is_unspecified()
+    }
+
+    /// Reports a packet, whose transport protocol is not supported.
+    pub(crate) fn icmp_protocol_unreachable(&mut self, ifid: IfId, pkt: IpPacketRef) {
+        let pkt = match pkt {
+            IpPacketRef::V4(pkt) => pkt,
+            IpPacketRef::V6(pkt) => return self.icmpv6_protocol_unreachable(ifid, pkt),
+        };
+
+        if self.icmp_error_forbidden(pkt) {
+            return;
+        }
+
+        let icmp = IcmpPacket::new(
+            IcmpType::DestinationUnreachable {
+                next_hop_mtu: 0,
+                code: IcmpDestinationUnreachableCode::ProtocolUnreachable,
+            },
+            pkt,
+        );
+        let mut ip = pkt.reverse();
+        ip.src = Ipv4Addr::UNSPECIFIED;
+        ip.proto = PROTO_ICMP;
+        ip.content = icmp.to_vec().expect("Failed to parse ICMP");
+        let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V4(ip), true);
+    }
 }
@@ -1,9 +1,10 @@
 use bytepack::ToBytestream;
 use des::prelude::*;
 use inet_types::icmp::{IcmpPacket, IcmpType, PROTO_ICMP};
-use inet_types::ip::{IpPacket, Ipv4Flags, Ipv4Packet};
+use inet_types::icmpv6::{Icmpv6Packet, PROTO_ICMPV6};
+use inet_types::ip::{IpPacket, Ipv4Flags, Ipv4Packet, Ipv6Packet};
 use std::io::{Error, ErrorKind, Result};
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::time::Duration;
 use tokio::sync::oneshot;
 
@@ -11,7 +12,7 @@ use crate::socket::SocketIfaceBinding;
 use crate::IOContext;
 
 pub(super) struct PingCB {
-    pub addr: Ipv4Addr,
+    pub addr: IpAddr,
     pub values: Vec<Duration>,
     pub identifier: u16,
     pub current_seq_no: u16,
@@ -25,7 +26,7 @@ pub(super) struct PingCB {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Ping {
     /// The received time-to-live (TTL) of
-    /// returing ICMP Echo Replys
+    /// returing ICMP Echo Replys, or the hop limit for IPv6.
     pub ttl: u32,
     /// The fastest measured round-trip-time (RTT).
     pub time_min: Duration,
@@ -37,7 +38,10 @@ pub struct Ping {
 
 /// Tries to determine reachability and round-trip time
 /// to a specified target
-pub async fn ping(addr: impl Into<Ipv4Addr>) -> Result<Ping> {
+///
+/// IPv4 targets are probed using ICMP Echo Requests,
+/// IPv6 targets using ICMPv6 Echo Requests.
+pub async fn ping(addr: impl Into<IpAddr>) -> Result<Ping> {
     ping_with(addr, 3).await
 }
 
@@ -46,7 +50,7 @@ pub async fn ping(addr: impl Into<Ipv4Addr>) -> Result<Ping> {
 ///
 /// This function takes the number of samples as an extra paramter.
 /// The default value used by `ping` is 3.
-pub async fn ping_with(addr: impl Into<Ipv4Addr>, c: usize) -> Result<Ping> {
+pub async fn ping_with(addr: impl Into<IpAddr>, c: usize) -> Result<Ping> {
     let addr = addr.into();
     let rx = IOContext::failable_api(|ctx| ctx.icmp_initiate_ping(addr, c))?;
     rx.await
@@ -56,9 +60,18 @@ pub async fn ping_with(addr: impl Into<Ipv4Addr>, c: usize) -> Result<Ping> {
 impl IOContext {
     fn icmp_initiate_ping(
         &mut self,
-        addr: Ipv4Addr,
+        addr: IpAddr,
         c: usize,
     ) -> Result<oneshot::Receiver<Result<Ping>>> {
+        // Replies to group addresses originate from the individual members
+        let broadcast = matches!(addr, IpAddr::V4(addr) if addr.is_broadcast());
+        if addr.is_multicast() || broadcast {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "cannot ping multicast or broadcast addresses",
+            ));
+        }
+
         let (tx, rx) = oneshot::channel();
         let identifier = random();
         self.icmp.pings.insert(
@@ -74,46 +87,110 @@ impl IOContext {
             },
         );
 
-        self.icmp_send_ping(addr, identifier, 0);
+        if let Err(e) = self.icmp_send_ping(addr, identifier, 0) {
+            self.icmp.pings.remove(&identifier);
+            return Err(e);
+        }
         Ok(rx)
     }
 
-    pub(super) fn icmp_send_ping(&mut self, addr: Ipv4Addr, identifier: u16, sequence: u16) {
-        let mut ip = Ipv4Packet {
-            enc: 0,
-            dscp: 0,
-            identification: 0,
-            flags: Ipv4Flags {
-                df: true,
-                mf: false,
-            },
-            fragment_offset: 0,
-            ttl: 32,
-            proto: PROTO_ICMP,
-            src: Ipv4Addr::UNSPECIFIED,
-            dest: addr,
-            content: vec![0; 36],
+    pub(super) fn icmp_send_ping(
+        &mut self,
+        addr: IpAddr,
+        identifier: u16,
+        sequence: u16,
+    ) -> Result<()> {
+        let ip = match addr {
+            IpAddr::V4(addr) => IpPacket::V4(echo_request_v4(addr, identifier, sequence)),
+            IpAddr::V6(addr) => IpPacket::V6(echo_request_v6(addr, identifier, sequence)),
         };
-        let icmp = IcmpPacket::new(
-            IcmpType::EchoRequest {
-                identifier,
-                sequence,
-            },
-            &ip,
-        );
-        ip.content = icmp.to_vec().expect("Failed to parse ICMP");
 
         self.send_ip_packet(
             SocketIfaceBinding::Any(self.ifaces.keys().copied().collect::<Vec<_>>()),
-            IpPacket::V4(ip),
+            ip,
             true,
         )
-        .expect("Failed to send");
+    }
+
+    /// Handles an echo reply for both ICMP and ICMPv6, returning
+    /// whether the reply belonged to a ping.
+    pub(super) fn icmp_recv_echo_reply(
+        &mut self,
+        src: IpAddr,
+        ttl: u8,
+        identifier: u16,
+        sequence: u16,
+    ) -> bool {
+        let Some(ping) = self.icmp.pings.get_mut(&identifier) else {
+            tracing::warn!("missguided icmp echo reply");
+            return false;
+        };
+
+        if ping.addr != src {
+            tracing::warn!("icmp echo reply from unexpected source {src}");
+            return false;
+        }
+
+        let more = ping.recv_echo_reply(identifier, sequence, ttl);
+        if more {
+            ping.current_seq_no += 1;
+            if let Err(e) = self.icmp_send_ping(src, identifier, sequence + 1) {
+                let ping = self.icmp.pings.remove(&identifier);
+                ping.and_then(|p| p.publish).map(|s| s.send(Err(e)));
+            }
+        } else {
+            self.icmp.pings.remove(&identifier);
+        }
+        true
+    }
+}
+
+fn echo_request_v4(addr: Ipv4Addr, identifier: u16, sequence: u16) -> Ipv4Packet {
+    let mut ip = Ipv4Packet {
+        enc: 0,
+        dscp: 0,
+        identification: 0,
+        flags: Ipv4Flags {
+            df: true,
+            mf: false,
+        },
+        fragment_offset: 0,
+        ttl: 32,
+        proto: PROTO_ICMP,
+        src: Ipv4Addr::UNSPECIFIED,
+        dest: addr,
+        content: vec![0; 36],
+    };
+    let icmp = IcmpPacket::new(
+        IcmpType::EchoRequest {
+            identifier,
+            sequence,
+        },
+        &ip,
+    );
+    ip.content = icmp.to_vec().expect("Failed to parse ICMP");
+    ip
+}
+
+fn echo_request_v6(addr: Ipv6Addr, identifier: u16, sequence: u16) -> Ipv6Packet {
+    let icmp = Icmpv6Packet::EchoRequest {
+        identifier,
+        sequence,
+        data: vec![0; 32],
+    };
+    Ipv6Packet {
+        traffic_class: 0,
+        flow_label: 0,
+        next_header: PROTO_ICMPV6,
+        hop_limit: 32,
+        src: Ipv6Addr::UNSPECIFIED,
+        dest: addr,
+        content: icmp.to_vec().expect("Failed to parse ICMPv6"),
     }
 }
 
 impl PingCB {
-    pub(super) fn recv_echo_reply(&mut self, identifer: u16, sequence: u16) -> bool {
+    pub(super) fn recv_echo_reply(&mut self, identifer: u16, sequence: u16, ttl: u8) -> bool {
         // Check Seq NO;
         assert_eq!(self.identifier, identifer);
         assert_eq!(self.current_seq_no, sequence);
@@ -137,7 +214,7 @@ impl PingCB {
             }
             let time_avg = Duration::from_secs_f64(acc.as_secs_f64() / self.values.len() as f64);
             let ping = Ping {
-                ttl: 0,
+                ttl: u32::from(ttl),
                 time_min: min,
                 time_max: max,
                 time_avg,
@@ -8,23 +8,23 @@ use des::{
 };
 use std::{
     io::{Error, ErrorKind, Result},
-    net::Ipv4Addr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     time::Duration,
 };
 
 #[allow(dead_code)]
 pub(crate) struct TracerouteCB {
     pub fd: Fd,
-    pub target: Ipv4Addr,
+    pub target: IpAddr,
     pub last_send: SimTime,
-    pub recent_err: Option<(Ipv4Addr, Duration)>,
+    pub recent_err: Option<(IpAddr, Duration)>,
 }
 
 /// The result of a call to `traceroute`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Traceroute {
     /// The target of the traced route.
-    pub target: Ipv4Addr,
+    pub target: IpAddr,
     /// A set of nodes identified allong the route to the
     /// target
     pub nodes: Vec<Trace>,
@@ -35,7 +35,7 @@ pub struct Traceroute {
 pub enum Trace {
     /// A node that responded to ICMP Echo Request,
     /// allowing for the computation of a RTT.
-    Found { addr: Ipv4Addr, rtt: Duration },
+    Found { addr: IpAddr, rtt: Duration },
     /// A non-responding node on the route.
     NotFound,
 }
@@ -44,9 +44,15 @@ pub enum Trace {
 ///
 /// This functions tries to determine all memebers of the routing
 /// path to the provided target. Nodes must respond to
-/// ICMP Echo Requests to be identified.
-pub async fn traceroute(addr: Ipv4Addr) -> Result<Traceroute> {
-    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+/// ICMP Echo Requests to be identified. For IPv6 targets, nodes
+/// are identified by ICMPv6 Hop Limit Exceeded messages.
+pub async fn traceroute(addr: impl Into<IpAddr>) -> Result<Traceroute> {
+    let addr = addr.into();
+    let unspecified = match addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
     IOContext::failable_api(|ctx| Ok(ctx.traceroute_create(socket.as_raw_fd(), addr)))?;
 
     let mut port = random::<u16>();
@@ -107,7 +113,7 @@ pub async fn traceroute(addr: Ipv4Addr) -> Result<Traceroute> {
 }
 
 impl IOContext {
-    fn traceroute_create(&mut self, fd: Fd, target: Ipv4Addr) {
+    fn traceroute_create(&mut self, fd: Fd, target: IpAddr) {
         self.icmp.traceroutes.insert(
             target,
             TracerouteCB {
@@ -119,14 +125,14 @@ impl IOContext {
         );
     }
 
-    fn traceroute_register_send(&mut self, target: Ipv4Addr) {
+    fn traceroute_register_send(&mut self, target: IpAddr) {
         let Some(trace) = self.icmp.traceroutes.get_mut(&target) else {
             return
         };
         trace.last_send = SimTime::now();
     }
 
-    fn traceroute_get_error(&mut self, target: Ipv4Addr) -> Option<(Ipv4Addr, Duration)> {
+    fn traceroute_get_error(&mut self, target: IpAddr) -> Option<(IpAddr, Duration)> {
         let Some(trace) = self.icmp.traceroutes.get_mut(&target) else {
             return None;
         };
@@ -0,0 +1,255 @@
+use std::{
+    io::{Error, ErrorKind},
+    net::{IpAddr, Ipv6Addr},
+};
+
+use bytepack::{FromBytestream, ToBytestream};
+use des::time::SimTime;
+use inet_types::{
+    icmpv6::{
+        Icmpv6DestinationUnreachableCode, Icmpv6Packet, Icmpv6ParameterProblemCode,
+        Icmpv6TimeExceededCode, PROTO_ICMPV6,
+    },
+    ip::{IpPacket, Ipv6Packet},
+};
+
+use crate::{
+    interface::IfId,
+    socket::{Fd, SocketDomain, SocketIfaceBinding, SocketType},
+    IOContext,
+};
+
+/// The maximum size of an ICMPv6 error message, so that
+/// it fits into the minimum IPv6 MTU (RFC 4443).
+const MAX_ERROR_LEN: usize = 1280 - 40 - 8;
+
+/// The offset of the next header field in the IPv6 header.
+const NEXT_HEADER_OFFSET: u32 = 6;
+
+impl IOContext {
+    pub(crate) fn recv_icmpv6_packet(&mut self, ip_icmp: &Ipv6Packet, ifid: IfId) -> bool {
+        assert_eq!(ip_icmp.next_header, PROTO_ICMPV6);
+
+        let Ok(pkt) = Icmpv6Packet::read_from_slice(&mut &ip_icmp.content[..]) else {
+            tracing::error!(
+                "received ip-packet with next_header=58 (icmpv6) but content was no icmpv6-packet"
+            );
+            return false;
+        };
+
+        if pkt.is_ndp() {
+            return self.recv_ndp(ifid, ip_icmp, pkt);
+        }
+
+        match pkt {
+            Icmpv6Packet::EchoRequest {
+                identifier,
+                sequence,
+                data,
+            } => {
+                // (0) Respond echo request, using an unicast source address
+                // if the request was send to a multicast group.
+                let src = if ip_icmp.dest.is_multicast() {
+                    Ipv6Addr::UNSPECIFIED
+                } else {
+                    ip_icmp.dest
+                };
+                let icmp = Icmpv6Packet::EchoReply {
+                    identifier,
+                    sequence,
+                    data,
+                };
+                let ip = Ipv6Packet {
+                    traffic_class: ip_icmp.traffic_class,
+                    flow_label: ip_icmp.flow_label,
+                    next_header: PROTO_ICMPV6,
+                    hop_limit: 64,
+                    src,
+                    dest: ip_icmp.src,
+                    content: icmp.to_vec().expect("Failed to parse ICMPv6"),
+                };
+                if let Err(e) =
+                    self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V6(ip), true)
+                {
+                    tracing::error!("failed to send echo reply: {e}");
+                }
+                true
+            }
+            Icmpv6Packet::EchoReply {
+                identifier,
+                sequence,
+                ..
+            } => {
+                let src = IpAddr::V6(ip_icmp.src);
+                self.icmp_recv_echo_reply(src, ip_icmp.hop_limit, identifier, sequence)
+            }
+            Icmpv6Packet::DestinationUnreachable { code, content } => {
+                let Some(unreachable) = contained_dest(&content) else {
+                    return false;
+                };
+                let e = Error::new(ErrorKind::ConnectionRefused, format!("{code:?}"));
+                self.icmpv6_destination_unreachable(unreachable, e)
+            }
+            Icmpv6Packet::PacketTooBig { mtu, content } => {
+                let Some(unreachable) = contained_dest(&content) else {
+                    return false;
+                };
+                let mtu = u16::try_from(mtu).unwrap_or(u16::MAX);
+                self.path_mtu_update(IpAddr::V6(unreachable), mtu);
+                true
+            }
+            Icmpv6Packet::TimeExceeded { code, content } => {
+                let Some(unreachable) = contained_dest(&content) else {
+                    return false;
+                };
+
+                let unreachable = IpAddr::V6(unreachable);
+                if let Some(trace) = self.icmp.traceroutes.get_mut(&unreachable) {
+                    let dur = SimTime::now() - trace.last_send;
+                    let _ = trace.recent_err.replace((IpAddr::V6(ip_icmp.src), dur));
+                }
+
+                // Forward the error to UDP sockets, to notify traceroutes
+                if let Some(fd) = self.icmpv6_socket_for(unreachable, SocketType::SOCK_DGRAM) {
+                    let e = Error::new(ErrorKind::TimedOut, format!("{code:?}"));
+                    self.udp_icmp_error(fd, e, unreachable);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn icmpv6_destination_unreachable(&mut self, unreachable: Ipv6Addr, e: Error) -> bool {
+        let unreachable = IpAddr::V6(unreachable);
+
+        // (0) check for recent pings
+        if let Some((ident, ping)) = self.icmp.pings.iter_mut().find(|p| p.1.addr == unreachable) {
+            ping.publish.take().map(|s| s.send(Err(e)));
+
+            let ident = *ident;
+            self.icmp.pings.remove(&ident);
+            return true;
+        }
+
+        // (1) Check sockets
+        if let Some(fd) = self.icmpv6_socket_for(unreachable, SocketType::SOCK_STREAM) {
+            self.tcp_icmp_destination_unreachable(fd, e);
+            return true;
+        }
+        if let Some(fd) = self.icmpv6_socket_for(unreachable, SocketType::SOCK_DGRAM) {
+            self.udp_icmp_error(fd, e, unreachable);
+            return true;
+        }
+        false
+    }
+
+    fn icmpv6_socket_for(&self, peer: IpAddr, typ: SocketType) -> Option<Fd> {
+        self.sockets
+            .iter()
+            .find(|(_, socket)| {
+                socket.domain == SocketDomain::AF_INET6
+                    && socket.typ == typ
+                    && socket.peer.ip() == peer
+            })
+            .map(|(fd, _)| *fd)
+    }
+
+    /// Reports a packet, that could not be delivered since address
+    /// resolution for the next hop failed.
+    pub(crate) fn icmpv6_address_unreachable(&mut self, ifid: IfId, pkt: &Ipv6Packet) {
+        // Locally generated packets are dropped silently
+        let local = self.ifaces.values().any(|iface| {
+            iface
+                .addrs
+                .iter()
+                .any(|addr| addr.matches_ip(pkt.src.into()))
+        });
+        if local {
+            return;
+        }
+
+        self.icmpv6_error(ifid, pkt, |content| Icmpv6Packet::DestinationUnreachable {
+            code: Icmpv6DestinationUnreachableCode::AddressUnreachable,
+            content,
+        });
+    }
+
+    /// Reports a packet, that could not be forwarded.
+    pub(crate) fn icmpv6_routing_failed(&mut self, ifid: IfId, e: Error, pkt: &Ipv6Packet) {
+        let code = match e.kind() {
+            ErrorKind::ConnectionRefused => Icmpv6DestinationUnreachableCode::NoRouteToDestination,
+            ErrorKind::NotConnected => Icmpv6DestinationUnreachableCode::AddressUnreachable,
+            _ => return,
+        };
+        self.icmpv6_error(ifid, pkt, |content| Icmpv6Packet::DestinationUnreachable {
+            code,
+            content,
+        });
+    }
+
+    /// Reports a packet, whose hop limit was exceeded while forwarding.
+    pub(crate) fn icmpv6_hop_limit_exceeded(&mut self, ifid: IfId, pkt: &Ipv6Packet) {
+        self.icmpv6_error(ifid, pkt, |content| Icmpv6Packet::TimeExceeded {
+            code: Icmpv6TimeExceededCode::HopLimitExceeded,
+            content,
+        });
+    }
+
+    /// Reports a datagram, that was addressed to a closed port.
+    pub(crate) fn icmpv6_port_unreachable(&mut self, ifid: IfId, pkt: &Ipv6Packet) {
+        self.icmpv6_error(ifid, pkt, |content| Icmpv6Packet::DestinationUnreachable {
+            code: Icmpv6DestinationUnreachableCode::PortUnreachable,
+            content,
+        });
+    }
+
+    /// Reports a packet, whose next header is not supported (RFC 4443).
+    pub(super) fn icmpv6_protocol_unreachable(&mut self, ifid: IfId, pkt: &Ipv6Packet) {
+        if pkt.dest.is_multicast() {
+            return;
+        }
+
+        self.icmpv6_error(ifid, pkt, |content| Icmpv6Packet::ParameterProblem {
+            code: Icmpv6ParameterProblemCode::UnrecognizedNextHeader,
+            pointer: NEXT_HEADER_OFFSET,
+            content,
+        });
+    }
+
+    fn icmpv6_error(
+        &mut self,
+        ifid: IfId,
+        pkt: &Ipv6Packet,
+        f: impl FnOnce(Vec<u8>) -> Icmpv6Packet,
+    ) {
+        // Errors are never send in response to other errors,
+        // or to packets without a unique source.
+        let error =
+            pkt.next_header == PROTO_ICMPV6 && pkt.content.first().is_some_and(|t| *t < 128);
+        if error || pkt.src.is_unspecified() || pkt.src.is_multicast() {
+            return;
+        }
+
+        let mut content = pkt.to_vec().expect("Failed to parse IPv6");
+        content.truncate(MAX_ERROR_LEN);
+        let icmp = f(content);
+        let ip = Ipv6Packet {
+            traffic_class: 0,
+            flow_label: 0,
+            next_header: PROTO_ICMPV6,
+            hop_limit: 64,
+            src: Ipv6Addr::UNSPECIFIED,
+            dest: pkt.src,
+            content: icmp.to_vec().expect("Failed to parse ICMPv6"),
+        };
+        let _ = self.send_ip_packet(SocketIfaceBinding::Bound(ifid), IpPacket::V6(ip), true);
+    }
+}
+
+/// The destination of the packet contained in an ICMPv6 error. The contained
+/// packet may be truncated, thus only the header is parsed.
+fn contained_dest(content: &[u8]) -> Option<Ipv6Addr> {
+    let dest: [u8; 16] = content.get(24..40)?.try_into().ok()?;
+    Some(Ipv6Addr::from(dest))
+}
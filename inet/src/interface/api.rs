@@ -11,7 +11,7 @@ use super::{
 };
 use crate::{
     arp::ArpEntryInternal,
-    routing::{FwdEntryV4, Ipv4Gateway, Ipv6Gateway, RoutingTableId},
+    routing::{FwdEntryV4, FwdEntryV6, Ipv4Gateway, Ipv6Gateway, RoutingTableId},
     IOContext,
 };
 
@@ -28,6 +28,7 @@ pub struct InterfaceState {
     pub name: InterfaceName,
     pub flags: InterfaceFlags,
     pub addrs: Vec<InterfaceAddr>,
+    pub mtu: u16,
     pub status: InterfaceStatus,
     pub busy: InterfaceBusyState,
     pub queuelen: usize,
@@ -62,28 +63,30 @@ impl IOContext {
                 }
 
                 if iface.ipv6_subnet().is_some() {
-                    let _ = self.arp.update(ArpEntryInternal {
-                        negated: false,
-                        hostname: None,
-                        ip: IpAddr::V6(Ipv6Addr::new(0xf801, 0, 0, 0, 0, 0, 0, 1)),
-                        mac: MacAddress::BROADCAST,
-                        iface: iface.name.id,
-                        expires: SimTime::MAX,
-                    });
-
-                    self.ipv6router.add_entry(
+                    let _ = self.ndp.insert_permanent(
                         Ipv6Addr::new(0xf801, 0, 0, 0, 0, 0, 0, 1),
-                        Ipv6Addr::new(
-                            0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff,
-                        ),
-                        Ipv6Gateway::Broadcast,
+                        MacAddress::BROADCAST,
                         iface.name.id,
-                        usize::MAX,
+                        None,
+                    );
+
+                    self.ipv6_fwd.add_entry(
+                        FwdEntryV6 {
+                            dest: Ipv6Addr::new(0xf801, 0, 0, 0, 0, 0, 0, 1),
+                            mask: Ipv6Addr::new(
+                                0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff,
+                            ),
+                            gateway: Ipv6Gateway::Broadcast,
+                            iface: iface.name.clone(),
+                            metric: 0,
+                            expires: SimTime::MAX,
+                        },
+                        RoutingTableId::DEFAULT,
                     );
                 }
             }
 
-            // (1) Add all interface addrs to ARP
+            // (1) Add all interface addrs to ARP, or the neighbor cache
             for addr in &iface.addrs {
                 match addr {
                     InterfaceAddr::Inet { addr, .. } => {
@@ -97,14 +100,12 @@ impl IOContext {
                         });
                     }
                     InterfaceAddr::Inet6 { addr, .. } => {
-                        let _ = self.arp.update(ArpEntryInternal {
-                            negated: false,
-                            hostname: Some(module_name()),
-                            ip: IpAddr::V6(*addr),
-                            mac: iface.device.addr,
-                            iface: iface.name.id,
-                            expires: SimTime::MAX,
-                        });
+                        let _ = self.ndp.insert_permanent(
+                            *addr,
+                            iface.device.addr,
+                            iface.name.id,
+                            Some(module_name()),
+                        );
                     }
                     _ => todo!(),
                 }
@@ -120,6 +121,9 @@ impl IOContext {
                             mask,
                             gateway: Ipv4Gateway::Local,
                             iface: iface.name.clone(),
+                            metric: 0,
+                            weight: 1,
+                            expires: SimTime::MAX,
                         },
                         RoutingTableId::DEFAULT,
                     );
@@ -128,17 +132,16 @@ impl IOContext {
 
             // (3) Add interface subnet to routing table.
             if let Some((addr, mask)) = iface.ipv6_subnet() {
-                self.ipv6router.add_entry(
-                    addr,
-                    mask,
-                    Ipv6Gateway::Local,
-                    iface.name.id,
-                    usize::MAX / 4,
-                )
+                self.ipv6_fwd.add_entry(
+                    FwdEntryV6::local(addr, mask, iface.name.clone()),
+                    RoutingTableId::DEFAULT,
+                );
             }
 
-            self.ifaces.insert(iface.name.id, iface);
-            Ok(())
+            // (4) Solicit routers on the new link
+            let ifid = iface.name.id;
+            self.ifaces.insert(ifid, iface);
+            self.ndp_solicit_routers(ifid)
         }
     }
 
@@ -153,6 +156,7 @@ impl IOContext {
             name: iface.name.clone(),
             flags: iface.flags,
             addrs: iface.addrs.clone(),
+            mtu: iface.mtu,
             status: iface.status,
             busy: iface.state.clone(),
             queuelen: iface.buffer.len(),
@@ -7,12 +7,17 @@ use std::{
     io::{Error, ErrorKind, Result},
 };
 
+use crate::ip::TIMER_REASSEMBLY;
+use crate::ndp::{is_link_local, link_local_addr, TIMER_NDP};
+use crate::routing::TIMER_ROUTE;
 use crate::socket::Fd;
 use crate::IOContext;
 use des::prelude::*;
 use inet_types::arp::ArpPacket;
 use inet_types::arp::KIND_ARP;
+use inet_types::icmpv6::{solicited_node_multicast, ALL_NODES_MULTICAST};
 use inet_types::iface::MacAddress;
+use inet_types::ip::{IpVersion, KIND_IPV4, KIND_IPV6};
 
 macro_rules! hash {
     ($v:expr) => {{
@@ -51,6 +56,8 @@ pub struct Interface {
     pub flags: InterfaceFlags,
     /// A list of addresses bound to this interface
     pub addrs: Vec<InterfaceAddr>,
+    /// The maximum transmission unit of the link, in bytes
+    pub mtu: u16,
     /// The internal state of the interface
     pub status: InterfaceStatus,
     /// A flag indicating whether the interface is currently busy sending
@@ -58,6 +65,9 @@ pub struct Interface {
 
     pub(crate) prio: usize,
     pub(crate) buffer: VecDeque<Message>,
+    /// IPv6 multicast groups joined by protocol components, in addition
+    /// to the groups derived from the interface addrs.
+    pub(crate) ipv6_groups: Vec<Ipv6Addr>,
 }
 
 /// A result forwarded after linklayer processing
@@ -111,10 +121,12 @@ impl Interface {
                 addr: subnet,
                 netmask: mask,
             }],
+            mtu: MTU_ETHERNET,
             status: InterfaceStatus::Active,
             state: InterfaceBusyState::Idle,
             prio: 100,
             buffer: VecDeque::new(),
+            ipv6_groups: Vec::new(),
         }
     }
 
@@ -133,10 +145,43 @@ impl Interface {
                 prefixlen: 64,
                 scope_id: None,
             }],
+            mtu: MTU_ETHERNET,
             status: InterfaceStatus::Active,
             state: InterfaceBusyState::Idle,
             prio: 200,
             buffer: VecDeque::new(),
+            ipv6_groups: Vec::new(),
+        }
+    }
+
+    /// Creates a new ethernet interface, that configures its IPv6
+    /// addrs using stateless address autoconfiguration.
+    pub fn ethv6_autoconf(device: NetworkDevice) -> Interface {
+        Self::ethv6_autoconf_named("en1", device)
+    }
+
+    /// Creates a new ethernet interface, that configures its IPv6
+    /// addrs using stateless address autoconfiguration.
+    ///
+    /// Initially only a link-local address, derived from the MAC address
+    /// of the device, is assigned. Further addrs are derived from the
+    /// prefixes announced by routers on the link.
+    pub fn ethv6_autoconf_named(name: impl AsRef<str>, device: NetworkDevice) -> Interface {
+        Interface {
+            name: InterfaceName::new(name),
+            flags: InterfaceFlags::en0(),
+            addrs: vec![InterfaceAddr::Inet6 {
+                addr: link_local_addr(device.addr),
+                prefixlen: 64,
+                scope_id: None,
+            }],
+            device,
+            mtu: MTU_ETHERNET,
+            status: InterfaceStatus::Active,
+            state: InterfaceBusyState::Idle,
+            prio: 200,
+            buffer: VecDeque::new(),
+            ipv6_groups: Vec::new(),
         }
     }
 
@@ -162,10 +207,12 @@ impl Interface {
                     scope_id: None,
                 },
             ],
+            mtu: MTU_ETHERNET,
             status: InterfaceStatus::Active,
             state: InterfaceBusyState::Idle,
             prio: 100,
             buffer: VecDeque::new(),
+            ipv6_groups: Vec::new(),
         }
     }
 
@@ -176,10 +223,12 @@ impl Interface {
             device: NetworkDevice::loopback(),
             flags: InterfaceFlags::loopback(),
             addrs: Vec::from(InterfaceAddr::loopback()),
+            mtu: MTU_LOOPBACK,
             status: InterfaceStatus::Active,
             prio: 100,
             state: InterfaceBusyState::Idle,
             buffer: VecDeque::new(),
+            ipv6_groups: Vec::new(),
         }
     }
 
@@ -213,6 +262,50 @@ impl Interface {
         })
     }
 
+    /// The source address used for packets to the given destination.
+    /// Addrs of the same scope as the destination are preferred.
+    pub(crate) fn ipv6_src_addr(&self, dest: Ipv6Addr) -> Option<Ipv6Addr> {
+        let link_local =
+            is_link_local(dest) || (dest.is_multicast() && dest.segments()[0] & 0xf == 2);
+        let mut addrs = self.addrs.iter().filter_map(|addr| match addr {
+            InterfaceAddr::Inet6 { addr, .. } => Some(*addr),
+            _ => None,
+        });
+        let first = addrs.clone().next();
+        addrs
+            .find(|addr| is_link_local(*addr) == link_local)
+            .or(first)
+    }
+
+    /// The IPv6 multicast groups joined by the interface, namely the
+    /// all-nodes group, the solicited-node groups of its addresses and
+    /// groups joined by protocol components.
+    pub(crate) fn ipv6_multicast_groups(&self) -> impl Iterator<Item = Ipv6Addr> + '_ {
+        let mut addrs = self
+            .addrs
+            .iter()
+            .filter_map(|addr| match addr {
+                InterfaceAddr::Inet6 { addr, .. } => Some(*addr),
+                _ => None,
+            })
+            .peekable();
+        let all_nodes = addrs.peek().map(|_| ALL_NODES_MULTICAST);
+        all_nodes
+            .into_iter()
+            .chain(addrs.map(solicited_node_multicast))
+            .chain(self.ipv6_groups.iter().copied())
+    }
+
+    pub(crate) fn join_ipv6_group(&mut self, group: Ipv6Addr) {
+        self.ipv6_groups.push(group);
+    }
+
+    pub(crate) fn leave_ipv6_group(&mut self, group: Ipv6Addr) {
+        if let Some(i) = self.ipv6_groups.iter().position(|g| *g == group) {
+            self.ipv6_groups.swap_remove(i);
+        }
+    }
+
     pub(crate) fn send_buffered(&mut self, msg: Message) -> Result<()> {
         if self.is_busy() {
             // if self.buffer.len() >= 16 {
@@ -307,6 +400,22 @@ impl IOContext {
                 self.recv_arp_wakeup();
                 return Consumed();
             }
+            if msg.header().typ == TIMER_REASSEMBLY && msg.header().id == KIND_IPV4 {
+                self.recv_ipv4_reassembly_wakeup();
+                return Consumed();
+            }
+            if msg.header().typ == TIMER_NDP && msg.header().id == KIND_IPV6 {
+                self.recv_ndp_wakeup();
+                return Consumed();
+            }
+            if msg.header().typ == TIMER_ROUTE && msg.header().id == KIND_IPV4 {
+                self.recv_routing_wakeup(IpVersion::V4);
+                return Consumed();
+            }
+            if msg.header().typ == TIMER_ROUTE && msg.header().id == KIND_IPV6 {
+                self.recv_routing_wakeup(IpVersion::V6);
+                return Consumed();
+            }
 
             return Timeout(msg);
         }
@@ -328,7 +437,17 @@ impl IOContext {
 
         // Check that packet is addressed correctly.
         if iface.device.addr != dest && !dest.is_broadcast() {
-            return PassThrough(msg);
+            if !dest.is_multicast() {
+                return PassThrough(msg);
+            }
+
+            // Frames of multicast groups, that were not joined, are filtered by the device.
+            let joined = iface
+                .ipv6_multicast_groups()
+                .any(|group| MacAddress::ipv6_multicast(group) == dest);
+            if !joined {
+                return Consumed();
+            }
         }
 
         if msg.header().kind == KIND_ARP {
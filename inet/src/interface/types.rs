@@ -6,6 +6,11 @@ use crate::socket::Fd;
 pub(crate) const KIND_LINK_UPDATE: MessageKind = 0x0500;
 pub(crate) const KIND_IO_TIMEOUT: MessageKind = 0x0128;
 
+/// The default MTU of an ethernet link.
+pub const MTU_ETHERNET: u16 = 1500;
+/// The default MTU of the loopback interface.
+pub const MTU_LOOPBACK: u16 = u16::MAX;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, MessageBody)]
 pub(crate) struct LinkUpdate(pub IfId);
 
@@ -0,0 +1,485 @@
+//! IPv4 fragmentation, reassembly and path MTU discovery.
+//!
+//! Packets that exceed the MTU of the outgoing link are split into
+//! fragments, unless the DF flag is set. Received fragments are buffered
+//! until the original packet is complete, or the reassembly timer expires.
+//! Path MTUs reported by ICMP are cached per destination.
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+use des::{
+    prelude::{schedule_at, Message},
+    time::SimTime,
+};
+use fxhash::{FxBuildHasher, FxHashMap};
+use inet_types::ip::{Ipv4Packet, KIND_IPV4};
+
+use crate::{
+    interface::{IfId, KIND_IO_TIMEOUT},
+    IOContext,
+};
+
+mod pmtu;
+pub(crate) use self::pmtu::*;
+
+/// The time a partially received packet is kept, before
+/// the reassembly is aborted.
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The typ of the reassembly wakeup, distinct from the TCP timers.
+pub(crate) const TIMER_REASSEMBLY: u8 = 4;
+
+/// The size of the IPv4 header, as written by `inet_types`.
+const IPV4_HEADER_LEN: usize = 20;
+
+/// The maximum payload of an IPv4 packet, limiting the bytes
+/// buffered per reassembled packet.
+const MAX_PAYLOAD_LEN: usize = u16::MAX as usize - IPV4_HEADER_LEN;
+
+/// An error indicating that a packet with the DF flag set
+/// exceeds the MTU of the next hop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FragmentationNeeded {
+    pub(crate) mtu: u16,
+}
+
+impl fmt::Display for FragmentationNeeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "message too long, fragmentation needed (mtu {})",
+            self.mtu
+        )
+    }
+}
+
+impl std::error::Error for FragmentationNeeded {}
+
+impl FragmentationNeeded {
+    /// Extracts the error from an `io::Error`, if present.
+    pub(crate) fn from_io(e: &Error) -> Option<FragmentationNeeded> {
+        e.get_ref()?.downcast_ref::<FragmentationNeeded>().copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    src: Ipv4Addr,
+    dest: Ipv4Addr,
+    proto: u8,
+    identification: u16,
+}
+
+impl FragmentKey {
+    fn of(pkt: &Ipv4Packet) -> Self {
+        Self {
+            src: pkt.src,
+            dest: pkt.dest,
+            proto: pkt.proto,
+            identification: pkt.identification,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ReassemblyBuffer {
+    ifid: IfId,
+    deadline: SimTime,
+    // The first fragment, providing the header of the reassembled packet
+    first: Option<Ipv4Packet>,
+    // Fragments as (byte offset, content)
+    fragments: Vec<(usize, Vec<u8>)>,
+    total_len: Option<usize>,
+}
+
+impl ReassemblyBuffer {
+    /// Adds a fragment to the buffer. Returns `false` if the fragment
+    /// contradicts the already received fragments, or exceeds the maximum
+    /// packet size, in which case the packet cannot be reassembled.
+    fn insert(&mut self, pkt: Ipv4Packet) -> bool {
+        let offset = pkt.fragment_offset as usize * 8;
+        let end = offset + pkt.content.len();
+        let buffered = self.fragments.iter().map(|(_, c)| c.len()).sum::<usize>();
+        if end > MAX_PAYLOAD_LEN || buffered + pkt.content.len() > MAX_PAYLOAD_LEN {
+            return false;
+        }
+        if !pkt.flags.mf {
+            // The last fragment defines the length of the packet, all
+            // other fragments must end before it
+            if self.total_len.is_some_and(|total_len| total_len != end)
+                || self.fragments.iter().any(|(o, c)| o + c.len() > end)
+            {
+                return false;
+            }
+            self.total_len = Some(end);
+        }
+        if self.total_len.is_some_and(|total_len| end > total_len) {
+            return false;
+        }
+
+        if offset == 0 {
+            self.first = Some(pkt.clone());
+        }
+        self.fragments.push((offset, pkt.content));
+        true
+    }
+
+    fn try_assemble(&mut self) -> Option<Ipv4Packet> {
+        let total_len = self.total_len?;
+        self.first.as_ref()?;
+
+        // (0) Check that the fragments cover the entire packet
+        self.fragments.sort_by_key(|(offset, _)| *offset);
+        let mut covered = 0;
+        for (offset, content) in &self.fragments {
+            if *offset > covered {
+                return None;
+            }
+            covered = covered.max(offset + content.len());
+        }
+        if covered < total_len {
+            return None;
+        }
+
+        // (1) Assemble, later fragments override overlapping bytes
+        let mut content = vec![0; total_len];
+        for (offset, fragment) in &self.fragments {
+            let end = (offset + fragment.len()).min(total_len);
+            content[*offset..end].copy_from_slice(&fragment[..end - offset]);
+        }
+
+        let mut pkt = self.first.take()?;
+        pkt.flags.mf = false;
+        pkt.fragment_offset = 0;
+        pkt.content = content;
+        Some(pkt)
+    }
+}
+
+pub(crate) struct Ipv4Reassembly {
+    buffers: FxHashMap<FragmentKey, ReassemblyBuffer>,
+    identification: u16,
+    active_wakeup: Option<SimTime>,
+}
+
+impl Ipv4Reassembly {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffers: FxHashMap::with_hasher(FxBuildHasher::default()),
+            identification: 0,
+            active_wakeup: None,
+        }
+    }
+
+    fn next_identification(&mut self) -> u16 {
+        self.identification = self.identification.wrapping_add(1).max(1);
+        self.identification
+    }
+}
+
+/// Splits a packet into fragments, each fitting into the given MTU.
+///
+/// Packets that allready fit are returned as is. Should the packet
+/// require fragmentation, but has the DF flag set, an error containing
+/// [`FragmentationNeeded`] is returned.
+pub(crate) fn ipv4_fragment(
+    mut pkt: Ipv4Packet,
+    mtu: u16,
+    identification: impl FnOnce() -> u16,
+) -> Result<Vec<Ipv4Packet>> {
+    let mtu = mtu as usize;
+    if IPV4_HEADER_LEN + pkt.content.len() <= mtu {
+        return Ok(vec![pkt]);
+    }
+
+    if pkt.flags.df {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            FragmentationNeeded { mtu: mtu as u16 },
+        ));
+    }
+
+    let chunk = (mtu.saturating_sub(IPV4_HEADER_LEN) / 8) * 8;
+    if chunk == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "mtu to small to fragment packet",
+        ));
+    }
+
+    // Packets without an identification need one, to be reassembled.
+    if pkt.identification == 0 {
+        pkt.identification = identification();
+    }
+
+    let content = std::mem::take(&mut pkt.content);
+    let n = content.len().div_ceil(chunk);
+    let fragments = content
+        .chunks(chunk)
+        .enumerate()
+        .map(|(i, content)| {
+            let mut fragment = pkt.clone();
+            fragment.fragment_offset = pkt.fragment_offset + (i * chunk / 8) as u16;
+            fragment.flags.mf = i + 1 < n || pkt.flags.mf;
+            fragment.content = content.to_vec();
+            fragment
+        })
+        .collect();
+
+    Ok(fragments)
+}
+
+impl IOContext {
+    pub(super) fn ipv4_fragment(&mut self, pkt: Ipv4Packet, ifid: IfId) -> Result<Vec<Ipv4Packet>> {
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Ok(vec![pkt]);
+        };
+        let mtu = match self.pmtu.get(IpAddr::V4(pkt.dest)) {
+            Some(pmtu) => pmtu.min(iface.mtu),
+            None => iface.mtu,
+        };
+        let reassembly = &mut self.ipv4_reassembly;
+        ipv4_fragment(pkt, mtu, || reassembly.next_identification())
+    }
+
+    /// Adds a fragment to the reassembly buffer, returning the reassembled
+    /// packet once all fragments were received.
+    pub(super) fn ipv4_reassemble(&mut self, pkt: Ipv4Packet, ifid: IfId) -> Option<Ipv4Packet> {
+        let key = FragmentKey::of(&pkt);
+        let buffer = self
+            .ipv4_reassembly
+            .buffers
+            .entry(key)
+            .or_insert_with(|| ReassemblyBuffer {
+                ifid,
+                deadline: SimTime::now() + REASSEMBLY_TIMEOUT,
+                first: None,
+                fragments: Vec::new(),
+                total_len: None,
+            });
+
+        if !buffer.insert(pkt) {
+            tracing::warn!(
+                "dropping packet from {} (id {}) due to inconsistent fragments",
+                key.src,
+                key.identification
+            );
+            self.ipv4_reassembly.buffers.remove(&key);
+            return None;
+        }
+        if let Some(pkt) = buffer.try_assemble() {
+            self.ipv4_reassembly.buffers.remove(&key);
+            return Some(pkt);
+        }
+
+        self.ipv4_reassembly_schedule_wakeup();
+        None
+    }
+
+    fn ipv4_reassembly_schedule_wakeup(&mut self) {
+        let Some(deadline) = self
+            .ipv4_reassembly
+            .buffers
+            .values()
+            .map(|b| b.deadline)
+            .min()
+        else {
+            return;
+        };
+
+        if self
+            .ipv4_reassembly
+            .active_wakeup
+            .is_some_and(|wakeup| wakeup <= deadline)
+        {
+            return;
+        }
+
+        self.ipv4_reassembly.active_wakeup = Some(deadline);
+        schedule_at(
+            Message::new()
+                .kind(KIND_IO_TIMEOUT)
+                .typ(TIMER_REASSEMBLY)
+                .id(KIND_IPV4)
+                .build(),
+            deadline,
+        );
+    }
+
+    pub(super) fn recv_ipv4_reassembly_wakeup(&mut self) {
+        self.ipv4_reassembly.active_wakeup = None;
+
+        let now = SimTime::now();
+        let expired = self
+            .ipv4_reassembly
+            .buffers
+            .iter()
+            .filter(|(_, buffer)| buffer.deadline <= now)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        for key in expired {
+            let buffer = self.ipv4_reassembly.buffers.remove(&key).unwrap();
+            tracing::warn!(
+                "dropping incomplete packet from {} (id {}) due to reassembly timeout",
+                key.src,
+                key.identification
+            );
+
+            // An ICMP error may only be send, if the first fragment was received.
+            if let Some(first) = buffer.first {
+                self.icmp_reassembly_timeout(buffer.ifid, &first);
+            }
+        }
+
+        self.ipv4_reassembly_schedule_wakeup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inet_types::ip::Ipv4Flags;
+
+    fn packet(len: usize, df: bool) -> Ipv4Packet {
+        Ipv4Packet {
+            flags: Ipv4Flags { df, mf: false },
+            proto: 17,
+            src: Ipv4Addr::new(10, 0, 0, 1),
+            dest: Ipv4Addr::new(10, 0, 0, 2),
+            content: (0..len).map(|i| i as u8).collect(),
+            ..Ipv4Packet::EMPTY
+        }
+    }
+
+    fn reassemble(fragments: impl IntoIterator<Item = Ipv4Packet>) -> Option<Ipv4Packet> {
+        let mut buffer = ReassemblyBuffer {
+            ifid: IfId::NULL,
+            deadline: SimTime::ZERO,
+            first: None,
+            fragments: Vec::new(),
+            total_len: None,
+        };
+        let mut result = None;
+        for fragment in fragments {
+            if !buffer.insert(fragment) {
+                return None;
+            }
+            result = buffer.try_assemble();
+        }
+        result
+    }
+
+    fn fragment(offset: u16, len: usize, mf: bool) -> Ipv4Packet {
+        Ipv4Packet {
+            flags: Ipv4Flags { df: false, mf },
+            fragment_offset: offset / 8,
+            ..packet(len, false)
+        }
+    }
+
+    #[test]
+    fn fragment_within_mtu() {
+        let fragments = ipv4_fragment(packet(1480, true), 1500, || 1).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert!(!fragments[0].flags.mf);
+    }
+
+    #[test]
+    fn fragment_respects_df() {
+        let e = ipv4_fragment(packet(1481, true), 1500, || 1).unwrap_err();
+        assert_eq!(
+            FragmentationNeeded::from_io(&e),
+            Some(FragmentationNeeded { mtu: 1500 })
+        );
+    }
+
+    #[test]
+    fn fragment_and_reassemble() {
+        let pkt = packet(4000, false);
+        let fragments = ipv4_fragment(pkt.clone(), 1500, || 42).unwrap();
+
+        assert_eq!(fragments.len(), 3);
+        assert_eq!(
+            fragments
+                .iter()
+                .map(|f| f.fragment_offset)
+                .collect::<Vec<_>>(),
+            [0, 185, 370]
+        );
+        assert_eq!(
+            fragments.iter().map(|f| f.flags.mf).collect::<Vec<_>>(),
+            [true, true, false]
+        );
+        assert!(fragments.iter().all(|f| f.identification == 42));
+        assert!(fragments.iter().all(|f| f.content.len() + 20 <= 1500));
+
+        // Out of order delivery
+        let mut reordered = fragments.clone();
+        reordered.reverse();
+        let result = reassemble(reordered).unwrap();
+        assert_eq!(result.content, pkt.content);
+        assert!(!result.flags.mf);
+        assert_eq!(result.fragment_offset, 0);
+
+        // Missing fragments
+        assert!(reassemble(fragments.into_iter().skip(1)).is_none());
+    }
+
+    #[test]
+    fn refragment_fragment() {
+        let pkt = packet(4000, false);
+        let fragments = ipv4_fragment(pkt.clone(), 1500, || 7).unwrap();
+
+        // A router with a smaller MTU refragments the first fragment
+        let mut all = ipv4_fragment(fragments[0].clone(), 576, || 8).unwrap();
+        assert!(all.iter().all(|f| f.flags.mf && f.identification == 7));
+        all.extend(fragments.into_iter().skip(1));
+
+        let result = reassemble(all).unwrap();
+        assert_eq!(result.content, pkt.content);
+    }
+
+    #[test]
+    fn reject_inconsistent_fragments() {
+        // A last fragment ending before the already received fragments
+        let fragments = [
+            fragment(0, 16, true),
+            fragment(16, 16, true),
+            fragment(8, 0, false),
+        ];
+        assert!(reassemble(fragments).is_none());
+
+        // Two last fragments with different ends
+        let fragments = [fragment(16, 8, false), fragment(16, 16, false)];
+        assert!(reassemble(fragments).is_none());
+
+        // A fragment ending past the last fragment
+        let fragments = [fragment(16, 8, false), fragment(0, 32, true)];
+        assert!(reassemble(fragments).is_none());
+
+        // A fragment ending past the maximum packet size
+        let fragments = [fragment(65528, 16, false)];
+        assert!(reassemble(fragments).is_none());
+
+        // Duplicates exceeding the maximum packet size
+        let fragments = (0..45).map(|_| fragment(0, 1480, true));
+        assert!(reassemble(fragments).is_none());
+    }
+
+    #[test]
+    fn reassemble_max_packet() {
+        // Small MTUs require many fragments for large packets
+        let pkt = packet(MAX_PAYLOAD_LEN, false);
+        let fragments = ipv4_fragment(pkt.clone(), 576, || 9).unwrap();
+        assert!(fragments.len() > 100);
+
+        let result = reassemble(fragments).unwrap();
+        assert_eq!(result.content, pkt.content);
+    }
+}
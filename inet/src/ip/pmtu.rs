@@ -0,0 +1,121 @@
+use std::{net::IpAddr, time::Duration};
+
+use des::time::SimTime;
+use fxhash::{FxBuildHasher, FxHashMap};
+
+use crate::{
+    interface::MTU_ETHERNET,
+    socket::{Fd, SocketType},
+    IOContext,
+};
+
+/// The time after which a learned path MTU expires, so that
+/// increases of the path MTU can be detected (RFC 1191).
+const PMTU_EXPIRY: Duration = Duration::from_secs(600);
+
+/// The smallest MTU any IPv4 link must support (RFC 791).
+const MIN_MTU_V4: u16 = 68;
+
+/// The smallest MTU any IPv6 link must support (RFC 8200).
+const MIN_MTU_V6: u16 = 1280;
+
+/// Common MTU values, used if a router does not report the
+/// MTU of the next hop (RFC 1191).
+const MTU_PLATEAUS: [u16; 10] = [32000, 17914, 8166, 4352, 2002, 1492, 1006, 508, 296, 68];
+
+/// A per-destination cache of path MTUs, learned from ICMP
+/// "fragmentation needed" errors.
+pub(crate) struct PathMtuCache {
+    entries: FxHashMap<IpAddr, PathMtuEntry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PathMtuEntry {
+    mtu: u16,
+    expires: SimTime,
+}
+
+impl PathMtuCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: FxHashMap::with_hasher(FxBuildHasher::default()),
+        }
+    }
+
+    pub(crate) fn get(&self, dest: IpAddr) -> Option<u16> {
+        self.entries
+            .get(&dest)
+            .filter(|entry| entry.expires > SimTime::now())
+            .map(|entry| entry.mtu)
+    }
+}
+
+impl IOContext {
+    /// The MTU of the link used to reach `dest`.
+    fn link_mtu(&self, dest: IpAddr) -> u16 {
+        let ifid = match dest {
+            IpAddr::V4(dest) => self.ipv4_fwd.lookup(dest).map(|(_, iface)| iface.id),
+            IpAddr::V6(dest) => self.ipv6_fwd.lookup(dest).map(|(_, iface)| iface.id),
+        };
+        ifid.and_then(|ifid| self.ifaces.get(&ifid))
+            .map(|iface| iface.mtu)
+            .unwrap_or(MTU_ETHERNET)
+    }
+
+    /// The path MTU towards `dest`, bounded by the MTU of the outgoing link.
+    pub(crate) fn path_mtu(&self, dest: IpAddr) -> u16 {
+        let link_mtu = self.link_mtu(dest);
+        self.pmtu
+            .get(dest)
+            .map_or(link_mtu, |mtu| mtu.min(link_mtu))
+    }
+
+    /// Lowers the path MTU towards `dest`, after a router reported that
+    /// a packet was to big for the next hop.
+    ///
+    /// A `next_hop_mtu` of zero indicates a router that does not support
+    /// RFC 1191, so the next lower plateau is used instead.
+    pub(crate) fn path_mtu_update(&mut self, dest: IpAddr, next_hop_mtu: u16) {
+        let current = self.path_mtu(dest);
+        let mtu = if next_hop_mtu == 0 {
+            MTU_PLATEAUS
+                .iter()
+                .copied()
+                .find(|plateau| *plateau < current)
+                .unwrap_or(MIN_MTU_V4)
+        } else {
+            next_hop_mtu
+        };
+        let min_mtu = if dest.is_ipv4() {
+            MIN_MTU_V4
+        } else {
+            MIN_MTU_V6
+        };
+        let mtu = mtu.max(min_mtu);
+
+        if mtu >= current {
+            return;
+        }
+
+        tracing::debug!("path mtu towards {dest} lowered to {mtu} (was {current})");
+        self.pmtu.entries.insert(
+            dest,
+            PathMtuEntry {
+                mtu,
+                expires: SimTime::now() + PMTU_EXPIRY,
+            },
+        );
+
+        // Streams towards the destination must reduce their segment size
+        let streams = self
+            .sockets
+            .iter()
+            .filter(|(_, socket)| socket.typ == SocketType::SOCK_STREAM && socket.peer.ip() == dest)
+            .map(|(fd, _)| *fd)
+            .collect::<Vec<Fd>>();
+
+        for fd in streams {
+            self.tcp_path_mtu_update(fd, mtu);
+        }
+    }
+}
@@ -4,9 +4,11 @@ mod macros;
 pub mod arp;
 pub mod dns;
 pub mod extensions;
+pub mod firewall;
 pub mod icmp;
 pub mod interface;
 pub mod io;
+pub mod ndp;
 pub mod routing;
 pub mod socket;
 pub mod utils;
@@ -26,6 +28,8 @@ cfg_uds! {
 
 pub use inet_types as types;
 
+mod ip;
+
 mod udp;
 pub use udp::*;
 
@@ -0,0 +1,143 @@
+use std::{fmt::Display, io::Result, net::Ipv6Addr};
+
+use inet_types::iface::MacAddress;
+
+use super::{NdpConfig, NeighborState, RouterAdvertisementConfig};
+use crate::{
+    interface::{IfId, InterfaceName},
+    IOContext,
+};
+
+/// An entry in the neighbor cache
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NeighborEntry {
+    /// A human-readable name for the resolved node
+    pub hostname: Option<String>,
+    /// The IPv6 address of the neighbor
+    pub ip: Ipv6Addr,
+    /// The MAC address of the neighbor, unspecified while
+    /// address resolution is incomplete
+    pub mac: MacAddress,
+    /// An identifier for the related interface
+    pub iface: InterfaceName,
+    /// The reachability state of the neighbor
+    pub state: NeighborState,
+    /// A flag indicating whether the neighbor is a router
+    pub router: bool,
+}
+
+impl Display for NeighborEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} dev {} ", self.ip, self.iface)?;
+        if self.state != NeighborState::Incomplete {
+            write!(f, "lladdr {} ", self.mac)?;
+        }
+        if self.router {
+            write!(f, "router ")?;
+        }
+        write!(f, "{}", self.state)
+    }
+}
+
+/// Display the IPv6 neighbor cache
+///
+/// This function is roughly equivalent to the shell command
+/// `ip -6 neigh`. On success this function returns a list of all
+/// entries in the neighbor cache, including their reachability state.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::ndp::neighbors;
+///
+/// /* ... */
+/// # fn main() -> std::io::Result<()> {
+/// for neighbor in neighbors()? {
+///     println!("{neighbor}")
+/// }
+/// # Ok(())
+/// # }
+/// /* ... */
+///
+/// ```
+pub fn neighbors() -> Result<Vec<NeighborEntry>> {
+    IOContext::failable_api(|ctx| Ok(ctx.neighbors()))
+}
+
+/// Sets the configuration of the neighbor cache
+///
+/// Note that router advertisements may override the reachable
+/// time and the retransmission timer.
+pub fn set_ndp_config(cfg: NdpConfig) -> Result<()> {
+    IOContext::failable_api(|ctx| ctx.set_ndp_config(cfg))
+}
+
+/// Starts sending router advertisements on an interface
+///
+/// Hosts on the link will use this node as a default router, and
+/// autoconfigure addrs from the announced prefixes. Advertisements
+/// are send periodically, and in response to router solicitations.
+/// Should the interface have no link-local address, one is derived
+/// from the MAC address of the interface.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::interface::InterfaceName;
+/// use inet::ndp::{start_router_advertisements, PrefixAdvertisement, RouterAdvertisementConfig};
+/// use std::net::Ipv6Addr;
+///
+/// /* ... */
+/// # fn main() -> std::io::Result<()> {
+/// let prefix = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0);
+/// start_router_advertisements(
+///     &InterfaceName::new("en1").id(),
+///     RouterAdvertisementConfig {
+///         prefixes: vec![PrefixAdvertisement::new(prefix, 64)],
+///         ..Default::default()
+///     },
+/// )?;
+/// # Ok(())
+/// # }
+/// /* ... */
+/// ```
+pub fn start_router_advertisements(ifid: &IfId, cfg: RouterAdvertisementConfig) -> Result<()> {
+    IOContext::failable_api(|ctx| ctx.ndp_start_advertising(*ifid, cfg))
+}
+
+/// Stops sending router advertisements on an interface
+///
+/// A final advertisement is send, informing hosts that this
+/// node is no longer a default router.
+pub fn stop_router_advertisements(ifid: &IfId) -> Result<()> {
+    IOContext::failable_api(|ctx| ctx.ndp_stop_advertising(*ifid))
+}
+
+impl IOContext {
+    fn neighbors(&mut self) -> Vec<NeighborEntry> {
+        let mut results = Vec::with_capacity(self.ndp.len());
+        for (ip, entry) in self.ndp.entries() {
+            let iface = if let Some(iface) = self.ifaces.get(&entry.iface) {
+                iface.name.clone()
+            } else {
+                InterfaceName::new("?")
+            };
+
+            results.push(NeighborEntry {
+                hostname: entry.hostname.clone(),
+                ip: *ip,
+                mac: entry.mac,
+                iface,
+                state: entry.state(),
+                router: entry.router,
+            });
+        }
+
+        results
+    }
+
+    fn set_ndp_config(&mut self, cfg: NdpConfig) -> Result<()> {
+        self.ndp.config = cfg;
+        Ok(())
+    }
+}
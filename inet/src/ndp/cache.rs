@@ -0,0 +1,210 @@
+use des::time::SimTime;
+use fxhash::{FxBuildHasher, FxHashMap};
+use std::{fmt::Display, net::Ipv6Addr, time::Duration};
+
+use super::{AutoconfAddr, RouterAdvertiser};
+use crate::interface::IfId;
+use inet_types::{iface::MacAddress, ip::IpPacket};
+
+pub(crate) struct NeighborCache {
+    pub(super) map: FxHashMap<Ipv6Addr, NeighborEntryInternal>,
+    pub(super) config: NdpConfig,
+    pub(super) solicitations: FxHashMap<IfId, RouterSolicitations>,
+    pub(super) autoconf: FxHashMap<Ipv6Addr, AutoconfAddr>,
+    pub(super) advertisers: FxHashMap<IfId, RouterAdvertiser>,
+    pub(super) active_wakeup: Option<SimTime>,
+}
+
+/// Configuration options for the Neighbor Discovery Protocol (NDP)
+pub struct NdpConfig {
+    /// The duration in which a neighbor is considered reachable,
+    /// after its reachability was confirmed.
+    pub reachable_time: Duration,
+    /// The timeout duration for responses to a neighbor
+    /// solicitation.
+    pub retrans_timer: Duration,
+    /// The delay before a stale neighbor, that traffic is send to,
+    /// is probed.
+    pub delay_first_probe_time: Duration,
+    /// The number of multicast solicitations send, before
+    /// address resolution fails.
+    pub max_multicast_solicit: usize,
+    /// The number of unicast probes send, before a neighbor
+    /// is considered unreachable.
+    pub max_unicast_solicit: usize,
+    /// Whether addrs are autoconfigured from prefixes announced
+    /// by routers.
+    pub autoconf: bool,
+    /// The method used to derive interface identifiers for
+    /// autoconfigured addrs.
+    pub addr_gen_mode: AddrGenMode,
+    /// The number of solicitations send to detect duplicate
+    /// addrs. A value of zero disables duplicate address detection.
+    pub dup_addr_detect_transmits: usize,
+}
+
+/// The method used to derive interface identifiers for
+/// autoconfigured addrs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddrGenMode {
+    /// The identifier is derived from the MAC address of the
+    /// interface, using the modified EUI-64 format (RFC 4291).
+    Eui64,
+    /// A random identifier is used (RFC 7217). On collisions
+    /// a new identifier is generated.
+    Random,
+}
+
+/// The reachability state of a neighbor (RFC 4861).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeighborState {
+    /// Address resolution is in progress, the link-layer
+    /// address is not yet known.
+    Incomplete,
+    /// The neighbor was recently confirmed to be reachable.
+    Reachable,
+    /// The neighbor is no longer known to be reachable. No
+    /// action is taken until traffic is send to the neighbor.
+    Stale,
+    /// Traffic was send to a stale neighbor. The neighbor will
+    /// be probed, should no confirmation arrive in time.
+    Delay,
+    /// The neighbor is probed with unicast solicitations.
+    Probe,
+    /// A static entry, that never expires.
+    Permanent,
+}
+
+impl Display for NeighborState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Incomplete => write!(f, "INCOMPLETE"),
+            Self::Reachable => write!(f, "REACHABLE"),
+            Self::Stale => write!(f, "STALE"),
+            Self::Delay => write!(f, "DELAY"),
+            Self::Probe => write!(f, "PROBE"),
+            Self::Permanent => write!(f, "PERMANENT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct NeighborEntryInternal {
+    pub hostname: Option<String>,
+    pub state: NeighborState,
+    pub mac: MacAddress,
+    pub iface: IfId,
+    pub router: bool,
+    pub deadline: SimTime,
+    pub probes: usize,
+    pub buffer: Vec<IpPacket>,
+}
+
+pub(super) struct RouterSolicitations {
+    pub remaining: usize,
+    pub deadline: SimTime,
+}
+
+impl Default for NdpConfig {
+    fn default() -> Self {
+        Self {
+            reachable_time: Duration::from_secs(30),
+            retrans_timer: Duration::from_secs(1),
+            delay_first_probe_time: Duration::from_secs(5),
+            max_multicast_solicit: 3,
+            max_unicast_solicit: 3,
+            autoconf: true,
+            addr_gen_mode: AddrGenMode::Eui64,
+            dup_addr_detect_transmits: 1,
+        }
+    }
+}
+
+impl NeighborEntryInternal {
+    /// The current state of the entry. Reachable entries become
+    /// stale without further notice, once the reachable time passed.
+    pub fn state(&self) -> NeighborState {
+        if self.state == NeighborState::Reachable && self.deadline <= SimTime::now() {
+            NeighborState::Stale
+        } else {
+            self.state
+        }
+    }
+
+    /// Indicates whether the entry waits for a timeout.
+    fn is_timed(&self) -> bool {
+        matches!(
+            self.state,
+            NeighborState::Incomplete | NeighborState::Delay | NeighborState::Probe
+        )
+    }
+}
+
+impl NeighborCache {
+    pub fn new() -> Self {
+        Self {
+            map: FxHashMap::with_hasher(FxBuildHasher::default()),
+            config: NdpConfig::default(),
+            solicitations: FxHashMap::with_hasher(FxBuildHasher::default()),
+            autoconf: FxHashMap::with_hasher(FxBuildHasher::default()),
+            advertisers: FxHashMap::with_hasher(FxBuildHasher::default()),
+            active_wakeup: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&Ipv6Addr, &NeighborEntryInternal)> {
+        self.map.iter()
+    }
+
+    /// Adds a static entry, returning packets buffered for the address.
+    #[must_use]
+    pub fn insert_permanent(
+        &mut self,
+        ip: Ipv6Addr,
+        mac: MacAddress,
+        iface: IfId,
+        hostname: Option<String>,
+    ) -> Vec<IpPacket> {
+        let prev = self.map.insert(
+            ip,
+            NeighborEntryInternal {
+                hostname,
+                state: NeighborState::Permanent,
+                mac,
+                iface,
+                router: false,
+                deadline: SimTime::MAX,
+                probes: 0,
+                buffer: Vec::new(),
+            },
+        );
+        prev.map(|entry| entry.buffer).unwrap_or_default()
+    }
+
+    /// The earliest point in time, at which an entry, a router
+    /// solicitation, an autoconfigured address or a router
+    /// advertisement times out.
+    pub(super) fn next_deadline(&self) -> Option<SimTime> {
+        let entries = self
+            .map
+            .values()
+            .filter(|entry| entry.is_timed())
+            .map(|entry| entry.deadline);
+        let solicitations = self.solicitations.values().map(|rs| rs.deadline);
+        let autoconf = self
+            .autoconf
+            .values()
+            .map(|addr| addr.deadline)
+            .filter(|deadline| *deadline != SimTime::MAX);
+        let advertisers = self.advertisers.values().map(|ra| ra.deadline);
+        entries
+            .chain(solicitations)
+            .chain(autoconf)
+            .chain(advertisers)
+            .min()
+    }
+}
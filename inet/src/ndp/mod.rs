@@ -0,0 +1,724 @@
+//! The Neighbor Discovery Protocol (NDP)
+//!
+//! NDP replaces ARP for IPv6 (RFC 4861). Link-layer addresses
+//! are resolved by sending neighbor solicitations to the
+//! solicited-node multicast group of the target, while the
+//! reachability of known neighbors is tracked in the
+//! neighbor cache. Additionally hosts solicit routers on new
+//! interfaces, and learn default routers and on-link prefixes
+//! from router advertisements.
+//!
+//! Hosts autoconfigure addrs from announced prefixes (RFC 4862),
+//! after verifying that no other node uses the same address.
+//! Routers announce prefixes on an interface, once
+//! `start_router_advertisements` was called.
+//!
+//! The user may inspect the neighbor cache using `neighbors`.
+//! The function `set_ndp_config` can be used to configure
+//! the timeouts of the neighbor cache and autoconfiguration.
+//!
+
+use std::io::{self, Error, ErrorKind};
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
+use bytepack::ToBytestream;
+use des::prelude::{schedule_at, Message};
+use des::time::SimTime;
+use inet_types::icmpv6::{
+    solicited_node_multicast, Icmpv6Packet, NdpOption, ALL_NODES_MULTICAST, ALL_ROUTERS_MULTICAST,
+    NDP_HOP_LIMIT, PROTO_ICMPV6,
+};
+use inet_types::iface::MacAddress;
+use inet_types::ip::{IpPacket, Ipv6Packet, KIND_IPV6};
+
+use crate::interface::{IfId, KIND_IO_TIMEOUT};
+use crate::socket::SocketIfaceBinding;
+use crate::IOContext;
+
+mod cache;
+pub use self::cache::*;
+
+mod api;
+pub use self::api::*;
+
+mod slaac;
+pub(crate) use self::slaac::*;
+
+mod router;
+pub use self::router::*;
+
+/// The typ of the NDP wakeup, distinct from the TCP timers
+/// and the reassembly wakeup.
+pub(crate) const TIMER_NDP: u8 = 5;
+
+/// The number of router solicitations send on a new interface.
+const MAX_RTR_SOLICITATIONS: usize = 3;
+
+/// The time between router solicitations.
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+
+/// The lifetime of prefix information, indicating infinity.
+const INFINITE_LIFETIME: u32 = u32::MAX;
+
+impl IOContext {
+    pub(crate) fn recv_ndp(&mut self, ifid: IfId, ip: &Ipv6Packet, icmp: Icmpv6Packet) -> bool {
+        // Packets with any other hop limit could have been forwarded
+        // by a router, thus may originate from another link.
+        if ip.hop_limit != NDP_HOP_LIMIT {
+            tracing::warn!(
+                "dropping ndp packet from {} with hop limit {}",
+                ip.src,
+                ip.hop_limit
+            );
+            return true;
+        }
+
+        match icmp {
+            Icmpv6Packet::NeighborSolicitation { target, .. } => {
+                self.recv_neighbor_solicitation(ifid, ip, target, icmp.source_link_layer_addr());
+            }
+            Icmpv6Packet::NeighborAdvertisement {
+                router,
+                solicited,
+                overrides,
+                target,
+                ..
+            } => {
+                let mac = icmp.target_link_layer_addr();
+                self.recv_neighbor_advertisement(ifid, target, mac, router, solicited, overrides);
+            }
+            Icmpv6Packet::RouterSolicitation { .. } => {
+                // Hosts silently discard router solicitations, routers
+                // answer them on advertising interfaces.
+                let mac = icmp.source_link_layer_addr();
+                self.ndp_recv_router_solicitation(ifid, ip, mac);
+            }
+            Icmpv6Packet::RouterAdvertisement {
+                router_lifetime,
+                reachable_time,
+                retrans_timer,
+                ref options,
+                ..
+            } => {
+                // Router advertisements must originate from link-local addresses.
+                if !is_link_local(ip.src) {
+                    tracing::warn!("dropping router advertisement from {}", ip.src);
+                    return true;
+                }
+                // Routers do not learn from other routers on advertising interfaces.
+                if self.ndp.advertisers.contains_key(&ifid) {
+                    return true;
+                }
+
+                if reachable_time != 0 {
+                    self.ndp.config.reachable_time =
+                        Duration::from_millis(u64::from(reachable_time));
+                }
+                if retrans_timer != 0 {
+                    self.ndp.config.retrans_timer = Duration::from_millis(u64::from(retrans_timer));
+                }
+
+                self.recv_router_advertisement(ifid, ip.src, router_lifetime, options);
+                if let Some(mac) = icmp.source_link_layer_addr() {
+                    self.ndp_update(ifid, ip.src, mac);
+                }
+                if let Some(entry) = self.ndp.map.get_mut(&ip.src) {
+                    entry.router = true;
+                }
+            }
+            _ => unreachable!(),
+        }
+        true
+    }
+
+    fn recv_neighbor_solicitation(
+        &mut self,
+        ifid: IfId,
+        ip: &Ipv6Packet,
+        target: Ipv6Addr,
+        mac: Option<MacAddress>,
+    ) {
+        // (0) Solicitations for tentative addrs indicate a duplicate, if they
+        // originate from another node performing duplicate address detection.
+        if self
+            .ndp
+            .autoconf
+            .get(&target)
+            .is_some_and(|addr| addr.tentative)
+        {
+            if ip.src.is_unspecified() {
+                self.slaac_duplicate(target);
+            }
+            return;
+        }
+
+        // (1) Add sender entry to the neighbor cache
+        if !ip.src.is_unspecified() {
+            if let Some(mac) = mac {
+                self.ndp_update(ifid, ip.src, mac);
+            }
+        }
+
+        // (2) Check whether the target is a local address
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return;
+        };
+        if !iface
+            .addrs
+            .iter()
+            .any(|addr| addr.matches_ip(IpAddr::V6(target)))
+        {
+            return;
+        }
+
+        // (3) Respond with a neighbor advertisement. Solicitations from the
+        // unspecified address are answered to all nodes.
+        let (dest, dest_mac, solicited) = if ip.src.is_unspecified() {
+            let mac = MacAddress::ipv6_multicast(ALL_NODES_MULTICAST);
+            (ALL_NODES_MULTICAST, mac, false)
+        } else {
+            let Some(mac) = mac.or_else(|| self.ndp.map.get(&ip.src).map(|e| e.mac)) else {
+                return;
+            };
+            (ip.src, mac, true)
+        };
+
+        tracing::trace!(
+            "responding to neighbor solicitation for {} with {}",
+            target,
+            iface.device.addr
+        );
+
+        let advertisement = Icmpv6Packet::NeighborAdvertisement {
+            router: self.ndp.advertisers.contains_key(&ifid),
+            solicited,
+            overrides: true,
+            target,
+            options: vec![NdpOption::TargetLinkLayerAddress(iface.device.addr)],
+        };
+        if let Err(e) = self.ndp_send(ifid, target, dest, dest_mac, &advertisement) {
+            tracing::error!("failed to send neighbor advertisement: {e}");
+        }
+    }
+
+    fn recv_neighbor_advertisement(
+        &mut self,
+        ifid: IfId,
+        target: Ipv6Addr,
+        mac: Option<MacAddress>,
+        router: bool,
+        solicited: bool,
+        overrides: bool,
+    ) {
+        // Advertisments for tentative addrs indicate a duplicate
+        if self.slaac_duplicate(target) {
+            return;
+        }
+
+        // Advertisments for unknown neighbors are silently discarded
+        let Some(entry) = self.ndp.map.get_mut(&target) else {
+            return;
+        };
+        if entry.state == NeighborState::Permanent {
+            return;
+        }
+
+        let now = SimTime::now();
+        if entry.state == NeighborState::Incomplete {
+            let Some(mac) = mac else {
+                return;
+            };
+
+            entry.mac = mac;
+            entry.iface = ifid;
+            entry.router = router;
+            if solicited {
+                entry.state = NeighborState::Reachable;
+                entry.deadline = now + self.ndp.config.reachable_time;
+            } else {
+                entry.state = NeighborState::Stale;
+            }
+
+            tracing::trace!("resolved neighbor {target} is {mac}");
+            let buffer = std::mem::take(&mut entry.buffer);
+            self.ndp_flush(ifid, target, buffer);
+            return;
+        }
+
+        let changed = mac.is_some_and(|mac| mac != entry.mac);
+        if changed && !overrides {
+            // Keep the known address, but do no longer trust it
+            if entry.state() == NeighborState::Reachable {
+                entry.state = NeighborState::Stale;
+            }
+            return;
+        }
+
+        if let Some(mac) = mac {
+            entry.mac = mac;
+        }
+        entry.router = router;
+        if solicited {
+            entry.state = NeighborState::Reachable;
+            entry.deadline = now + self.ndp.config.reachable_time;
+        } else if changed {
+            entry.state = NeighborState::Stale;
+        }
+    }
+
+    fn recv_router_advertisement(
+        &mut self,
+        ifid: IfId,
+        router: Ipv6Addr,
+        router_lifetime: u16,
+        options: &[NdpOption],
+    ) {
+        let now = SimTime::now();
+        self.ndp.solicitations.remove(&ifid);
+        let Some(name) = self.ifaces.get(&ifid).map(|iface| iface.name.clone()) else {
+            return;
+        };
+
+        // (0) Update the default router list. A lifetime of zero
+        // indicates that the router is no longer a default router.
+        let expire = now + Duration::from_secs(u64::from(router_lifetime));
+        self.ipv6_fwd
+            .update_default_router(router, name.clone(), expire);
+
+        // (1) Apply the options provided by the router
+        for option in options {
+            match *option {
+                NdpOption::Mtu(mtu) => {
+                    let Some(iface) = self.ifaces.get_mut(&ifid) else {
+                        continue;
+                    };
+                    let Ok(mtu) = u16::try_from(mtu) else {
+                        continue;
+                    };
+                    if (1280..=iface.mtu).contains(&mtu) {
+                        iface.mtu = mtu;
+                    }
+                }
+                NdpOption::PrefixInformation {
+                    prefix_len,
+                    on_link,
+                    autonomous,
+                    valid_lifetime,
+                    preferred_lifetime,
+                    prefix,
+                } => {
+                    // The link-local prefix is always on-link
+                    if is_link_local(prefix) || prefix_len > 128 {
+                        continue;
+                    }
+
+                    if on_link {
+                        let expire = lifetime_expiry(valid_lifetime);
+                        let mask = Ipv6Addr::from(
+                            !u128::MAX.checked_shr(u32::from(prefix_len)).unwrap_or(0),
+                        );
+                        self.ipv6_fwd
+                            .update_on_link_prefix(prefix, mask, name.clone(), expire);
+                    }
+                    if autonomous {
+                        self.slaac_recv_prefix(
+                            ifid,
+                            prefix,
+                            prefix_len,
+                            valid_lifetime,
+                            preferred_lifetime,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // (2) Learned routes are removed once they expire
+        self.routing_schedule_wakeup();
+    }
+
+    /// Updates the link-layer address of a neighbor, learned from
+    /// a solicitation or a router advertisement.
+    fn ndp_update(&mut self, ifid: IfId, ip: Ipv6Addr, mac: MacAddress) {
+        let Some(entry) = self.ndp.map.get_mut(&ip) else {
+            let _ = self.ndp.map.insert(
+                ip,
+                NeighborEntryInternal {
+                    hostname: None,
+                    state: NeighborState::Stale,
+                    mac,
+                    iface: ifid,
+                    router: false,
+                    deadline: SimTime::now(),
+                    probes: 0,
+                    buffer: Vec::new(),
+                },
+            );
+            return;
+        };
+
+        match entry.state {
+            NeighborState::Permanent => {}
+            NeighborState::Incomplete => {
+                entry.mac = mac;
+                entry.iface = ifid;
+                entry.state = NeighborState::Stale;
+
+                let buffer = std::mem::take(&mut entry.buffer);
+                self.ndp_flush(ifid, ip, buffer);
+            }
+            _ if entry.mac != mac => {
+                entry.mac = mac;
+                entry.iface = ifid;
+                entry.state = NeighborState::Stale;
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a static neighbor entry, that never expires.
+    pub(crate) fn ndp_set_permanent(&mut self, ip: Ipv6Addr, mac: MacAddress, ifid: IfId) {
+        let buffer = self.ndp.insert_permanent(ip, mac, ifid, None);
+        self.ndp_flush(ifid, ip, buffer);
+    }
+
+    fn ndp_flush(&mut self, ifid: IfId, ip: Ipv6Addr, buffer: Vec<IpPacket>) {
+        for pkt in buffer {
+            if let Err(e) = self.send_lan_local_ip_packet(
+                SocketIfaceBinding::Bound(ifid),
+                IpAddr::V6(ip),
+                pkt,
+                true,
+            ) {
+                tracing::error!("failed to send buffered packet to {ip}: {e}");
+            }
+        }
+    }
+
+    pub(crate) fn ndp_lookup(
+        &mut self,
+        dest: Ipv6Addr,
+        preferred_iface: &SocketIfaceBinding,
+    ) -> Option<(bool, MacAddress, IfId)> {
+        // Multicast groups map directly to link-layer addresses.
+        if dest.is_multicast() {
+            let ifid = self.ndp_iface_for(preferred_iface, dest).ok()?;
+            return Some((false, MacAddress::ipv6_multicast(dest), ifid));
+        }
+
+        let Some(entry) = self.ndp.map.get_mut(&dest) else {
+            return self.self_addr_lookup(IpAddr::V6(dest), preferred_iface);
+        };
+
+        match entry.state() {
+            NeighborState::Incomplete => None,
+            NeighborState::Stale => {
+                // Give upper layers some time to confirm reachability,
+                // before probing the neighbor.
+                entry.state = NeighborState::Delay;
+                entry.deadline = SimTime::now() + self.ndp.config.delay_first_probe_time;
+                let result = (false, entry.mac, entry.iface);
+                self.ndp_schedule_wakeup();
+                Some(result)
+            }
+            _ => Some((false, entry.mac, entry.iface)),
+        }
+    }
+
+    pub(crate) fn ndp_missing_addr_mapping(
+        &mut self,
+        ifid: SocketIfaceBinding,
+        pkt: IpPacket,
+        dest: Ipv6Addr,
+    ) -> io::Result<()> {
+        // (0) Address resolution is allready in progress
+        if let Some(entry) = self.ndp.map.get_mut(&dest) {
+            entry.buffer.push(pkt);
+            return Ok(());
+        }
+
+        // (1) Initiate address resolution
+        let ifid = self.ndp_iface_for(&ifid, dest)?;
+        tracing::trace!("missing address resolution for {dest}, sending neighbor solicitation");
+
+        self.ndp.map.insert(
+            dest,
+            NeighborEntryInternal {
+                hostname: None,
+                state: NeighborState::Incomplete,
+                mac: MacAddress::NULL,
+                iface: ifid,
+                router: false,
+                deadline: SimTime::now() + self.ndp.config.retrans_timer,
+                probes: 1,
+                buffer: vec![pkt],
+            },
+        );
+        self.ndp_schedule_wakeup();
+        self.ndp_send_solicitation(ifid, dest, None)
+    }
+
+    /// The interface used to reach a neighbor. Loopback
+    /// interfaces are replaced by the first LAN interface.
+    fn ndp_iface_for(&self, binding: &SocketIfaceBinding, dest: Ipv6Addr) -> io::Result<IfId> {
+        let ifid = match binding {
+            SocketIfaceBinding::Bound(ifid) => *ifid,
+            SocketIfaceBinding::Any(ifids) if !ifids.is_empty() => ifids[0],
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::NotConnected,
+                    "socket bound to no interface",
+                ))
+            }
+        };
+
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "interface does not exist anymore",
+            ));
+        };
+
+        if iface.flags.loopback && !dest.is_loopback() {
+            return self
+                .ifaces
+                .iter()
+                .find(|(_, iface)| !iface.flags.loopback)
+                .map(|(ifid, _)| *ifid)
+                .ok_or(Error::new(
+                    ErrorKind::NotConnected,
+                    "no interface to reach neighbor",
+                ));
+        }
+
+        Ok(ifid)
+    }
+
+    /// Sends a neighbor solicitation for the target, either to the solicited-node
+    /// multicast group, or to a known link-layer address to probe reachability.
+    fn ndp_send_solicitation(
+        &mut self,
+        ifid: IfId,
+        target: Ipv6Addr,
+        unicast: Option<MacAddress>,
+    ) -> io::Result<()> {
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "interface does not exist anymore",
+            ));
+        };
+
+        let src = iface.ipv6_src_addr(target).unwrap_or(Ipv6Addr::UNSPECIFIED);
+        let (dest, mac) = match unicast {
+            Some(mac) => (target, mac),
+            None => {
+                let group = solicited_node_multicast(target);
+                (group, MacAddress::ipv6_multicast(group))
+            }
+        };
+
+        let solicitation = Icmpv6Packet::NeighborSolicitation {
+            target,
+            options: vec![NdpOption::SourceLinkLayerAddress(iface.device.addr)],
+        };
+        self.ndp_send(ifid, src, dest, mac, &solicitation)
+    }
+
+    /// Starts soliciting routers on a new interface.
+    pub(crate) fn ndp_solicit_routers(&mut self, ifid: IfId) -> io::Result<()> {
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Ok(());
+        };
+        if iface.flags.loopback || !iface.flags.multicast || iface.ipv6_subnet().is_none() {
+            return Ok(());
+        }
+
+        self.ndp.solicitations.insert(
+            ifid,
+            RouterSolicitations {
+                remaining: MAX_RTR_SOLICITATIONS - 1,
+                deadline: SimTime::now() + RTR_SOLICITATION_INTERVAL,
+            },
+        );
+        self.ndp_schedule_wakeup();
+        self.ndp_send_router_solicitation(ifid)
+    }
+
+    fn ndp_send_router_solicitation(&mut self, ifid: IfId) -> io::Result<()> {
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Ok(());
+        };
+
+        let src = iface
+            .ipv6_src_addr(ALL_ROUTERS_MULTICAST)
+            .unwrap_or(Ipv6Addr::UNSPECIFIED);
+        let solicitation = Icmpv6Packet::RouterSolicitation {
+            options: vec![NdpOption::SourceLinkLayerAddress(iface.device.addr)],
+        };
+        let mac = MacAddress::ipv6_multicast(ALL_ROUTERS_MULTICAST);
+        self.ndp_send(ifid, src, ALL_ROUTERS_MULTICAST, mac, &solicitation)
+    }
+
+    /// Sends a NDP packet directly onto the link, bypassing routing
+    /// and address resolution.
+    fn ndp_send(
+        &mut self,
+        ifid: IfId,
+        src: Ipv6Addr,
+        dest: Ipv6Addr,
+        mac: MacAddress,
+        icmp: &Icmpv6Packet,
+    ) -> io::Result<()> {
+        let Some(iface) = self.ifaces.get_mut(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "interface does not exist anymore",
+            ));
+        };
+
+        let pkt = Ipv6Packet {
+            traffic_class: 0,
+            flow_label: 0,
+            next_header: PROTO_ICMPV6,
+            hop_limit: NDP_HOP_LIMIT,
+            src,
+            dest,
+            content: icmp.to_vec()?,
+        };
+        let msg = Message::new()
+            .kind(KIND_IPV6)
+            .src(iface.device.addr.into())
+            .dest(mac.into())
+            .content(pkt)
+            .build();
+
+        iface.send_buffered(msg)
+    }
+
+    fn ndp_schedule_wakeup(&mut self) {
+        let Some(deadline) = self.ndp.next_deadline() else {
+            return;
+        };
+
+        if self
+            .ndp
+            .active_wakeup
+            .is_some_and(|wakeup| wakeup <= deadline)
+        {
+            return;
+        }
+
+        self.ndp.active_wakeup = Some(deadline);
+        schedule_at(
+            Message::new()
+                .kind(KIND_IO_TIMEOUT)
+                .typ(TIMER_NDP)
+                .id(KIND_IPV6)
+                .build(),
+            deadline,
+        );
+    }
+
+    pub(crate) fn recv_ndp_wakeup(&mut self) {
+        self.ndp.active_wakeup = None;
+
+        let now = SimTime::now();
+        let expired = self
+            .ndp
+            .map
+            .iter()
+            .filter(|(_, entry)| {
+                matches!(
+                    entry.state,
+                    NeighborState::Incomplete | NeighborState::Delay | NeighborState::Probe
+                ) && entry.deadline <= now
+            })
+            .map(|(addr, _)| *addr)
+            .collect::<Vec<_>>();
+
+        // (0) Retransmit solicitations or give up on neighbors
+        for addr in expired {
+            let entry = self.ndp.map.get_mut(&addr).unwrap();
+            let (ifid, mac) = (entry.iface, entry.mac);
+            let result = match entry.state {
+                NeighborState::Incomplete
+                    if entry.probes < self.ndp.config.max_multicast_solicit =>
+                {
+                    entry.probes += 1;
+                    entry.deadline = now + self.ndp.config.retrans_timer;
+                    self.ndp_send_solicitation(ifid, addr, None)
+                }
+                NeighborState::Delay => {
+                    entry.state = NeighborState::Probe;
+                    entry.probes = 1;
+                    entry.deadline = now + self.ndp.config.retrans_timer;
+                    self.ndp_send_solicitation(ifid, addr, Some(mac))
+                }
+                NeighborState::Probe if entry.probes < self.ndp.config.max_unicast_solicit => {
+                    entry.probes += 1;
+                    entry.deadline = now + self.ndp.config.retrans_timer;
+                    self.ndp_send_solicitation(ifid, addr, Some(mac))
+                }
+                _ => {
+                    let entry = self.ndp.map.remove(&addr).unwrap();
+                    tracing::error!(
+                        "neighbor {addr} unreachable, dropping {} packets",
+                        entry.buffer.len()
+                    );
+                    for pkt in entry.buffer {
+                        if let IpPacket::V6(pkt) = pkt {
+                            self.icmpv6_address_unreachable(ifid, &pkt);
+                        }
+                    }
+                    Ok(())
+                }
+            };
+
+            if let Err(e) = result {
+                tracing::error!("failed to send neighbor solicitation: {e}");
+            }
+        }
+
+        // (1) Retransmit router solicitations
+        let expired = self
+            .ndp
+            .solicitations
+            .iter()
+            .filter(|(_, rs)| rs.deadline <= now)
+            .map(|(ifid, _)| *ifid)
+            .collect::<Vec<_>>();
+
+        for ifid in expired {
+            let rs = self.ndp.solicitations.get_mut(&ifid).unwrap();
+            rs.remaining -= 1;
+            rs.deadline = now + RTR_SOLICITATION_INTERVAL;
+            if rs.remaining == 0 {
+                self.ndp.solicitations.remove(&ifid);
+            }
+
+            if let Err(e) = self.ndp_send_router_solicitation(ifid) {
+                tracing::error!("failed to send router solicitation: {e}");
+            }
+        }
+
+        // (2) Probe tentative addrs and remove expired addrs
+        self.slaac_wakeup(now);
+
+        // (3) Send unsolicited router advertisements
+        self.ndp_advertise_wakeup(now);
+
+        self.ndp_schedule_wakeup();
+    }
+}
+
+/// The point in time at which a lifetime, announced in seconds, expires.
+fn lifetime_expiry(lifetime: u32) -> SimTime {
+    if lifetime == INFINITE_LIFETIME {
+        SimTime::MAX
+    } else {
+        SimTime::now() + Duration::from_secs(u64::from(lifetime))
+    }
+}
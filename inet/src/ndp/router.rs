@@ -0,0 +1,305 @@
+use des::prelude::module_name;
+use des::runtime::random;
+use des::time::SimTime;
+use std::io::{self, Error, ErrorKind};
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use inet_types::icmpv6::{Icmpv6Packet, NdpOption, ALL_NODES_MULTICAST, ALL_ROUTERS_MULTICAST};
+use inet_types::iface::MacAddress;
+use inet_types::ip::Ipv6Packet;
+
+use super::{is_link_local, link_local_addr, INFINITE_LIFETIME};
+use crate::interface::{IfId, InterfaceAddr};
+use crate::routing::{FwdEntryV6, RoutingTableId};
+use crate::IOContext;
+
+/// The number of initial advertisements, that are send
+/// in shorter intervals.
+const MAX_INITIAL_RTR_ADVERTISEMENTS: usize = 3;
+
+/// The maximum interval between the initial advertisements.
+const MAX_INITIAL_RTR_ADVERT_INTERVAL: Duration = Duration::from_secs(16);
+
+/// Configuration of the router advertisements send on an interface
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RouterAdvertisementConfig {
+    /// The minimum time between unsolicited advertisements.
+    pub min_interval: Duration,
+    /// The maximum time between unsolicited advertisements.
+    pub max_interval: Duration,
+    /// The duration for which hosts may use this node as a default
+    /// router. A duration of zero indicates that this node should not
+    /// be used as a default router.
+    pub router_lifetime: Duration,
+    /// The hop limit that hosts should use, or zero if unspecified.
+    pub cur_hop_limit: u8,
+    /// The reachable time that hosts should use, or zero if unspecified.
+    pub reachable_time: Duration,
+    /// The retransmission timer that hosts should use, or zero if unspecified.
+    pub retrans_timer: Duration,
+    /// The MTU of the link, if announced.
+    pub mtu: Option<u32>,
+    /// The prefixes announced on the link.
+    pub prefixes: Vec<PrefixAdvertisement>,
+}
+
+/// A prefix announced by router advertisements
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefixAdvertisement {
+    /// The announced prefix.
+    pub prefix: Ipv6Addr,
+    /// The length of the announced prefix.
+    pub prefix_len: u8,
+    /// Whether addrs with this prefix are reachable on the link.
+    pub on_link: bool,
+    /// Whether hosts may autoconfigure addrs from this prefix.
+    pub autonomous: bool,
+    /// The duration for which the prefix is valid.
+    pub valid_lifetime: Duration,
+    /// The duration for which autoconfigured addrs are preferred.
+    pub preferred_lifetime: Duration,
+}
+
+impl PrefixAdvertisement {
+    /// Creates a new on-link prefix, that hosts may use for
+    /// autoconfiguration. The prefix is valid for 30 days, and
+    /// preferred for 7 days.
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Self {
+        Self {
+            prefix,
+            prefix_len,
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: Duration::from_secs(30 * 24 * 60 * 60),
+            preferred_lifetime: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl Default for RouterAdvertisementConfig {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(200),
+            max_interval: Duration::from_secs(600),
+            router_lifetime: Duration::from_secs(1800),
+            cur_hop_limit: 64,
+            reachable_time: Duration::ZERO,
+            retrans_timer: Duration::ZERO,
+            mtu: None,
+            prefixes: Vec::new(),
+        }
+    }
+}
+
+pub(crate) struct RouterAdvertiser {
+    pub cfg: RouterAdvertisementConfig,
+    pub deadline: SimTime,
+    pub initial: usize,
+}
+
+fn lifetime_secs(lifetime: Duration) -> u32 {
+    u32::try_from(lifetime.as_secs()).unwrap_or(INFINITE_LIFETIME)
+}
+
+fn millis(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+impl IOContext {
+    pub(super) fn ndp_start_advertising(
+        &mut self,
+        ifid: IfId,
+        cfg: RouterAdvertisementConfig,
+    ) -> io::Result<()> {
+        if cfg.min_interval > cfg.max_interval {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "minimum advertisement interval exceeds maximum interval",
+            ));
+        }
+
+        let Some(iface) = self.ifaces.get_mut(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no such interface exists",
+            ));
+        };
+        if iface.flags.loopback || !iface.flags.multicast {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "interface does not support multicast",
+            ));
+        }
+
+        // (0) Advertisements are send from a link-local address,
+        // so one is assigned if the interface has none.
+        if !iface
+            .addrs
+            .iter()
+            .any(|addr| matches!(addr, InterfaceAddr::Inet6 { addr, .. } if is_link_local(*addr)))
+        {
+            let addr = link_local_addr(iface.device.addr);
+            iface.addrs.push(InterfaceAddr::Inet6 {
+                addr,
+                prefixlen: 64,
+                scope_id: None,
+            });
+
+            let mac = iface.device.addr;
+            let name = iface.name.clone();
+            let _ = self
+                .ndp
+                .insert_permanent(addr, mac, ifid, Some(module_name()));
+            self.ipv6_fwd.add_entry(
+                FwdEntryV6::local(
+                    Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0),
+                    Ipv6Addr::new(0xffff, 0xffff, 0xffff, 0xffff, 0, 0, 0, 0),
+                    name,
+                ),
+                RoutingTableId::DEFAULT,
+            );
+        }
+
+        // (1) Join the all-routers group, to receive solicitations
+        if !self.ndp.advertisers.contains_key(&ifid) {
+            iface.join_ipv6_group(ALL_ROUTERS_MULTICAST);
+        }
+
+        self.ndp.advertisers.insert(
+            ifid,
+            RouterAdvertiser {
+                cfg,
+                deadline: SimTime::now(),
+                initial: MAX_INITIAL_RTR_ADVERTISEMENTS,
+            },
+        );
+        self.ndp_advertise(ifid)
+    }
+
+    pub(super) fn ndp_stop_advertising(&mut self, ifid: IfId) -> io::Result<()> {
+        let Some(ra) = self.ndp.advertisers.remove(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "interface does not send router advertisements",
+            ));
+        };
+        if let Some(iface) = self.ifaces.get_mut(&ifid) {
+            iface.leave_ipv6_group(ALL_ROUTERS_MULTICAST);
+        }
+
+        // Inform hosts that this node is no longer a default router
+        self.ndp_send_router_advertisement(ifid, &ra.cfg, Duration::ZERO)
+    }
+
+    /// Responds to a router solicitation on an advertising interface.
+    pub(super) fn ndp_recv_router_solicitation(
+        &mut self,
+        ifid: IfId,
+        ip: &Ipv6Packet,
+        mac: Option<MacAddress>,
+    ) {
+        let Some(ra) = self.ndp.advertisers.get(&ifid) else {
+            return;
+        };
+        let cfg = ra.cfg.clone();
+
+        if !ip.src.is_unspecified() {
+            if let Some(mac) = mac {
+                self.ndp_update(ifid, ip.src, mac);
+            }
+        }
+
+        if let Err(e) = self.ndp_send_router_advertisement(ifid, &cfg, cfg.router_lifetime) {
+            tracing::error!("failed to send router advertisement: {e}");
+        }
+    }
+
+    /// Sends an unsolicited router advertisement, and schedules the next one.
+    fn ndp_advertise(&mut self, ifid: IfId) -> io::Result<()> {
+        let Some(ra) = self.ndp.advertisers.get_mut(&ifid) else {
+            return Ok(());
+        };
+
+        let spread = ra.cfg.max_interval - ra.cfg.min_interval;
+        let mut interval = ra.cfg.min_interval + spread.mul_f64(random::<f64>());
+        if ra.initial > 0 {
+            ra.initial -= 1;
+            interval = interval.min(MAX_INITIAL_RTR_ADVERT_INTERVAL);
+        }
+        ra.deadline = SimTime::now() + interval;
+
+        let cfg = ra.cfg.clone();
+        self.ndp_schedule_wakeup();
+        self.ndp_send_router_advertisement(ifid, &cfg, cfg.router_lifetime)
+    }
+
+    fn ndp_send_router_advertisement(
+        &mut self,
+        ifid: IfId,
+        cfg: &RouterAdvertisementConfig,
+        router_lifetime: Duration,
+    ) -> io::Result<()> {
+        let Some(iface) = self.ifaces.get(&ifid) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "interface does not exist anymore",
+            ));
+        };
+        let Some(src) = iface
+            .ipv6_src_addr(ALL_NODES_MULTICAST)
+            .filter(|addr| is_link_local(*addr))
+        else {
+            return Err(Error::new(
+                ErrorKind::AddrNotAvailable,
+                "interface has no link-local address",
+            ));
+        };
+
+        let mut options = vec![NdpOption::SourceLinkLayerAddress(iface.device.addr)];
+        if let Some(mtu) = cfg.mtu {
+            options.push(NdpOption::Mtu(mtu));
+        }
+        options.extend(
+            cfg.prefixes
+                .iter()
+                .map(|prefix| NdpOption::PrefixInformation {
+                    prefix_len: prefix.prefix_len,
+                    on_link: prefix.on_link,
+                    autonomous: prefix.autonomous,
+                    valid_lifetime: lifetime_secs(prefix.valid_lifetime),
+                    preferred_lifetime: lifetime_secs(prefix.preferred_lifetime),
+                    prefix: prefix.prefix,
+                }),
+        );
+
+        let advertisement = Icmpv6Packet::RouterAdvertisement {
+            cur_hop_limit: cfg.cur_hop_limit,
+            managed: false,
+            other: false,
+            router_lifetime: u16::try_from(router_lifetime.as_secs()).unwrap_or(u16::MAX),
+            reachable_time: millis(cfg.reachable_time),
+            retrans_timer: millis(cfg.retrans_timer),
+            options,
+        };
+        let mac = MacAddress::ipv6_multicast(ALL_NODES_MULTICAST);
+        self.ndp_send(ifid, src, ALL_NODES_MULTICAST, mac, &advertisement)
+    }
+
+    /// Sends the unsolicited router advertisements, that are due.
+    pub(super) fn ndp_advertise_wakeup(&mut self, now: SimTime) {
+        let expired = self
+            .ndp
+            .advertisers
+            .iter()
+            .filter(|(_, ra)| ra.deadline <= now)
+            .map(|(ifid, _)| *ifid)
+            .collect::<Vec<_>>();
+
+        for ifid in expired {
+            if let Err(e) = self.ndp_advertise(ifid) {
+                tracing::error!("failed to send router advertisement: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use des::prelude::module_name;
+use des::runtime::random;
+use des::time::SimTime;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
+use inet_types::icmpv6::{solicited_node_multicast, Icmpv6Packet};
+use inet_types::iface::MacAddress;
+
+use super::{lifetime_expiry, AddrGenMode};
+use crate::interface::{IfId, InterfaceAddr};
+use crate::IOContext;
+
+/// The number of new interface identifiers tried, after a random
+/// identifier collided with another address (RFC 7217).
+const IDGEN_RETRIES: usize = 3;
+
+/// Valid lifetimes below this bound are only accepted, if they extend
+/// the remaining lifetime of an address (RFC 4862).
+const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// The prefix length required for autoconfiguration, since interface
+/// identifiers are 64 bits long.
+const AUTOCONF_PREFIX_LEN: u8 = 64;
+
+/// An address derived from a prefix announced by a router.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct AutoconfAddr {
+    pub iface: IfId,
+    pub prefix: Ipv6Addr,
+    /// Whether duplicate address detection is still in progress.
+    pub tentative: bool,
+    /// The next probe for tentative addrs, the end of the valid
+    /// lifetime otherwise.
+    pub deadline: SimTime,
+    pub valid_until: SimTime,
+    pub probes: usize,
+    pub retries: usize,
+}
+
+/// Indicates whether the address is a link-local unicast address.
+pub(crate) fn is_link_local(addr: Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// The link-local address of an interface, derived from its MAC address.
+pub(crate) fn link_local_addr(mac: MacAddress) -> Ipv6Addr {
+    with_interface_id(
+        Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0),
+        eui64_interface_id(mac),
+    )
+}
+
+/// The modified EUI-64 interface identifier of a MAC address (RFC 4291).
+fn eui64_interface_id(mac: MacAddress) -> u64 {
+    let mac = mac.as_slice();
+    u64::from_be_bytes([
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ])
+}
+
+fn with_interface_id(prefix: Ipv6Addr, id: u64) -> Ipv6Addr {
+    let prefix = u128::from_be_bytes(prefix.octets()) & !u128::from(u64::MAX);
+    Ipv6Addr::from(prefix | u128::from(id))
+}
+
+impl IOContext {
+    /// Autoconfigures an address from a prefix announced by a router,
+    /// or updates the lifetime of an existing address (RFC 4862).
+    pub(super) fn slaac_recv_prefix(
+        &mut self,
+        ifid: IfId,
+        prefix: Ipv6Addr,
+        prefix_len: u8,
+        valid_lifetime: u32,
+        preferred_lifetime: u32,
+    ) {
+        if !self.ndp.config.autoconf || is_link_local(prefix) || preferred_lifetime > valid_lifetime
+        {
+            return;
+        }
+        if prefix_len != AUTOCONF_PREFIX_LEN {
+            tracing::warn!("cannot autoconfigure address from prefix {prefix}/{prefix_len}");
+            return;
+        }
+
+        let now = SimTime::now();
+        let prefix = with_interface_id(prefix, 0);
+        let valid_until = lifetime_expiry(valid_lifetime);
+
+        // (0) Update the lifetime of known addrs. Short lifetimes are
+        // ignored, so that spoofed advertisements cannot remove addrs.
+        let known = self
+            .ndp
+            .autoconf
+            .values_mut()
+            .find(|addr| addr.iface == ifid && addr.prefix == prefix);
+        if let Some(addr) = known {
+            if valid_until > now + MIN_VALID_LIFETIME || valid_until > addr.valid_until {
+                addr.valid_until = valid_until;
+            } else if addr.valid_until > now + MIN_VALID_LIFETIME {
+                addr.valid_until = now + MIN_VALID_LIFETIME;
+            }
+            if !addr.tentative {
+                addr.deadline = addr.valid_until;
+            }
+            self.ndp_schedule_wakeup();
+            return;
+        }
+
+        // (1) Derive a new address from the prefix
+        if valid_lifetime != 0 {
+            self.slaac_start(ifid, prefix, valid_until, 0);
+        }
+    }
+
+    /// Starts duplicate address detection for a new address.
+    fn slaac_start(&mut self, ifid: IfId, prefix: Ipv6Addr, valid_until: SimTime, retries: usize) {
+        let Some(iface) = self.ifaces.get_mut(&ifid) else {
+            return;
+        };
+
+        let id = match self.ndp.config.addr_gen_mode {
+            AddrGenMode::Eui64 => eui64_interface_id(iface.device.addr),
+            AddrGenMode::Random => random(),
+        };
+        let addr = with_interface_id(prefix, id);
+        if self.ndp.autoconf.contains_key(&addr)
+            || iface
+                .addrs
+                .iter()
+                .any(|iaddr| iaddr.matches_ip(IpAddr::V6(addr)))
+        {
+            return;
+        }
+
+        self.ndp.autoconf.insert(
+            addr,
+            AutoconfAddr {
+                iface: ifid,
+                prefix,
+                tentative: true,
+                deadline: SimTime::now() + self.ndp.config.retrans_timer,
+                valid_until,
+                probes: 1,
+                retries,
+            },
+        );
+        if self.ndp.config.dup_addr_detect_transmits == 0 {
+            self.slaac_assign(addr);
+            return;
+        }
+
+        // Join the solicited-node group of the tentative address,
+        // to receive the probes of other nodes using the same address.
+        tracing::trace!("starting duplicate address detection for {addr}");
+        iface.join_ipv6_group(solicited_node_multicast(addr));
+        self.ndp_schedule_wakeup();
+        if let Err(e) = self.slaac_send_probe(ifid, addr) {
+            tracing::error!("failed to send duplicate address detection probe: {e}");
+        }
+    }
+
+    fn slaac_send_probe(&mut self, ifid: IfId, addr: Ipv6Addr) -> io::Result<()> {
+        let group = solicited_node_multicast(addr);
+        let solicitation = Icmpv6Packet::NeighborSolicitation {
+            target: addr,
+            options: Vec::new(),
+        };
+        self.ndp_send(
+            ifid,
+            Ipv6Addr::UNSPECIFIED,
+            group,
+            MacAddress::ipv6_multicast(group),
+            &solicitation,
+        )
+    }
+
+    /// Assigns a tentative address to its interface, once no
+    /// duplicate was detected.
+    fn slaac_assign(&mut self, addr: Ipv6Addr) {
+        let Some(entry) = self.ndp.autoconf.get_mut(&addr) else {
+            return;
+        };
+        entry.tentative = false;
+        entry.deadline = entry.valid_until;
+
+        let ifid = entry.iface;
+        let Some(iface) = self.ifaces.get_mut(&ifid) else {
+            return;
+        };
+
+        iface.leave_ipv6_group(solicited_node_multicast(addr));
+        iface.addrs.push(InterfaceAddr::Inet6 {
+            addr,
+            prefixlen: usize::from(AUTOCONF_PREFIX_LEN),
+            scope_id: None,
+        });
+        tracing::info!("autoconfigured address {addr} on {}", iface.name);
+
+        let mac = iface.device.addr;
+        let buffer = self
+            .ndp
+            .insert_permanent(addr, mac, ifid, Some(module_name()));
+        self.ndp_flush(ifid, addr, buffer);
+        self.ndp_schedule_wakeup();
+    }
+
+    fn slaac_remove(&mut self, addr: Ipv6Addr) -> Option<AutoconfAddr> {
+        let entry = self.ndp.autoconf.remove(&addr)?;
+        let iface = self.ifaces.get_mut(&entry.iface)?;
+        if entry.tentative {
+            iface.leave_ipv6_group(solicited_node_multicast(addr));
+        } else {
+            iface
+                .addrs
+                .retain(|iaddr| !iaddr.matches_ip(IpAddr::V6(addr)));
+            self.ndp.map.remove(&addr);
+        }
+        Some(entry)
+    }
+
+    /// Handles a duplicate of a tentative address, returning
+    /// whether the address was tentative.
+    pub(super) fn slaac_duplicate(&mut self, addr: Ipv6Addr) -> bool {
+        if !self
+            .ndp
+            .autoconf
+            .get(&addr)
+            .is_some_and(|entry| entry.tentative)
+        {
+            return false;
+        }
+
+        let Some(entry) = self.slaac_remove(addr) else {
+            return true;
+        };
+        tracing::error!("duplicate address {addr} detected, address was not assigned");
+
+        // Random identifiers may be regenerated, identifiers derived
+        // from the MAC address would collide again.
+        if self.ndp.config.addr_gen_mode == AddrGenMode::Random && entry.retries < IDGEN_RETRIES {
+            self.slaac_start(
+                entry.iface,
+                entry.prefix,
+                entry.valid_until,
+                entry.retries + 1,
+            );
+        }
+        true
+    }
+
+    /// Probes tentative addrs, and removes addrs whose
+    /// valid lifetime expired.
+    pub(super) fn slaac_wakeup(&mut self, now: SimTime) {
+        let expired = self
+            .ndp
+            .autoconf
+            .iter()
+            .filter(|(_, entry)| entry.deadline <= now)
+            .map(|(addr, _)| *addr)
+            .collect::<Vec<_>>();
+
+        for addr in expired {
+            let transmits = self.ndp.config.dup_addr_detect_transmits;
+            let entry = self.ndp.autoconf.get_mut(&addr).unwrap();
+            if !entry.tentative || entry.valid_until <= now {
+                tracing::info!("autoconfigured address {addr} expired");
+                self.slaac_remove(addr);
+            } else if entry.probes < transmits {
+                entry.probes += 1;
+                entry.deadline = now + self.ndp.config.retrans_timer;
+                let ifid = entry.iface;
+                if let Err(e) = self.slaac_send_probe(ifid, addr) {
+                    tracing::error!("failed to send duplicate address detection probe: {e}");
+                }
+            } else {
+                self.slaac_assign(addr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eui64_link_local_addr() {
+        let mac = MacAddress::from([0x00, 0x1b, 0x63, 0x84, 0x45, 0xe6]);
+        assert_eq!(
+            link_local_addr(mac),
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0x021b, 0x63ff, 0xfe84, 0x45e6)
+        );
+        assert!(is_link_local(link_local_addr(mac)));
+    }
+
+    #[test]
+    fn interface_id_replaces_host_bits() {
+        let prefix = Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0xffff, 0, 0, 1);
+        assert_eq!(
+            with_interface_id(prefix, 0x0102_0304_0506_0708),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0x0102, 0x0304, 0x0506, 0x0708)
+        );
+    }
+}
@@ -1,10 +1,59 @@
+use des::{
+    net::message::MessageKind,
+    prelude::{schedule_at, Message},
+    time::SimTime,
+};
+use inet_types::ip::{IpVersion, KIND_IPV4, KIND_IPV6};
 use std::{
     io::{self, Error, ErrorKind},
     net::IpAddr,
+    time::Duration,
+};
+
+use super::{
+    FwdEntry, FwdEntryV4, FwdEntryV6, Ipv4Gateway, Ipv6Gateway, RoutingRule, RoutingTableId,
 };
+use crate::{interface::KIND_IO_TIMEOUT, IOContext};
+
+pub(crate) const TIMER_ROUTE: u8 = 6;
 
-use super::{FwdEntryV4, Ipv4Gateway, RoutingTableId};
-use crate::IOContext;
+/// Options for adding or replacing a routing entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RouteOptions {
+    /// The metric of the entry. For entries with an equal prefix,
+    /// the entry with the lowest metric is used.
+    pub metric: u32,
+    /// The lifetime of the entry, after which the entry is removed.
+    /// Entries without a lifetime never expire.
+    pub lifetime: Option<Duration>,
+    /// The routing table, the entry is added to.
+    pub table: RoutingTableId,
+    /// The weight of the entry among multiple next hops with equal
+    /// cost, see `append_routing_entry`. Only used for IPv4.
+    pub weight: u32,
+}
+
+/// How a routing entry is added to the existing entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AddMode {
+    /// Replace entries with equal prefix and metric.
+    Add,
+    /// Add an additional next hop to entries with equal prefix and metric.
+    Append,
+    /// Replace all entries with equal prefix.
+    Replace,
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        Self {
+            metric: 0,
+            lifetime: None,
+            table: RoutingTableId::DEFAULT,
+            weight: 1,
+        }
+    }
+}
 
 /// Sets the default routing gateway for the entire node.
 pub fn set_default_gateway(ip: impl Into<IpAddr>) -> io::Result<()> {
@@ -24,11 +73,133 @@ pub fn add_routing_entry(
             mask.into(),
             gw.into(),
             interface,
-            RoutingTableId::DEFAULT,
+            RouteOptions::default(),
+            AddMode::Add,
+        )
+    })
+}
+
+/// Adds a routing entry with a metric and lifetime to the routing tables
+///
+/// This function is roughly equivalent to the shell command
+/// `ip route add <subnet> via <gw> dev <iface> metric <metric>`. An
+/// existing entry with equal prefix and metric is replaced, while entries
+/// with other metrics remain as alternatives. Entries with a lifetime are
+/// removed automatically once it elapsed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::routing::*;
+/// use std::{net::Ipv4Addr, time::Duration};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let subnet = Ipv4Addr::new(10, 0, 3, 0);
+/// let mask = Ipv4Addr::new(255, 255, 255, 0);
+///
+/// // A backup route, used if the primary route is removed or expires
+/// add_routing_entry_with(subnet, mask, Ipv4Addr::new(10, 0, 1, 1), "en0", RouteOptions {
+///     metric: 100,
+///     ..Default::default()
+/// })?;
+/// add_routing_entry_with(subnet, mask, Ipv4Addr::new(10, 0, 2, 1), "en1", RouteOptions {
+///     metric: 10,
+///     lifetime: Some(Duration::from_secs(180)),
+///     ..Default::default()
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn add_routing_entry_with(
+    addr: impl Into<IpAddr>,
+    mask: impl Into<IpAddr>,
+    gw: impl Into<IpAddr>,
+    interface: &str,
+    opts: RouteOptions,
+) -> io::Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.add_routing_entry(
+            addr.into(),
+            mask.into(),
+            gw.into(),
+            interface,
+            opts,
+            AddMode::Add,
+        )
+    })
+}
+
+/// Adds a routing entry as an additional next hop for a subnet
+///
+/// This function is roughly equivalent to the shell command
+/// `ip route append`. Entries with equal prefix and metric form a
+/// group of equal-cost next hops, among which packets are distributed
+/// by the multipath policy and the weights of the entries. An existing
+/// entry with the same next hop is replaced. Only IPv4 entries may have
+/// multiple next hops.
+///
+/// See `set_multipath_policy` for an example.
+pub fn append_routing_entry(
+    addr: impl Into<IpAddr>,
+    mask: impl Into<IpAddr>,
+    gw: impl Into<IpAddr>,
+    interface: &str,
+    opts: RouteOptions,
+) -> io::Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.add_routing_entry(
+            addr.into(),
+            mask.into(),
+            gw.into(),
+            interface,
+            opts,
+            AddMode::Append,
+        )
+    })
+}
+
+/// Replaces all routing entries for a subnet with a single entry
+///
+/// This function is roughly equivalent to the shell command
+/// `ip route replace`. All entries for the prefix in the table
+/// given by the options are removed, regardless of their metric.
+pub fn replace_routing_entry(
+    addr: impl Into<IpAddr>,
+    mask: impl Into<IpAddr>,
+    gw: impl Into<IpAddr>,
+    interface: &str,
+    opts: RouteOptions,
+) -> io::Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.add_routing_entry(
+            addr.into(),
+            mask.into(),
+            gw.into(),
+            interface,
+            opts,
+            AddMode::Replace,
         )
     })
 }
 
+/// Removes all routing entries for a subnet from the default routing table
+///
+/// This function is roughly equivalent to the shell command
+/// `ip route del`. If no entry for the subnet exists,
+/// an error of kind `NotFound` is returned.
+pub fn remove_routing_entry(addr: impl Into<IpAddr>, mask: impl Into<IpAddr>) -> io::Result<()> {
+    remove_routing_entry_from(addr, mask, RoutingTableId::DEFAULT)
+}
+
+/// Removes all routing entries for a subnet from a routing table.
+pub fn remove_routing_entry_from(
+    addr: impl Into<IpAddr>,
+    mask: impl Into<IpAddr>,
+    table_id: RoutingTableId,
+) -> io::Result<()> {
+    IOContext::failable_api(|ctx| ctx.remove_routing_entry(addr.into(), mask.into(), table_id))
+}
+
 #[must_use]
 pub fn add_routing_table() -> io::Result<RoutingTableId> {
     IOContext::failable_api(|ctx| ctx.add_routing_table())
@@ -41,19 +212,78 @@ pub fn add_routing_entry_to(
     interface: &str,
     table_id: RoutingTableId,
 ) -> io::Result<()> {
+    let opts = RouteOptions {
+        table: table_id,
+        ..Default::default()
+    };
     IOContext::failable_api(|ctx| {
-        ctx.add_routing_entry(addr.into(), mask.into(), gw.into(), interface, table_id)
+        ctx.add_routing_entry(
+            addr.into(),
+            mask.into(),
+            gw.into(),
+            interface,
+            opts,
+            AddMode::Add,
+        )
     })
 }
 
-/// Returns the contents of the routing table
-pub fn route() -> io::Result<Vec<FwdEntryV4>> {
+/// Returns the contents of the routing tables of both address families,
+/// with the entries of the newest table first.
+pub fn route() -> io::Result<Vec<FwdEntry>> {
     IOContext::failable_api(|ctx| Ok(ctx.route()))
 }
 
+/// Adds a policy routing rule
+///
+/// This function is roughly equivalent to the shell command
+/// `ip rule add`. Rules select the routing table used for IPv4 packets
+/// by their source address, ingress interface, DSCP value, or the mark
+/// of the sending socket. Tables selected by at least one rule are
+/// only used through their rules.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::routing::*;
+/// use std::net::Ipv4Addr;
+///
+/// # fn main() -> std::io::Result<()> {
+/// // Traffic from 10.0.2.0/24 uses its own uplink
+/// let table = add_routing_table()?;
+/// add_routing_entry_to(
+///     Ipv4Addr::UNSPECIFIED,
+///     Ipv4Addr::UNSPECIFIED,
+///     Ipv4Addr::new(10, 0, 2, 1),
+///     "en1",
+///     table,
+/// )?;
+/// add_routing_rule(RoutingRule {
+///     from: Some((Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0))),
+///     ..RoutingRule::new(100, table)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn add_routing_rule(rule: RoutingRule) -> io::Result<()> {
+    IOContext::failable_api(|ctx| ctx.ipv4_fwd.add_rule(rule))
+}
+
+/// Removes a policy routing rule, that is equal to the provided rule.
+pub fn remove_routing_rule(rule: &RoutingRule) -> io::Result<()> {
+    IOContext::failable_api(|ctx| ctx.ipv4_fwd.remove_rule(rule))
+}
+
+/// Returns all policy routing rules, in the order of their evaluation.
+pub fn routing_rules() -> io::Result<Vec<RoutingRule>> {
+    IOContext::failable_api(|ctx| Ok(ctx.ipv4_fwd.rules()))
+}
+
 impl IOContext {
-    fn route(&mut self) -> Vec<FwdEntryV4> {
-        self.ipv4_fwd.entries()
+    fn route(&mut self) -> Vec<FwdEntry> {
+        let v4 = self.ipv4_fwd.entries().into_iter().map(FwdEntry::V4);
+        let v6 = self.ipv6_fwd.entries().into_iter().map(FwdEntry::V6);
+        v4.chain(v6).collect()
     }
 
     fn set_default_gateway(&mut self, ip: IpAddr) -> io::Result<()> {
@@ -73,7 +303,9 @@ impl IOContext {
             IpAddr::V4(ip) => self
                 .ipv4_fwd
                 .set_default_gw(Ipv4Gateway::Gateway(ip), iface.name.clone()),
-            IpAddr::V6(_) => todo!(),
+            IpAddr::V6(ip) => self
+                .ipv6_fwd
+                .set_default_gw(Ipv6Gateway::Gateway(ip), iface.name.clone()),
         }
 
         Ok(())
@@ -85,9 +317,16 @@ impl IOContext {
         mask: IpAddr,
         gw: IpAddr,
         interface: &str,
-        table_id: RoutingTableId,
+        opts: RouteOptions,
+        mode: AddMode,
     ) -> io::Result<()> {
         // Defines a route to a subnet via a gateway and a defined interface
+        if opts.weight == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "routing entry weight must be positive",
+            ));
+        }
 
         let Some(iface) = self.ifaces.values().find(|iface| {
             iface
@@ -101,29 +340,125 @@ impl IOContext {
             ))
         };
 
+        let expires = opts
+            .lifetime
+            .map_or(SimTime::MAX, |lifetime| SimTime::now() + lifetime);
+
         use IpAddr::{V4, V6};
         match (subnet, mask, gw) {
             (V4(dest), V4(mask), V4(gw)) => {
-                self.ipv4_fwd.add_entry(
-                    FwdEntryV4 {
-                        dest,
-                        mask,
-                        gateway: Ipv4Gateway::Gateway(gw),
-                        iface: iface.name.clone(),
-                    },
-                    table_id,
-                );
+                let entry = FwdEntryV4 {
+                    dest,
+                    mask,
+                    gateway: Ipv4Gateway::Gateway(gw),
+                    iface: iface.name.clone(),
+                    metric: opts.metric,
+                    weight: opts.weight,
+                    expires,
+                };
+                match mode {
+                    AddMode::Add => self.ipv4_fwd.try_add_entry(entry, opts.table)?,
+                    AddMode::Append => self.ipv4_fwd.append_entry(entry, opts.table)?,
+                    AddMode::Replace => self.ipv4_fwd.replace_entry(entry, opts.table)?,
+                }
             }
-            (V6(_dest), V6(_mask), V6(_gw)) => {
-                todo!()
+            (V6(dest), V6(mask), V6(gw)) => {
+                let entry = FwdEntryV6 {
+                    dest,
+                    mask,
+                    gateway: Ipv6Gateway::Gateway(gw),
+                    iface: iface.name.clone(),
+                    metric: opts.metric,
+                    expires,
+                };
+                match mode {
+                    AddMode::Add => self.ipv6_fwd.try_add_entry(entry, opts.table)?,
+                    AddMode::Append => {
+                        return Err(Error::new(
+                            ErrorKind::Unsupported,
+                            "ipv6 routing entries cannot have multiple next hops",
+                        ))
+                    }
+                    AddMode::Replace => self.ipv6_fwd.replace_entry(entry, opts.table)?,
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "subnet, mask and gateway must be of the same address family",
+                ))
             }
-            _ => unreachable!(),
         }
 
+        self.routing_schedule_wakeup();
         Ok(())
     }
 
+    fn remove_routing_entry(
+        &mut self,
+        subnet: IpAddr,
+        mask: IpAddr,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        match (subnet, mask) {
+            (IpAddr::V4(dest), IpAddr::V4(mask)) => {
+                self.ipv4_fwd.remove_entries(dest, mask, table_id)
+            }
+            (IpAddr::V6(dest), IpAddr::V6(mask)) => {
+                self.ipv6_fwd.remove_entries(dest, mask, table_id)
+            }
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "subnet and mask must be of the same address family",
+            )),
+        }
+    }
+
     fn add_routing_table(&mut self) -> io::Result<RoutingTableId> {
-        self.ipv4_fwd.add_table()
+        // Tables are created for both address families, so
+        // that table ids are valid for either family.
+        let table_id = self.ipv4_fwd.add_table()?;
+        let v6_table_id = self.ipv6_fwd.add_table();
+        debug_assert_eq!(table_id, v6_table_id);
+        Ok(table_id)
     }
+
+    pub(crate) fn routing_schedule_wakeup(&mut self) {
+        if let Some(deadline) = self.ipv4_fwd.next_deadline() {
+            schedule_route_wakeup(&mut self.ipv4_fwd.active_wakeup, deadline, KIND_IPV4);
+        }
+        if let Some(deadline) = self.ipv6_fwd.next_deadline() {
+            schedule_route_wakeup(&mut self.ipv6_fwd.active_wakeup, deadline, KIND_IPV6);
+        }
+    }
+
+    pub(crate) fn recv_routing_wakeup(&mut self, version: IpVersion) {
+        match version {
+            IpVersion::V4 => {
+                self.ipv4_fwd.active_wakeup = None;
+                self.ipv4_fwd.remove_expired(SimTime::now());
+            }
+            IpVersion::V6 => {
+                self.ipv6_fwd.active_wakeup = None;
+                self.ipv6_fwd.remove_expired(SimTime::now());
+            }
+        }
+        self.routing_schedule_wakeup();
+    }
+}
+
+fn schedule_route_wakeup(active_wakeup: &mut Option<SimTime>, deadline: SimTime, id: MessageKind) {
+    if active_wakeup.is_some_and(|wakeup| wakeup <= deadline) {
+        return;
+    }
+
+    *active_wakeup = Some(deadline);
+    schedule_at(
+        Message::new()
+            .kind(KIND_IO_TIMEOUT)
+            .typ(TIMER_ROUTE)
+            .id(id)
+            .build(),
+        deadline,
+    );
 }
@@ -0,0 +1,113 @@
+use fxhash::{FxBuildHasher, FxHashMap};
+use inet_types::ip::IpVersion;
+use std::io::{self, Error, ErrorKind};
+
+use crate::{interface::IfId, IOContext};
+
+/// Configuration, whether IP packets not addressed to
+/// the local node are forwarded, or dropped.
+///
+/// Forwarding is enabled for both address families by default, so
+/// that every node may act as a router. End hosts should disable
+/// forwarding, to drop packets that were misdirected to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IpForwarding {
+    ipv4: bool,
+    ipv6: bool,
+    ifaces: FxHashMap<(IfId, IpVersion), bool>,
+}
+
+impl IpForwarding {
+    pub fn new() -> Self {
+        Self {
+            ipv4: true,
+            ipv6: true,
+            ifaces: FxHashMap::with_hasher(FxBuildHasher::default()),
+        }
+    }
+
+    /// Whether packets of the address family, received on the
+    /// interface, are forwarded.
+    pub fn enabled(&self, ifid: IfId, version: IpVersion) -> bool {
+        self.ifaces
+            .get(&(ifid, version))
+            .copied()
+            .unwrap_or(match version {
+                IpVersion::V4 => self.ipv4,
+                IpVersion::V6 => self.ipv6,
+            })
+    }
+}
+
+/// Enables or disables IP forwarding for an address family
+///
+/// This function is roughly equivalent to the sysctls
+/// `net.ipv4.ip_forward` and `net.ipv6.conf.all.forwarding`.
+/// Nodes with forwarding disabled act as hosts, dropping all
+/// packets not addressed to the node itself. Routers respond
+/// to packets without a route with ICMP Destination Unreachable
+/// errors. Forwarding is enabled by default.
+///
+/// Overrides for specific interfaces, set by `set_ip_forward_on`,
+/// take precedence over this setting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::routing::set_ip_forward;
+/// use inet::types::ip::IpVersion;
+///
+/// # fn main() -> std::io::Result<()> {
+/// // This node is an end host
+/// set_ip_forward(IpVersion::V4, false)?;
+/// set_ip_forward(IpVersion::V6, false)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_ip_forward(version: IpVersion, enabled: bool) -> io::Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.set_ip_forward(version, enabled);
+        Ok(())
+    })
+}
+
+/// Enables or disables IP forwarding for packets received
+/// on a specific interface
+///
+/// This setting overrides the node-wide setting of `set_ip_forward`
+/// for this interface, and is roughly equivalent to the sysctl
+/// `net.ipv4.conf.<iface>.forwarding`.
+pub fn set_ip_forward_on(ifid: &IfId, version: IpVersion, enabled: bool) -> io::Result<()> {
+    IOContext::failable_api(|ctx| ctx.set_ip_forward_on(*ifid, version, enabled))
+}
+
+/// Indicates whether packets of an address family, received
+/// on the given interface, are forwarded.
+pub fn ip_forward(ifid: &IfId, version: IpVersion) -> io::Result<bool> {
+    IOContext::failable_api(|ctx| Ok(ctx.ip_forward.enabled(*ifid, version)))
+}
+
+impl IOContext {
+    fn set_ip_forward(&mut self, version: IpVersion, enabled: bool) {
+        match version {
+            IpVersion::V4 => self.ip_forward.ipv4 = enabled,
+            IpVersion::V6 => self.ip_forward.ipv6 = enabled,
+        }
+    }
+
+    fn set_ip_forward_on(
+        &mut self,
+        ifid: IfId,
+        version: IpVersion,
+        enabled: bool,
+    ) -> io::Result<()> {
+        if !self.ifaces.contains_key(&ifid) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no such interface exists",
+            ));
+        }
+        self.ip_forward.ifaces.insert((ifid, version), enabled);
+        Ok(())
+    }
+}
@@ -1,21 +1,47 @@
+use super::{
+    next_hop_key, publish, FwdEntry, Multipath, RouteCounter, RouteEvent, RouteKey, RoutingRule,
+    Watchers,
+};
 use crate::interface::InterfaceName;
-use std::{fmt::Display, io, net::Ipv4Addr};
+use des::time::SimTime;
+use inet_types::ip::Ipv4Packet;
+use std::{
+    fmt::Display,
+    io::{self, Error, ErrorKind},
+    mem,
+    net::Ipv4Addr,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct RoutingTableId(usize);
+pub struct RoutingTableId(pub(super) usize);
 impl RoutingTableId {
     pub const DEFAULT: RoutingTableId = RoutingTableId(0);
 }
 
+impl Display for RoutingTableId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct FwdV4 {
     tables: Vec<FwdTableV4>,
+    // A list of all routing rules, with the lowest priority first
+    rules: Vec<RoutingRule>,
+    pub(super) watchers: Watchers,
+    pub(super) active_wakeup: Option<SimTime>,
+    pub(super) multipath: Multipath,
 }
 
 impl FwdV4 {
     pub(crate) fn new() -> Self {
         Self {
             tables: vec![FwdTableV4::new()],
+            rules: Vec::new(),
+            watchers: Watchers::new(),
+            active_wakeup: None,
+            multipath: Multipath::new(),
         }
     }
 
@@ -24,19 +50,85 @@ impl FwdV4 {
     }
 
     pub(crate) fn set_default_gw(&mut self, gateway: Ipv4Gateway, iface: InterfaceName) {
-        self.tables
-            .last_mut()
-            .expect("no tables, not allowed")
-            .set_default_gw(gateway, iface)
+        let table = RoutingTableId(self.len() - 1);
+        self.add_entry(FwdEntryV4::default_gw(gateway, iface), table);
     }
 
     pub(crate) fn lookup(&self, addr: Ipv4Addr) -> Option<(&Ipv4Gateway, &InterfaceName)> {
-        for table in self.tables.iter().rev() {
-            if let Some(ret) = table.lookup(addr) {
-                return Some(ret);
-            }
+        self.lookup_with(addr, &RouteKey::default())
+    }
+
+    /// Looks up a route, using the tables selected by the routing rules
+    /// matching the key. Tables that are not selected by any rule are
+    /// searched afterwards, from the newest to the oldest table.
+    pub(crate) fn lookup_with(
+        &self,
+        addr: Ipv4Addr,
+        key: &RouteKey,
+    ) -> Option<(&Ipv4Gateway, &InterfaceName)> {
+        let (_, group) = self.lookup_group(addr, key)?;
+        Some((&group[0].gateway, &group[0].iface))
+    }
+
+    /// Looks up a route for a packet like `lookup_with`, selecting one
+    /// of multiple equal-cost next hops by the multipath policy.
+    pub(crate) fn lookup_multipath(
+        &mut self,
+        pkt: &Ipv4Packet,
+        key: &RouteKey,
+    ) -> Option<(Ipv4Gateway, InterfaceName)> {
+        let Self {
+            tables,
+            rules,
+            multipath,
+            ..
+        } = self;
+        let (table, group) = lookup_group(tables, rules, pkt.dest, key)?;
+        let entry = multipath.select(table, &group, pkt);
+        Some((entry.gateway.clone(), entry.iface.clone()))
+    }
+
+    fn lookup_group(
+        &self,
+        addr: Ipv4Addr,
+        key: &RouteKey,
+    ) -> Option<(RoutingTableId, Vec<&FwdEntryV4>)> {
+        lookup_group(&self.tables, &self.rules, addr, key)
+    }
+
+    pub(crate) fn add_rule(&mut self, rule: RoutingRule) -> io::Result<()> {
+        if rule.table.0 >= self.tables.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no such routing table exists",
+            ));
         }
-        None
+        if self.rules.contains(&rule) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "routing rule already exists",
+            ));
+        }
+
+        // Rules with equal priority are evaluated in insertion order
+        let i = self.rules.partition_point(|r| r.priority <= rule.priority);
+        self.rules.insert(i, rule);
+        Ok(())
+    }
+
+    pub(crate) fn remove_rule(&mut self, rule: &RoutingRule) -> io::Result<()> {
+        let Some(i) = self.rules.iter().position(|r| r == rule) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "routing rule does not exist",
+            ));
+        };
+        self.rules.remove(i);
+        Ok(())
+    }
+
+    pub(crate) fn rules(&self) -> Vec<RoutingRule> {
+        self.rules.clone()
     }
 
     pub(crate) fn add_table(&mut self) -> io::Result<RoutingTableId> {
@@ -44,8 +136,112 @@ impl FwdV4 {
         Ok(RoutingTableId(self.len() - 1))
     }
 
+    fn table_mut(&mut self, table_id: RoutingTableId) -> io::Result<&mut FwdTableV4> {
+        self.tables.get_mut(table_id.0).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "no such routing table exists",
+        ))
+    }
+
+    /// Adds an entry, replacing all entries with equal prefix and metric.
     pub(crate) fn add_entry(&mut self, entry: FwdEntryV4, table_id: RoutingTableId) {
-        self.tables[table_id.0].add_entry(entry)
+        let replaced = self.tables[table_id.0].add_entry(entry.clone());
+        for replaced in replaced {
+            self.publish_removed(replaced, table_id);
+        }
+        self.publish_added(entry, table_id);
+    }
+
+    /// Adds an entry as an additional next hop to the entries with equal
+    /// prefix and metric, replacing only an entry with the same next hop.
+    pub(crate) fn append_entry(
+        &mut self,
+        entry: FwdEntryV4,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        let replaced = self.table_mut(table_id)?.append_entry(entry.clone());
+        if let Some(replaced) = replaced {
+            self.publish_removed(replaced, table_id);
+        }
+        self.publish_added(entry, table_id);
+        Ok(())
+    }
+
+    pub(crate) fn try_add_entry(
+        &mut self,
+        entry: FwdEntryV4,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        self.table_mut(table_id)?;
+        self.add_entry(entry, table_id);
+        Ok(())
+    }
+
+    /// Replaces all entries for the prefix of the given entry.
+    pub(crate) fn replace_entry(
+        &mut self,
+        entry: FwdEntryV4,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        let replaced = self
+            .table_mut(table_id)?
+            .remove_entries(entry.dest, entry.mask);
+        for replaced in replaced {
+            self.publish_removed(replaced, table_id);
+        }
+        self.add_entry(entry, table_id);
+        Ok(())
+    }
+
+    /// Removes all entries for a prefix, regardless of their metric.
+    pub(crate) fn remove_entries(
+        &mut self,
+        dest: Ipv4Addr,
+        mask: Ipv4Addr,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        let removed = self.table_mut(table_id)?.remove_entries(dest, mask);
+        if removed.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "no route to the subnet exists",
+            ));
+        }
+        for entry in removed {
+            self.publish_removed(entry, table_id);
+        }
+        Ok(())
+    }
+
+    fn publish_added(&mut self, entry: FwdEntryV4, table: RoutingTableId) {
+        let entry = FwdEntry::V4(entry);
+        publish(&mut self.watchers, RouteEvent::Added { entry, table });
+    }
+
+    fn publish_removed(&mut self, entry: FwdEntryV4, table: RoutingTableId) {
+        self.multipath.counters.remove(&next_hop_key(table, &entry));
+        let entry = FwdEntry::V4(entry);
+        publish(&mut self.watchers, RouteEvent::Removed { entry, table });
+    }
+
+    /// The earliest expiry of any entry.
+    pub(super) fn next_deadline(&self) -> Option<SimTime> {
+        self.tables
+            .iter()
+            .flat_map(|table| table.entries.iter())
+            .map(|entry| entry.expires)
+            .filter(|expires| *expires != SimTime::MAX)
+            .min()
+    }
+
+    /// Removes all entries that expired at the given time.
+    pub(super) fn remove_expired(&mut self, now: SimTime) {
+        for i in 0..self.tables.len() {
+            for entry in self.tables[i].remove_expired(now) {
+                tracing::debug!("routing entry {entry} expired");
+                self.publish_removed(entry, RoutingTableId(i));
+            }
+        }
     }
 
     pub(crate) fn entries(&self) -> Vec<FwdEntryV4> {
@@ -55,15 +251,56 @@ impl FwdV4 {
         }
         ret
     }
+
+    pub(crate) fn counters(&self) -> Vec<RouteCounter> {
+        let mut ret = Vec::with_capacity(32);
+        for (i, table) in self.tables.iter().enumerate().rev() {
+            let table_id = RoutingTableId(i);
+            for entry in &table.entries {
+                let key = next_hop_key(table_id, entry);
+                ret.push(RouteCounter {
+                    entry: entry.clone(),
+                    table: table_id,
+                    packets: self.multipath.counters.get(&key).copied().unwrap_or(0),
+                });
+            }
+        }
+        ret
+    }
+}
+
+fn lookup_group<'a>(
+    tables: &'a [FwdTableV4],
+    rules: &[RoutingRule],
+    addr: Ipv4Addr,
+    key: &RouteKey,
+) -> Option<(RoutingTableId, Vec<&'a FwdEntryV4>)> {
+    for rule in rules.iter().filter(|rule| rule.matches(key)) {
+        if let Some(group) = tables[rule.table.0].lookup_group(addr) {
+            return Some((rule.table, group));
+        }
+    }
+
+    for (i, table) in tables.iter().enumerate().rev() {
+        if rules.iter().any(|rule| rule.table.0 == i) {
+            continue;
+        }
+        if let Some(group) = table.lookup_group(addr) {
+            return Some((RoutingTableId(i), group));
+        }
+    }
+    None
 }
 
 #[derive(Debug)]
 pub(crate) struct FwdTableV4 {
-    // A list of all fwd entrys with the smallest prefixes first
+    // A list of all fwd entrys with the smallest prefixes first,
+    // and the lowest metric last for equal prefixes
     pub(super) entries: Vec<FwdEntryV4>,
 }
 
-/// A forwarding entry. Will not expire since FWD should be managed manually by routing deamons
+/// A forwarding entry. Entries are managed manually by routing deamons,
+/// but may be given a lifetime after which they expire.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FwdEntryV4 {
     /// The subnet this entry points to.
@@ -74,6 +311,15 @@ pub struct FwdEntryV4 {
     pub gateway: Ipv4Gateway,
     /// The interface to be used to forward to the gateway.
     pub iface: InterfaceName,
+    /// The metric of the entry. For equal prefixes, the entry
+    /// with the lowest metric is used.
+    pub metric: u32,
+    /// The weight of the entry, if multiple entries with equal prefix
+    /// and metric exist. Next hops are selected in proportion to
+    /// their weights.
+    pub weight: u32,
+    /// The time the entry expires, or `SimTime::MAX`.
+    pub expires: SimTime,
 }
 
 /// A type that describes differnt types of packet forwarding in inet.
@@ -94,39 +340,85 @@ impl FwdTableV4 {
         }
     }
 
+    #[cfg(test)]
     pub(crate) fn set_default_gw(&mut self, gateway: Ipv4Gateway, iface: InterfaceName) {
-        if let Some(f) = self.entries.first_mut() {
-            if f.is_default_gw() {
-                f.gateway = gateway;
-                f.iface = iface;
-            } else {
-                self.entries
-                    .insert(0, FwdEntryV4::default_gw(gateway, iface))
+        self.add_entry(FwdEntryV4::default_gw(gateway, iface));
+    }
+
+    /// Adds an entry, returning the replaced entries with equal prefix and metric.
+    pub(crate) fn add_entry(&mut self, entry: FwdEntryV4) -> Vec<FwdEntryV4> {
+        let mut replaced = Vec::new();
+        self.entries.retain(|e| {
+            if e.matches(&entry) {
+                replaced.push(e.clone());
             }
-        } else {
-            self.entries.push(FwdEntryV4::default_gw(gateway, iface));
+            !e.matches(&entry)
+        });
+        self.insert(entry);
+        replaced
+    }
+
+    /// Adds an entry, returning the replaced entry with equal prefix,
+    /// metric and next hop.
+    pub(crate) fn append_entry(&mut self, entry: FwdEntryV4) -> Option<FwdEntryV4> {
+        if let Some(in_place) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.matches(&entry) && e.gateway == entry.gateway && e.iface == entry.iface)
+        {
+            return Some(mem::replace(in_place, entry));
         }
+        self.insert(entry);
+        None
+    }
+
+    fn insert(&mut self, entry: FwdEntryV4) {
+        let i = self.entries.partition_point(|e| {
+            e.mask
+                .cmp(&entry.mask)
+                .then(entry.metric.cmp(&e.metric))
+                .is_lt()
+        });
+        self.entries.insert(i, entry);
     }
 
-    pub(crate) fn add_entry(&mut self, entry: FwdEntryV4) {
-        if let Some(in_place) = self.entries.iter_mut().find(|e| e.matches(&entry)) {
-            *in_place = entry;
-        } else {
-            match self.entries.binary_search_by(|e| e.mask.cmp(&entry.mask)) {
-                Ok(i) | Err(i) => self.entries.insert(i, entry),
+    pub(crate) fn remove_entries(&mut self, dest: Ipv4Addr, mask: Ipv4Addr) -> Vec<FwdEntryV4> {
+        let mut removed = Vec::new();
+        self.entries.retain(|e| {
+            let matches = e.dest == dest && e.mask == mask;
+            if matches {
+                removed.push(e.clone());
             }
-        }
+            !matches
+        });
+        removed
+    }
+
+    fn remove_expired(&mut self, now: SimTime) -> Vec<FwdEntryV4> {
+        let mut removed = Vec::new();
+        self.entries.retain(|e| {
+            if e.expires <= now {
+                removed.push(e.clone());
+            }
+            e.expires > now
+        });
+        removed
     }
 
+    #[cfg(test)]
     pub(crate) fn lookup(&self, addr: Ipv4Addr) -> Option<(&Ipv4Gateway, &InterfaceName)> {
+        let group = self.lookup_group(addr)?;
+        Some((&group[0].gateway, &group[0].iface))
+    }
+
+    /// Returns all next hops of the best matching prefix and metric.
+    pub(crate) fn lookup_group(&self, addr: Ipv4Addr) -> Option<Vec<&FwdEntryV4>> {
         let addr = u32::from(addr);
-        for entry in self.entries.iter().rev() {
+        let best = self.entries.iter().rev().find(|entry| {
             let mask = u32::from(entry.mask);
-            if addr & mask == u32::from(entry.dest) & mask {
-                return Some((&entry.gateway, &entry.iface));
-            }
-        }
-        None
+            addr & mask == u32::from(entry.dest) & mask
+        })?;
+        Some(self.entries.iter().filter(|e| e.matches(best)).collect())
     }
 }
 
@@ -137,6 +429,9 @@ impl FwdEntryV4 {
             mask: Ipv4Addr::UNSPECIFIED,
             gateway,
             iface,
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         }
     }
 
@@ -146,15 +441,14 @@ impl FwdEntryV4 {
             mask: Ipv4Addr::BROADCAST,
             gateway: Ipv4Gateway::Broadcast,
             iface,
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         }
     }
 
     fn matches(&self, other: &Self) -> bool {
-        self.dest == other.dest && self.mask == other.mask
-    }
-
-    fn is_default_gw(&self) -> bool {
-        self.dest == Ipv4Addr::UNSPECIFIED && self.mask == Ipv4Addr::UNSPECIFIED
+        self.dest == other.dest && self.mask == other.mask && self.metric == other.metric
     }
 }
 
@@ -162,8 +456,8 @@ impl Display for FwdEntryV4 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} ({}) via {:?} on {}",
-            self.dest, self.mask, self.gateway, self.iface
+            "{} ({}) via {:?} on {} metric {}",
+            self.dest, self.mask, self.gateway, self.iface, self.metric
         )
     }
 }
@@ -171,6 +465,7 @@ impl Display for FwdEntryV4 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::routing::MultipathPolicy;
 
     #[test]
     fn netmask_ordering() {
@@ -181,12 +476,18 @@ mod tests {
             mask: Ipv4Addr::new(255, 255, 255, 0),
             gateway: Ipv4Gateway::Local,
             iface: InterfaceName::new("sub24"),
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         });
         fwd.add_entry(FwdEntryV4 {
             dest: Ipv4Addr::new(1, 2, 0, 0),
             mask: Ipv4Addr::new(255, 255, 0, 0),
             gateway: Ipv4Gateway::Local,
             iface: InterfaceName::new("sub16"),
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         });
 
         assert_eq!(
@@ -198,12 +499,18 @@ mod tests {
                     mask: Ipv4Addr::new(255, 255, 0, 0),
                     gateway: Ipv4Gateway::Local,
                     iface: InterfaceName::new("sub16"),
+                    metric: 0,
+                    weight: 1,
+                    expires: SimTime::MAX,
                 },
                 FwdEntryV4 {
                     dest: Ipv4Addr::new(1, 2, 3, 0),
                     mask: Ipv4Addr::new(255, 255, 255, 0),
                     gateway: Ipv4Gateway::Local,
                     iface: InterfaceName::new("sub24"),
+                    metric: 0,
+                    weight: 1,
+                    expires: SimTime::MAX,
                 }
             ]
         )
@@ -218,12 +525,18 @@ mod tests {
             mask: Ipv4Addr::new(255, 255, 255, 0),
             gateway: Ipv4Gateway::Local,
             iface: InterfaceName::new("sub24"),
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         });
         fwd.add_entry(FwdEntryV4 {
             dest: Ipv4Addr::new(1, 2, 0, 0),
             mask: Ipv4Addr::new(255, 255, 0, 0),
             gateway: Ipv4Gateway::Local,
             iface: InterfaceName::new("sub16"),
+            metric: 0,
+            weight: 1,
+            expires: SimTime::MAX,
         });
 
         assert_eq!(
@@ -241,4 +554,311 @@ mod tests {
             Some(&InterfaceName::new("gw"))
         );
     }
+
+    fn uplinks() -> (FwdV4, RoutingTableId) {
+        let mut fwd = FwdV4::new();
+        fwd.set_default_gw(Ipv4Gateway::Local, InterfaceName::new("main"));
+        let table = fwd.add_table().unwrap();
+        fwd.add_entry(
+            FwdEntryV4::default_gw(Ipv4Gateway::Local, InterfaceName::new("uplink")),
+            table,
+        );
+        (fwd, table)
+    }
+
+    #[test]
+    fn rule_selected_tables() {
+        let (mut fwd, table) = uplinks();
+        let dest = Ipv4Addr::new(8, 8, 8, 8);
+
+        // Without rules, the newest table is used
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("uplink"))
+        );
+
+        fwd.add_rule(RoutingRule {
+            from: Some((Ipv4Addr::new(10, 0, 2, 0), Ipv4Addr::new(255, 255, 255, 0))),
+            ..RoutingRule::new(100, table)
+        })
+        .unwrap();
+
+        let key = RouteKey {
+            src: Ipv4Addr::new(10, 0, 2, 5),
+            ..RouteKey::default()
+        };
+        assert_eq!(
+            fwd.lookup_with(dest, &key).map(|(_, i)| i),
+            Some(&InterfaceName::new("uplink"))
+        );
+
+        // Tables selected by rules are not used for other packets
+        let key = RouteKey {
+            src: Ipv4Addr::new(10, 0, 1, 5),
+            ..RouteKey::default()
+        };
+        assert_eq!(
+            fwd.lookup_with(dest, &key).map(|(_, i)| i),
+            Some(&InterfaceName::new("main"))
+        );
+    }
+
+    #[test]
+    fn rule_selectors() {
+        let (mut fwd, table) = uplinks();
+        let dest = Ipv4Addr::new(8, 8, 8, 8);
+        let iif = InterfaceName::new("en1");
+        fwd.add_rule(RoutingRule {
+            iif: Some(iif.clone()),
+            dscp: Some(46),
+            mark: Some(7),
+            ..RoutingRule::new(100, table)
+        })
+        .unwrap();
+
+        let key = RouteKey {
+            iif: Some(iif.id()),
+            dscp: 46,
+            mark: 7,
+            ..RouteKey::default()
+        };
+        assert_eq!(
+            fwd.lookup_with(dest, &key).map(|(_, i)| i),
+            Some(&InterfaceName::new("uplink"))
+        );
+
+        for key in [
+            RouteKey { iif: None, ..key },
+            RouteKey { dscp: 0, ..key },
+            RouteKey { mark: 0, ..key },
+        ] {
+            assert_eq!(
+                fwd.lookup_with(dest, &key).map(|(_, i)| i),
+                Some(&InterfaceName::new("main"))
+            );
+        }
+    }
+
+    #[test]
+    fn rule_priority_and_fallthrough() {
+        let (mut fwd, uplink) = uplinks();
+        let empty = fwd.add_table().unwrap();
+        let dest = Ipv4Addr::new(8, 8, 8, 8);
+
+        fwd.add_rule(RoutingRule::new(200, uplink)).unwrap();
+        fwd.add_rule(RoutingRule::new(100, empty)).unwrap();
+        assert_eq!(
+            fwd.rules().iter().map(|r| r.priority).collect::<Vec<_>>(),
+            vec![100, 200]
+        );
+
+        // The empty table has no route, so the next rule is evaluated
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("uplink"))
+        );
+
+        assert!(fwd.add_rule(RoutingRule::new(100, empty)).is_err());
+        let missing = RoutingTableId(42);
+        assert!(fwd.add_rule(RoutingRule::new(1, missing)).is_err());
+
+        // Tables without rules are searched from the newest to the oldest
+        fwd.remove_rule(&RoutingRule::new(200, uplink)).unwrap();
+        fwd.remove_rule(&RoutingRule::new(100, empty)).unwrap();
+        fwd.set_default_gw(Ipv4Gateway::Local, InterfaceName::new("empty"));
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("empty"))
+        );
+        assert!(fwd.remove_rule(&RoutingRule::new(200, uplink)).is_err());
+    }
+
+    fn route(iface: &str, metric: u32) -> FwdEntryV4 {
+        FwdEntryV4 {
+            dest: Ipv4Addr::new(10, 0, 0, 0),
+            mask: Ipv4Addr::new(255, 0, 0, 0),
+            gateway: Ipv4Gateway::Local,
+            iface: InterfaceName::new(iface),
+            metric,
+            weight: 1,
+            expires: SimTime::MAX,
+        }
+    }
+
+    #[test]
+    fn metric_ordering() {
+        let mut fwd = FwdTableV4::new();
+        fwd.set_default_gw(Ipv4Gateway::Local, InterfaceName::new("gw"));
+        assert_eq!(fwd.add_entry(route("backup", 20)), vec![]);
+        assert_eq!(fwd.add_entry(route("primary", 10)), vec![]);
+        assert_eq!(fwd.add_entry(route("fallback", 30)), vec![]);
+
+        let dest = Ipv4Addr::new(10, 1, 2, 3);
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("primary"))
+        );
+
+        // Equal prefix and metric replaces the entry
+        assert_eq!(
+            fwd.add_entry(route("other", 10)),
+            vec![route("primary", 10)]
+        );
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("other"))
+        );
+
+        let removed = fwd.remove_entries(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(255, 0, 0, 0));
+        assert_eq!(removed.len(), 3);
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("gw"))
+        );
+    }
+
+    #[test]
+    fn route_events() {
+        let mut fwd = FwdV4::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        fwd.watchers.push(tx);
+
+        let table = RoutingTableId::DEFAULT;
+        fwd.add_entry(route("backup", 20), table);
+        fwd.add_entry(route("primary", 10), table);
+        fwd.replace_entry(route("replaced", 10), table).unwrap();
+
+        let events = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                RouteEvent::Added {
+                    entry: FwdEntry::V4(route("backup", 20)),
+                    table
+                },
+                RouteEvent::Added {
+                    entry: FwdEntry::V4(route("primary", 10)),
+                    table
+                },
+                RouteEvent::Removed {
+                    entry: FwdEntry::V4(route("backup", 20)),
+                    table
+                },
+                RouteEvent::Removed {
+                    entry: FwdEntry::V4(route("primary", 10)),
+                    table
+                },
+                RouteEvent::Added {
+                    entry: FwdEntry::V4(route("replaced", 10)),
+                    table
+                },
+            ]
+        );
+
+        let (dest, mask) = (Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(255, 0, 0, 0));
+        fwd.remove_entries(dest, mask, table).unwrap();
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(RouteEvent::Removed {
+                entry: FwdEntry::V4(route("replaced", 10)),
+                table
+            })
+        );
+        assert_eq!(
+            fwd.remove_entries(dest, mask, table).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            fwd.remove_entries(dest, mask, RoutingTableId(42))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+
+        // Closed watchers are removed
+        drop(rx);
+        fwd.add_entry(route("primary", 10), table);
+        assert!(fwd.watchers.is_empty());
+    }
+
+    fn secs(secs: u64) -> SimTime {
+        SimTime::from(std::time::Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn route_expiry() {
+        let mut fwd = FwdV4::new();
+        let table = RoutingTableId::DEFAULT;
+        fwd.add_entry(route("static", 20), table);
+        fwd.add_entry(
+            FwdEntryV4 {
+                expires: secs(30),
+                ..route("learned", 10)
+            },
+            table,
+        );
+        assert_eq!(fwd.next_deadline(), Some(secs(30)));
+
+        let dest = Ipv4Addr::new(10, 1, 2, 3);
+        fwd.remove_expired(secs(10));
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("learned"))
+        );
+
+        fwd.remove_expired(secs(30));
+        assert_eq!(
+            fwd.lookup(dest).map(|(_, i)| i),
+            Some(&InterfaceName::new("static"))
+        );
+        assert_eq!(fwd.next_deadline(), None);
+    }
+
+    #[test]
+    fn equal_cost_next_hops() {
+        let mut fwd = FwdV4::new();
+        fwd.multipath.policy = MultipathPolicy::RoundRobin;
+        let table = RoutingTableId::DEFAULT;
+        fwd.add_entry(route("en0", 10), table);
+        fwd.append_entry(route("en1", 10), table).unwrap();
+        fwd.append_entry(route("backup", 20), table).unwrap();
+
+        let pkt = Ipv4Packet {
+            dest: Ipv4Addr::new(10, 1, 2, 3),
+            ..Ipv4Packet::EMPTY
+        };
+        let mut selected = (0..4)
+            .map(|_| {
+                let (_, iface) = fwd.lookup_multipath(&pkt, &RouteKey::default()).unwrap();
+                iface.name
+            })
+            .collect::<Vec<_>>();
+        selected.sort();
+        assert_eq!(selected, ["en0", "en0", "en1", "en1"]);
+
+        let counters = fwd.counters();
+        assert_eq!(counters.len(), 3);
+        assert!(counters
+            .iter()
+            .all(|c| c.packets == if c.entry.metric == 10 { 2 } else { 0 }));
+
+        // Appending the same next hop replaces it, adding replaces all
+        fwd.append_entry(
+            FwdEntryV4 {
+                weight: 3,
+                ..route("en1", 10)
+            },
+            table,
+        )
+        .unwrap();
+        assert_eq!(fwd.entries().len(), 3);
+        fwd.add_entry(route("single", 10), table);
+        assert_eq!(
+            fwd.lookup_multipath(&pkt, &RouteKey::default())
+                .map(|(_, i)| i),
+            Some(InterfaceName::new("single"))
+        );
+        assert_eq!(fwd.entries().len(), 2);
+        assert_eq!(fwd.multipath.counters.len(), 1);
+    }
 }
@@ -0,0 +1,490 @@
+use super::{publish, FwdEntry, RouteEvent, RoutingTableId, Watchers};
+use crate::interface::InterfaceName;
+use des::time::SimTime;
+use inet_types::ip::ipv6_matches_subnet;
+use std::{
+    fmt::Display,
+    io::{self, Error, ErrorKind},
+    mem,
+    net::Ipv6Addr,
+};
+
+/// The metric of routes to directly connected subnets.
+pub(crate) const METRIC_KERNEL: u32 = 256;
+/// The metric of routes learned from router advertisements.
+pub(crate) const METRIC_RA: u32 = 1024;
+
+#[derive(Debug)]
+pub(crate) struct FwdV6 {
+    tables: Vec<FwdTableV6>,
+    pub(super) watchers: Watchers,
+    pub(super) active_wakeup: Option<SimTime>,
+}
+
+impl FwdV6 {
+    pub(crate) fn new() -> Self {
+        Self {
+            tables: vec![FwdTableV6::new()],
+            watchers: Watchers::new(),
+            active_wakeup: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub(crate) fn set_default_gw(&mut self, gateway: Ipv6Gateway, iface: InterfaceName) {
+        let table = RoutingTableId(self.len() - 1);
+        self.add_entry(FwdEntryV6::default_gw(gateway, iface), table);
+    }
+
+    /// Looks up a route, searching all tables from
+    /// the newest to the oldest table.
+    pub(crate) fn lookup(&self, addr: Ipv6Addr) -> Option<(&Ipv6Gateway, &InterfaceName)> {
+        self.tables
+            .iter()
+            .rev()
+            .find_map(|table| table.lookup(addr))
+    }
+
+    pub(crate) fn add_table(&mut self) -> RoutingTableId {
+        self.tables.push(FwdTableV6::new());
+        RoutingTableId(self.len() - 1)
+    }
+
+    fn table_mut(&mut self, table_id: RoutingTableId) -> io::Result<&mut FwdTableV6> {
+        self.tables.get_mut(table_id.0).ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "no such routing table exists",
+        ))
+    }
+
+    /// Adds an entry, replacing an entry with equal prefix and metric.
+    pub(crate) fn add_entry(&mut self, entry: FwdEntryV6, table_id: RoutingTableId) {
+        let replaced = self.tables[table_id.0].add_entry(entry.clone());
+        if let Some(replaced) = replaced {
+            self.publish_removed(replaced, table_id);
+        }
+        self.publish_added(entry, table_id);
+    }
+
+    pub(crate) fn try_add_entry(
+        &mut self,
+        entry: FwdEntryV6,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        self.table_mut(table_id)?;
+        self.add_entry(entry, table_id);
+        Ok(())
+    }
+
+    /// Replaces all entries for the prefix of the given entry.
+    pub(crate) fn replace_entry(
+        &mut self,
+        entry: FwdEntryV6,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        let replaced = self
+            .table_mut(table_id)?
+            .remove_entries(entry.dest, entry.mask);
+        for replaced in replaced {
+            self.publish_removed(replaced, table_id);
+        }
+        self.add_entry(entry, table_id);
+        Ok(())
+    }
+
+    /// Removes all entries for a prefix, regardless of their metric.
+    pub(crate) fn remove_entries(
+        &mut self,
+        dest: Ipv6Addr,
+        mask: Ipv6Addr,
+        table_id: RoutingTableId,
+    ) -> io::Result<()> {
+        let removed = self.table_mut(table_id)?.remove_entries(dest, mask);
+        if removed.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "no route to the subnet exists",
+            ));
+        }
+        for entry in removed {
+            self.publish_removed(entry, table_id);
+        }
+        Ok(())
+    }
+
+    /// Adds, refreshes or removes a default router learned from a router
+    /// advertisement. Routers expiring now are removed.
+    pub(crate) fn update_default_router(
+        &mut self,
+        router: Ipv6Addr,
+        iface: InterfaceName,
+        expires: SimTime,
+    ) {
+        self.update_learned_entry(
+            FwdEntryV6 {
+                metric: METRIC_RA,
+                expires,
+                ..FwdEntryV6::default_gw(Ipv6Gateway::Gateway(router), iface)
+            },
+            SimTime::now(),
+        );
+    }
+
+    /// Adds, refreshes or removes an on-link prefix learned from a router
+    /// advertisement. Prefixes expiring now are removed.
+    pub(crate) fn update_on_link_prefix(
+        &mut self,
+        dest: Ipv6Addr,
+        mask: Ipv6Addr,
+        iface: InterfaceName,
+        expires: SimTime,
+    ) {
+        self.update_learned_entry(
+            FwdEntryV6 {
+                dest,
+                mask,
+                gateway: Ipv6Gateway::Local,
+                iface,
+                metric: METRIC_KERNEL,
+                expires,
+            },
+            SimTime::now(),
+        );
+    }
+
+    /// Learned entries are identified by their gateway, so that
+    /// multiple routers may provide the same prefix.
+    fn update_learned_entry(&mut self, entry: FwdEntryV6, now: SimTime) {
+        let table = RoutingTableId::DEFAULT;
+        let removed = self.tables[table.0].remove_learned(&entry);
+        let refreshed = removed.is_some() && entry.expires > now;
+        if let Some(removed) = removed.filter(|_| !refreshed) {
+            self.publish_removed(removed, table);
+        }
+        if entry.expires <= now {
+            return;
+        }
+
+        self.tables[table.0].insert(entry.clone());
+        if !refreshed {
+            self.publish_added(entry, table);
+        }
+    }
+
+    fn publish_added(&mut self, entry: FwdEntryV6, table: RoutingTableId) {
+        let entry = FwdEntry::V6(entry);
+        publish(&mut self.watchers, RouteEvent::Added { entry, table });
+    }
+
+    fn publish_removed(&mut self, entry: FwdEntryV6, table: RoutingTableId) {
+        let entry = FwdEntry::V6(entry);
+        publish(&mut self.watchers, RouteEvent::Removed { entry, table });
+    }
+
+    /// The earliest expiry of any entry.
+    pub(super) fn next_deadline(&self) -> Option<SimTime> {
+        self.tables
+            .iter()
+            .flat_map(|table| table.entries.iter())
+            .map(|entry| entry.expires)
+            .filter(|expires| *expires != SimTime::MAX)
+            .min()
+    }
+
+    /// Removes all entries that expired at the given time.
+    pub(super) fn remove_expired(&mut self, now: SimTime) {
+        for i in 0..self.tables.len() {
+            for entry in self.tables[i].remove_expired(now) {
+                tracing::debug!("routing entry {entry} expired");
+                self.publish_removed(entry, RoutingTableId(i));
+            }
+        }
+    }
+
+    pub(crate) fn entries(&self) -> Vec<FwdEntryV6> {
+        let mut ret = Vec::with_capacity(32);
+        for table in self.tables.iter().rev() {
+            ret.extend(table.entries.iter().cloned());
+        }
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct FwdTableV6 {
+    // A list of all fwd entrys with the smallest prefixes first,
+    // and the lowest metric last for equal prefixes
+    entries: Vec<FwdEntryV6>,
+}
+
+/// An IPv6 forwarding entry. Entries are managed manually by routing
+/// deamons or learned from router advertisements, and may expire.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FwdEntryV6 {
+    /// The subnet this entry points to.
+    pub dest: Ipv6Addr,
+    /// The netmask of the targeted subnet.
+    pub mask: Ipv6Addr,
+    /// The next gateway on the route to the target.
+    pub gateway: Ipv6Gateway,
+    /// The interface to be used to forward to the gateway.
+    pub iface: InterfaceName,
+    /// The metric of the entry. For equal prefixes, the entry
+    /// with the lowest metric is used.
+    pub metric: u32,
+    /// The time the entry expires, or `SimTime::MAX`.
+    pub expires: SimTime,
+}
+
+/// A type that describes differnt types of IPv6 packet forwarding in inet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ipv6Gateway {
+    /// This option indicates that packets should be forwarded to a bound LAN.
+    Local,
+    /// This option is used for the representation of broadcasts.
+    Broadcast,
+    /// This option instructs inet to forward packets to the next gateway.
+    Gateway(Ipv6Addr),
+}
+
+impl FwdTableV6 {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds an entry, returning the replaced entry with equal prefix and metric.
+    pub(crate) fn add_entry(&mut self, entry: FwdEntryV6) -> Option<FwdEntryV6> {
+        if let Some(in_place) = self.entries.iter_mut().find(|e| e.matches(&entry)) {
+            return Some(mem::replace(in_place, entry));
+        }
+        self.insert(entry);
+        None
+    }
+
+    fn insert(&mut self, entry: FwdEntryV6) {
+        let i = self.entries.partition_point(|e| {
+            e.mask
+                .cmp(&entry.mask)
+                .then(entry.metric.cmp(&e.metric))
+                .is_lt()
+        });
+        self.entries.insert(i, entry);
+    }
+
+    fn remove_learned(&mut self, entry: &FwdEntryV6) -> Option<FwdEntryV6> {
+        let i = self.entries.iter().position(|e| {
+            e.dest == entry.dest
+                && e.mask == entry.mask
+                && e.gateway == entry.gateway
+                && e.iface == entry.iface
+                && e.metric == entry.metric
+        })?;
+        Some(self.entries.remove(i))
+    }
+
+    pub(crate) fn remove_entries(&mut self, dest: Ipv6Addr, mask: Ipv6Addr) -> Vec<FwdEntryV6> {
+        let mut removed = Vec::new();
+        self.entries.retain(|e| {
+            let matches = e.dest == dest && e.mask == mask;
+            if matches {
+                removed.push(e.clone());
+            }
+            !matches
+        });
+        removed
+    }
+
+    fn remove_expired(&mut self, now: SimTime) -> Vec<FwdEntryV6> {
+        let mut removed = Vec::new();
+        self.entries.retain(|e| {
+            if e.expires <= now {
+                removed.push(e.clone());
+            }
+            e.expires > now
+        });
+        removed
+    }
+
+    pub(crate) fn lookup(&self, addr: Ipv6Addr) -> Option<(&Ipv6Gateway, &InterfaceName)> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| ipv6_matches_subnet(addr, entry.dest, entry.mask))
+            .map(|entry| (&entry.gateway, &entry.iface))
+    }
+}
+
+impl FwdEntryV6 {
+    pub(crate) fn default_gw(gateway: Ipv6Gateway, iface: InterfaceName) -> Self {
+        Self {
+            dest: Ipv6Addr::UNSPECIFIED,
+            mask: Ipv6Addr::UNSPECIFIED,
+            gateway,
+            iface,
+            metric: 0,
+            expires: SimTime::MAX,
+        }
+    }
+
+    pub(crate) fn local(dest: Ipv6Addr, mask: Ipv6Addr, iface: InterfaceName) -> Self {
+        Self {
+            dest,
+            mask,
+            gateway: Ipv6Gateway::Local,
+            iface,
+            metric: METRIC_KERNEL,
+            expires: SimTime::MAX,
+        }
+    }
+
+    fn matches(&self, other: &Self) -> bool {
+        self.dest == other.dest && self.mask == other.mask && self.metric == other.metric
+    }
+}
+
+impl Display for FwdEntryV6 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} via {:?} on {} metric {}",
+            self.dest,
+            u128::from(self.mask).leading_ones(),
+            self.gateway,
+            self.iface,
+            self.metric
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(dest: Ipv6Addr, len: u32) -> (Ipv6Addr, Ipv6Addr) {
+        (dest, Ipv6Addr::from(!(u128::MAX >> len)))
+    }
+
+    fn route(iface: &str, len: u32, metric: u32) -> FwdEntryV6 {
+        let (dest, mask) = prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), len);
+        FwdEntryV6 {
+            dest,
+            mask,
+            gateway: Ipv6Gateway::Local,
+            iface: InterfaceName::new(iface),
+            metric,
+            expires: SimTime::MAX,
+        }
+    }
+
+    #[test]
+    fn longest_prefix_and_metric() {
+        let mut fwd = FwdTableV6::new();
+        fwd.add_entry(FwdEntryV6::default_gw(
+            Ipv6Gateway::Local,
+            InterfaceName::new("gw"),
+        ));
+        fwd.add_entry(route("sub32", 32, 0));
+        fwd.add_entry(route("sub48-backup", 48, 100));
+        fwd.add_entry(route("sub48", 48, 10));
+
+        let iface = |fwd: &FwdTableV6, addr| fwd.lookup(addr).map(|(_, i)| i.clone());
+        assert_eq!(
+            iface(&fwd, Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1)),
+            Some(InterfaceName::new("sub48"))
+        );
+        assert_eq!(
+            iface(&fwd, Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1)),
+            Some(InterfaceName::new("sub32"))
+        );
+        assert_eq!(
+            iface(&fwd, Ipv6Addr::new(0x2001, 0xdb9, 0, 0, 0, 0, 0, 1)),
+            Some(InterfaceName::new("gw"))
+        );
+
+        let (dest, mask) = prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 48);
+        assert_eq!(fwd.remove_entries(dest, mask).len(), 2);
+        assert_eq!(
+            iface(&fwd, Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1)),
+            Some(InterfaceName::new("sub32"))
+        );
+    }
+
+    #[test]
+    fn tables_and_events() {
+        let mut fwd = FwdV6::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        fwd.watchers.push(tx);
+
+        let table = fwd.add_table();
+        fwd.try_add_entry(route("main", 32, 0), RoutingTableId::DEFAULT)
+            .unwrap();
+        fwd.try_add_entry(route("table", 32, 0), table).unwrap();
+        assert!(fwd
+            .try_add_entry(route("missing", 32, 0), RoutingTableId(42))
+            .is_err());
+
+        // Newer tables are searched first
+        let addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        assert_eq!(
+            fwd.lookup(addr).map(|(_, i)| i),
+            Some(&InterfaceName::new("table"))
+        );
+
+        let (dest, mask) = prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32);
+        fwd.remove_entries(dest, mask, table).unwrap();
+        assert_eq!(
+            fwd.lookup(addr).map(|(_, i)| i),
+            Some(&InterfaceName::new("main"))
+        );
+
+        let events = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                RouteEvent::Added {
+                    entry: FwdEntry::V6(route("main", 32, 0)),
+                    table: RoutingTableId::DEFAULT
+                },
+                RouteEvent::Added {
+                    entry: FwdEntry::V6(route("table", 32, 0)),
+                    table
+                },
+                RouteEvent::Removed {
+                    entry: FwdEntry::V6(route("table", 32, 0)),
+                    table
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn learned_routers() {
+        let mut fwd = FwdV6::new();
+        let now = SimTime::from(std::time::Duration::from_secs(10));
+        let expires = SimTime::from(std::time::Duration::from_secs(100));
+        let router = |n| Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, n);
+        let entry = |n, expires| FwdEntryV6 {
+            metric: METRIC_RA,
+            expires,
+            ..FwdEntryV6::default_gw(Ipv6Gateway::Gateway(router(n)), InterfaceName::new("en0"))
+        };
+
+        // Multiple routers may provide a default route
+        fwd.update_learned_entry(entry(1, expires), now);
+        fwd.update_learned_entry(entry(2, expires), now);
+        assert_eq!(fwd.entries().len(), 2);
+        assert_eq!(fwd.next_deadline(), Some(expires));
+
+        // A lifetime of zero removes the router
+        fwd.update_learned_entry(entry(1, now), now);
+        assert_eq!(fwd.entries(), vec![entry(2, expires)]);
+
+        fwd.remove_expired(expires);
+        assert!(fwd.entries().is_empty());
+    }
+}
@@ -1,9 +1,7 @@
 //! Routing utility and networking layer processing.
-use crate::IOPlugin;
+use crate::{interface::InterfaceName, IOPlugin};
 use des::prelude::*;
-
-mod tablev6;
-pub(crate) use self::tablev6::*;
+use std::fmt::Display;
 
 mod api;
 pub use self::api::*;
@@ -11,6 +9,21 @@ pub use self::api::*;
 mod fwdv4;
 pub use self::fwdv4::*;
 
+mod fwdv6;
+pub use self::fwdv6::*;
+
+mod forward;
+pub use self::forward::*;
+
+mod rules;
+pub use self::rules::*;
+
+mod multipath;
+pub use self::multipath::*;
+
+mod watch;
+pub use self::watch::*;
+
 /// A collection of information readable
 /// from the topology alone.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -137,6 +150,74 @@ impl RoutingPort {
 unsafe impl Send for RoutingPort {}
 unsafe impl Sync for RoutingPort {}
 
+/// A forwarding entry of either address family.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FwdEntry {
+    /// An IPv4 forwarding entry.
+    V4(FwdEntryV4),
+    /// An IPv6 forwarding entry.
+    V6(FwdEntryV6),
+}
+
+impl FwdEntry {
+    /// The subnet this entry points to.
+    pub fn dest(&self) -> IpAddr {
+        match self {
+            Self::V4(entry) => entry.dest.into(),
+            Self::V6(entry) => entry.dest.into(),
+        }
+    }
+
+    /// The netmask of the targeted subnet.
+    pub fn mask(&self) -> IpAddr {
+        match self {
+            Self::V4(entry) => entry.mask.into(),
+            Self::V6(entry) => entry.mask.into(),
+        }
+    }
+
+    /// The next gateway on the route to the target, if
+    /// the target is not directly reachable.
+    pub fn gateway(&self) -> Option<IpAddr> {
+        match self {
+            Self::V4(FwdEntryV4 {
+                gateway: Ipv4Gateway::Gateway(ip),
+                ..
+            }) => Some((*ip).into()),
+            Self::V6(FwdEntryV6 {
+                gateway: Ipv6Gateway::Gateway(ip),
+                ..
+            }) => Some((*ip).into()),
+            _ => None,
+        }
+    }
+
+    /// The interface to be used to forward to the gateway.
+    pub fn iface(&self) -> &InterfaceName {
+        match self {
+            Self::V4(entry) => &entry.iface,
+            Self::V6(entry) => &entry.iface,
+        }
+    }
+
+    /// The metric of the entry.
+    pub fn metric(&self) -> u32 {
+        match self {
+            Self::V4(entry) => entry.metric,
+            Self::V6(entry) => entry.metric,
+        }
+    }
+}
+
+impl Display for FwdEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V4(entry) => entry.fmt(f),
+            Self::V6(entry) => entry.fmt(f),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum IpGateway {
     Local,
@@ -0,0 +1,359 @@
+use des::runtime::random;
+use fxhash::{FxBuildHasher, FxHashMap};
+use inet_types::{ip::Ipv4Packet, tcp::PROTO_TCP, udp::PROTO_UDP};
+use std::{io, net::Ipv4Addr};
+
+use super::{FwdEntryV4, Ipv4Gateway, RoutingTableId};
+use crate::{interface::IfId, IOContext};
+
+/// The policy used to select a next hop, if a route
+/// provides multiple next hops with equal cost.
+///
+/// All policies respect the weights of the next hops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MultipathPolicy {
+    /// Packets are distributed by a hash of their source address,
+    /// destination address and protocol.
+    Layer3,
+    /// Packets are distributed by a hash of the 5-tuple of their flow.
+    /// Packets without ports, like fragments, fall back to the
+    /// fields used by `Layer3`.
+    #[default]
+    Layer4,
+    /// Packets are distributed in turns, regardless of their flow.
+    /// This may reorder the packets of a flow.
+    RoundRobin,
+}
+
+/// The number of packets routed using a forwarding entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RouteCounter {
+    /// The forwarding entry.
+    pub entry: FwdEntryV4,
+    /// The table containing the entry.
+    pub table: RoutingTableId,
+    /// The number of packets routed via this entry.
+    pub packets: u64,
+}
+
+/// Identifies a next hop of a route, in a routing table.
+pub(super) type NextHopKey = (RoutingTableId, Ipv4Addr, Ipv4Addr, u32, Ipv4Gateway, IfId);
+/// Identifies a group of equal-cost next hops, in a routing table.
+type GroupKey = (RoutingTableId, Ipv4Addr, Ipv4Addr, u32);
+
+#[derive(Debug)]
+pub(crate) struct Multipath {
+    pub(super) policy: MultipathPolicy,
+    // The seed of the flow hash, chosen randomly per node to prevent
+    // all nodes along a path from making the same decisions.
+    pub(super) seed: Option<u64>,
+    turns: FxHashMap<GroupKey, u64>,
+    pub(super) counters: FxHashMap<NextHopKey, u64>,
+}
+
+impl Multipath {
+    pub(super) fn new() -> Self {
+        Self {
+            policy: MultipathPolicy::default(),
+            seed: None,
+            turns: FxHashMap::with_hasher(FxBuildHasher::default()),
+            counters: FxHashMap::with_hasher(FxBuildHasher::default()),
+        }
+    }
+
+    /// Selects a next hop from a non-empty group of equal-cost next hops,
+    /// counting the packet towards the selected next hop.
+    pub(super) fn select<'a>(
+        &mut self,
+        table: RoutingTableId,
+        group: &[&'a FwdEntryV4],
+        pkt: &Ipv4Packet,
+    ) -> &'a FwdEntryV4 {
+        let entry = if group.len() == 1 {
+            group[0]
+        } else {
+            let value = match self.policy {
+                MultipathPolicy::RoundRobin => {
+                    let key = (table, group[0].dest, group[0].mask, group[0].metric);
+                    let turn = self.turns.entry(key).or_default();
+                    *turn = turn.wrapping_add(1);
+                    *turn - 1
+                }
+                policy => self.flow_hash(policy, pkt),
+            };
+            weighted(group, value)
+        };
+
+        *self.counters.entry(next_hop_key(table, entry)).or_default() += 1;
+        entry
+    }
+
+    fn flow_hash(&mut self, policy: MultipathPolicy, pkt: &Ipv4Packet) -> u64 {
+        let seed = *self.seed.get_or_insert_with(random);
+        let fragment = pkt.flags.mf || pkt.fragment_offset != 0;
+        let ports = match pkt.proto {
+            PROTO_TCP | PROTO_UDP if policy == MultipathPolicy::Layer4 && !fragment => {
+                pkt.content.get(..4)
+            }
+            _ => None,
+        };
+        fxhash::hash64(&(seed, pkt.src, pkt.dest, pkt.proto, ports))
+    }
+}
+
+pub(super) fn next_hop_key(table: RoutingTableId, entry: &FwdEntryV4) -> NextHopKey {
+    (
+        table,
+        entry.dest,
+        entry.mask,
+        entry.metric,
+        entry.gateway.clone(),
+        entry.iface.id,
+    )
+}
+
+/// Maps a value onto a next hop, so that each next hop
+/// is selected in proportion to its weight.
+fn weighted<'a>(group: &[&'a FwdEntryV4], value: u64) -> &'a FwdEntryV4 {
+    let total = group.iter().map(|e| u64::from(e.weight)).sum::<u64>();
+    let mut value = value % total.max(1);
+    for entry in group {
+        let weight = u64::from(entry.weight);
+        if value < weight {
+            return entry;
+        }
+        value -= weight;
+    }
+    group[0]
+}
+
+/// Sets the policy used to select between multiple next hops
+///
+/// This function is roughly equivalent to the sysctl
+/// `net.ipv4.fib_multipath_hash_policy`. Next hops with equal cost
+/// are added using `append_routing_entry`. By default, packets are
+/// distributed by the hash of the 5-tuple of their flow, so that the
+/// packets of a flow take the same path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::routing::*;
+/// use std::net::Ipv4Addr;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let subnet = Ipv4Addr::new(10, 2, 0, 0);
+/// let mask = Ipv4Addr::new(255, 255, 0, 0);
+///
+/// // Two uplinks towards the core, the second with twice the capacity
+/// let (gw0, gw1) = (Ipv4Addr::new(10, 0, 1, 1), Ipv4Addr::new(10, 0, 2, 1));
+/// append_routing_entry(subnet, mask, gw0, "en0", RouteOptions::default())?;
+/// append_routing_entry(subnet, mask, gw1, "en1", RouteOptions {
+///     weight: 2,
+///     ..Default::default()
+/// })?;
+/// set_multipath_policy(MultipathPolicy::Layer3)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_multipath_policy(policy: MultipathPolicy) -> io::Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.ipv4_fwd.multipath.policy = policy;
+        Ok(())
+    })
+}
+
+/// Returns the policy used to select between multiple next hops.
+pub fn multipath_policy() -> io::Result<MultipathPolicy> {
+    IOContext::failable_api(|ctx| Ok(ctx.ipv4_fwd.multipath.policy))
+}
+
+/// Returns the number of packets routed via each IPv4 forwarding entry,
+/// for all entries in the routing tables.
+pub fn route_counters() -> io::Result<Vec<RouteCounter>> {
+    IOContext::failable_api(|ctx| Ok(ctx.ipv4_fwd.counters()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interface::InterfaceName,
+        routing::{FwdV4, RouteKey},
+    };
+    use des::time::SimTime;
+    use inet_types::ip::Ipv4Flags;
+
+    fn next_hop(iface: &str, weight: u32) -> FwdEntryV4 {
+        FwdEntryV4 {
+            dest: Ipv4Addr::new(10, 0, 0, 0),
+            mask: Ipv4Addr::new(255, 0, 0, 0),
+            gateway: Ipv4Gateway::Local,
+            iface: InterfaceName::new(iface),
+            metric: 0,
+            weight,
+            expires: SimTime::MAX,
+        }
+    }
+
+    fn udp(src_port: u16, fragment_offset: u16) -> Ipv4Packet {
+        let mut content = src_port.to_be_bytes().to_vec();
+        content.extend([0, 53, 0, 0]);
+        Ipv4Packet {
+            dscp: 0,
+            enc: 0,
+            identification: 0,
+            flags: Ipv4Flags {
+                df: false,
+                mf: false,
+            },
+            fragment_offset,
+            ttl: 64,
+            proto: PROTO_UDP,
+            src: Ipv4Addr::new(10, 1, 0, 1),
+            dest: Ipv4Addr::new(10, 2, 0, 1),
+            content,
+        }
+    }
+
+    #[test]
+    fn weighted_round_robin() {
+        let mut multipath = Multipath::new();
+        multipath.policy = MultipathPolicy::RoundRobin;
+
+        let (a, b) = (next_hop("a", 1), next_hop("b", 2));
+        let group = [&a, &b];
+        let selected = (0..6)
+            .map(|_| {
+                let entry = multipath.select(RoutingTableId::DEFAULT, &group, &udp(1, 0));
+                entry.iface.name.clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(selected, ["a", "b", "b", "a", "b", "b"]);
+
+        assert_eq!(
+            multipath.counters[&next_hop_key(RoutingTableId::DEFAULT, &a)],
+            2
+        );
+        assert_eq!(
+            multipath.counters[&next_hop_key(RoutingTableId::DEFAULT, &b)],
+            4
+        );
+    }
+
+    #[test]
+    fn flow_hashing() {
+        let mut multipath = Multipath::new();
+        multipath.seed = Some(42);
+
+        let hops = (0..4)
+            .map(|i| next_hop(&format!("en{i}"), 1))
+            .collect::<Vec<_>>();
+        let group = hops.iter().collect::<Vec<_>>();
+        let table = RoutingTableId::DEFAULT;
+
+        // Packets of a flow take the same path
+        let path = multipath.select(table, &group, &udp(1000, 0)).clone();
+        for _ in 0..8 {
+            assert_eq!(multipath.select(table, &group, &udp(1000, 0)), &path);
+        }
+
+        // Different flows are distributed across paths
+        let mut paths = (1000..1064)
+            .map(|port| multipath.select(table, &group, &udp(port, 0)).clone())
+            .collect::<Vec<_>>();
+        paths.dedup();
+        assert!(paths.len() > 1);
+
+        // Layer 3 hashing ignores the ports, as do fragments
+        let hash = multipath.flow_hash(MultipathPolicy::Layer3, &udp(1000, 0));
+        assert_eq!(
+            multipath.flow_hash(MultipathPolicy::Layer3, &udp(2000, 0)),
+            hash
+        );
+        assert_eq!(
+            multipath.flow_hash(MultipathPolicy::Layer4, &udp(2000, 185)),
+            hash
+        );
+        assert_ne!(
+            multipath.flow_hash(MultipathPolicy::Layer4, &udp(2000, 0)),
+            hash
+        );
+    }
+
+    #[test]
+    fn append_next_hops() {
+        let mut fwd = FwdV4::new();
+        let table = RoutingTableId::DEFAULT;
+        let (a, b) = (next_hop("a", 1), next_hop("b", 1));
+        fwd.append_entry(a.clone(), table).unwrap();
+        fwd.append_entry(b.clone(), table).unwrap();
+        assert_eq!(fwd.entries(), [b.clone(), a.clone()]);
+
+        // Appending an existing next hop replaces it in place
+        let heavy = FwdEntryV4 {
+            weight: 3,
+            ..a.clone()
+        };
+        fwd.append_entry(heavy.clone(), table).unwrap();
+        assert_eq!(fwd.entries(), [b.clone(), heavy]);
+
+        // Adding an entry replaces all next hops
+        fwd.add_entry(a.clone(), table);
+        assert_eq!(fwd.entries(), [a]);
+
+        assert!(fwd.append_entry(b, RoutingTableId(1)).is_err());
+    }
+
+    #[test]
+    fn route_counters() {
+        let mut fwd = FwdV4::new();
+        fwd.multipath.policy = MultipathPolicy::RoundRobin;
+        let table = RoutingTableId::DEFAULT;
+        let (a, b) = (next_hop("a", 1), next_hop("b", 1));
+        fwd.append_entry(a.clone(), table).unwrap();
+        fwd.append_entry(b.clone(), table).unwrap();
+
+        let key = RouteKey::default();
+        let selected = (0..4)
+            .map(|_| fwd.lookup_multipath(&udp(1, 0), &key).unwrap().1.name)
+            .collect::<Vec<_>>();
+        assert_eq!(selected, ["b", "a", "b", "a"]);
+
+        let packets = |fwd: &FwdV4| {
+            fwd.counters()
+                .iter()
+                .map(|c| (c.entry.iface.name.clone(), c.packets))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(packets(&fwd), [("b".into(), 2), ("a".into(), 2)]);
+
+        // Counters are cleared with their entries
+        fwd.remove_entries(a.dest, a.mask, table).unwrap();
+        assert!(fwd.counters().is_empty());
+        fwd.append_entry(a, table).unwrap();
+        assert_eq!(packets(&fwd), [("a".into(), 0)]);
+    }
+
+    #[test]
+    fn policy_selects_next_hop() {
+        let mut fwd = FwdV4::new();
+        fwd.multipath.seed = Some(42);
+        let table = RoutingTableId::DEFAULT;
+        fwd.append_entry(next_hop("a", 1), table).unwrap();
+        fwd.append_entry(next_hop("b", 1), table).unwrap();
+
+        // By default, packets of a flow take the same path
+        let key = RouteKey::default();
+        let path = fwd.lookup_multipath(&udp(1000, 0), &key).unwrap();
+        for _ in 0..4 {
+            assert_eq!(fwd.lookup_multipath(&udp(1000, 0), &key).unwrap(), path);
+        }
+
+        // Round robin alternates, regardless of the flow
+        fwd.multipath.policy = MultipathPolicy::RoundRobin;
+        let first = fwd.lookup_multipath(&udp(1000, 0), &key).unwrap();
+        let second = fwd.lookup_multipath(&udp(1000, 0), &key).unwrap();
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt::Display, net::Ipv4Addr};
+
+use super::RoutingTableId;
+use crate::interface::{IfId, InterfaceName};
+
+/// A policy routing rule, that selects the routing table
+/// used for matching packets.
+///
+/// Rules are evaluated in ascending order of their priority. A rule
+/// matches a packet, if all of its selectors match. Should the selected
+/// table contain no route to the destination, the next matching rule is
+/// evaluated. Selectors that are `None` match all packets.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutingRule {
+    /// The priority of the rule, lower values are evaluated first.
+    pub priority: u32,
+    /// The source subnet and its netmask.
+    pub from: Option<(Ipv4Addr, Ipv4Addr)>,
+    /// The interface, a forwarded packet was received on.
+    /// Locally generated packets never match this selector.
+    pub iif: Option<InterfaceName>,
+    /// The DSCP value of the packet.
+    pub dscp: Option<u8>,
+    /// The mark of the socket, that send the packet.
+    pub mark: Option<u32>,
+    /// The routing table to use for matching packets.
+    pub table: RoutingTableId,
+}
+
+impl RoutingRule {
+    /// Creates a new rule, that selects the given table for all packets.
+    pub fn new(priority: u32, table: RoutingTableId) -> Self {
+        Self {
+            priority,
+            from: None,
+            iif: None,
+            dscp: None,
+            mark: None,
+            table,
+        }
+    }
+
+    pub(super) fn matches(&self, key: &RouteKey) -> bool {
+        let from = self.from.is_none_or(|(subnet, mask)| {
+            u32::from(key.src) & u32::from(mask) == u32::from(subnet) & u32::from(mask)
+        });
+        let iif = self.iif.as_ref().is_none_or(|iif| key.iif == Some(iif.id));
+
+        from && iif
+            && self.dscp.is_none_or(|dscp| dscp == key.dscp)
+            && self.mark.is_none_or(|mark| mark == key.mark)
+    }
+}
+
+impl Display for RoutingRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.priority)?;
+        match self.from {
+            Some((subnet, mask)) => write!(f, "from {subnet} ({mask}) ")?,
+            None => write!(f, "from all ")?,
+        }
+        if let Some(iif) = &self.iif {
+            write!(f, "iif {iif} ")?;
+        }
+        if let Some(dscp) = self.dscp {
+            write!(f, "dscp {dscp} ")?;
+        }
+        if let Some(mark) = self.mark {
+            write!(f, "fwmark 0x{mark:x} ")?;
+        }
+        write!(f, "lookup {}", self.table)
+    }
+}
+
+/// The properties of a packet, that routing rules may select on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RouteKey {
+    pub src: Ipv4Addr,
+    pub dscp: u8,
+    /// The ingress interface of forwarded packets.
+    pub iif: Option<IfId>,
+    pub mark: u32,
+}
+
+impl Default for RouteKey {
+    fn default() -> Self {
+        Self {
+            src: Ipv4Addr::UNSPECIFIED,
+            dscp: 0,
+            iif: None,
+            mark: 0,
+        }
+    }
+}
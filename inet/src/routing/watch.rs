@@ -0,0 +1,90 @@
+use std::io::{Error, ErrorKind, Result};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use super::{FwdEntry, RoutingTableId};
+use crate::IOContext;
+
+/// A change to the routing tables.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RouteEvent {
+    /// A route was added to a routing table.
+    Added {
+        /// The added route.
+        entry: FwdEntry,
+        /// The table containing the route.
+        table: RoutingTableId,
+    },
+    /// A route was removed from a routing table, either
+    /// explicitly, by replacement or since it expired.
+    Removed {
+        /// The removed route.
+        entry: FwdEntry,
+        /// The table that contained the route.
+        table: RoutingTableId,
+    },
+}
+
+/// A receiver of changes to the routing tables, created by `watch_routes`.
+#[derive(Debug)]
+pub struct RouteWatcher {
+    rx: UnboundedReceiver<RouteEvent>,
+}
+
+impl RouteWatcher {
+    /// Receives the next change to the routing tables (blockingly).
+    pub async fn recv(&mut self) -> Result<RouteEvent> {
+        self.rx
+            .recv()
+            .await
+            .ok_or(Error::new(ErrorKind::BrokenPipe, "routing tables dropped"))
+    }
+
+    /// Non-blockingly receives the next change to the routing
+    /// tables, or WouldBlock if there are none.
+    pub fn try_recv(&mut self) -> Result<RouteEvent> {
+        self.rx
+            .try_recv()
+            .map_err(|_| Error::new(ErrorKind::WouldBlock, "would block"))
+    }
+}
+
+/// Watches the routing tables for changes
+///
+/// This function is roughly equivalent to the shell command
+/// `ip monitor route`. The returned watcher yields an event for every
+/// route that is added to or removed from any routing table after this
+/// call, including routes added by interfaces and expired routes. Use
+/// `route` to retrieve the initial state of the routing tables.
+///
+/// # Examples
+///
+/// ```no_run
+/// use inet::routing::{watch_routes, RouteEvent};
+///
+/// # async fn f() -> std::io::Result<()> {
+/// let mut watcher = watch_routes()?;
+/// while let Ok(event) = watcher.recv().await {
+///     match event {
+///         RouteEvent::Added { entry, .. } => println!("added {entry}"),
+///         RouteEvent::Removed { entry, .. } => println!("removed {entry}"),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn watch_routes() -> Result<RouteWatcher> {
+    IOContext::failable_api(|ctx| {
+        let (tx, rx) = mpsc::unbounded_channel();
+        ctx.ipv4_fwd.watchers.push(tx.clone());
+        ctx.ipv6_fwd.watchers.push(tx);
+        Ok(RouteWatcher { rx })
+    })
+}
+
+/// The senders of all active `RouteWatcher`.
+pub(super) type Watchers = Vec<UnboundedSender<RouteEvent>>;
+
+/// Publishes an event to all active watchers, removing closed watchers.
+pub(super) fn publish(watchers: &mut Watchers, event: RouteEvent) {
+    watchers.retain(|tx| tx.send(event.clone()).is_ok());
+}
@@ -57,6 +57,22 @@ pub fn close(fd: Fd) -> Result<()> {
     IOContext::failable_api(|ctx| ctx.close_socket(fd))
 }
 
+/// Sets the mark of a socket
+///
+/// This function is roughly equivalent to setting the socket
+/// option `SO_MARK`. All packets send by the socket carry this mark,
+/// so that routing rules may select a routing table for them.
+/// Sockets are unmarked by default, equivalent to the mark 0.
+pub fn set_socket_mark(fd: Fd, mark: u32) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        let Some(socket) = ctx.sockets.get_mut(&fd) else {
+            return Err(Error::new(ErrorKind::NotFound, "no socket for fd"));
+        };
+        socket.mark = mark;
+        Ok(())
+    })
+}
+
 #[doc(hidden)]
 pub fn bsd_socket_info(fd: Fd) -> Result<Socket> {
     IOContext::failable_api(|ctx| {
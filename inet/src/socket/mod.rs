@@ -35,6 +35,7 @@ pub use self::raw::*;
 pub(super) struct Sockets {
     sockets: FxHashMap<Fd, Socket>,
     pub(super) handlers: FxHashMap<(u8, SocketDomain), (Fd, Sender<IpPacket>)>,
+    pub(super) unknown_proto: UnknownProto,
 }
 
 impl Sockets {
@@ -42,6 +43,7 @@ impl Sockets {
         Sockets {
             sockets: FxHashMap::with_hasher(FxBuildHasher::default()),
             handlers: FxHashMap::with_hasher(FxBuildHasher::default()),
+            unknown_proto: UnknownProto::default(),
         }
     }
 }
@@ -82,6 +84,8 @@ pub struct Socket {
     pub interface: SocketIfaceBinding,
     /// The ttl of IP like packets
     pub ttl: u8,
+    /// The mark of the socket, used by routing rules.
+    pub mark: u32,
 
     /// The total number of bytes received by this socket.
     pub recv_q: usize,
@@ -174,6 +178,7 @@ impl IOContext {
             fd,
             interface: SocketIfaceBinding::NotBound,
             ttl: 128,
+            mark: 0,
 
             recv_q: 0,
             send_q: 0,
@@ -4,10 +4,10 @@ use std::{
     net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
 };
 
-use inet_types::ip::IpPacket;
+use inet_types::ip::{IpPacket, IpPacketRef};
 use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use crate::IOContext;
+use crate::{interface::IfId, IOContext};
 
 use super::{Fd, SocketDomain};
 
@@ -61,6 +61,39 @@ impl RawIpSocket {
     }
 }
 
+/// Handling of IP packets, for whose protocol no
+/// `RawIpSocket` is bound.
+#[derive(Debug, Default)]
+pub(crate) struct UnknownProto {
+    passthrough: bool,
+    dropped: u64,
+}
+
+/// Configures, whether IP packets of unknown protocols are passed
+/// to the module
+///
+/// By default, IP packets with a protocol number that is neither
+/// handled by inet itself, nor bound by a `RawIpSocket`, are dropped.
+/// The sender is notified by an ICMP Destination Unreachable
+/// (protocol unreachable) or ICMPv6 Parameter Problem message.
+/// If enabled, such packets are instead passed to the module, like
+/// any other message.
+pub fn set_unknown_proto_passthrough(enabled: bool) -> Result<()> {
+    IOContext::failable_api(|ctx| {
+        ctx.sockets.unknown_proto.passthrough = enabled;
+        Ok(())
+    })
+}
+
+/// The number of IP packets, that were dropped since their protocol
+/// was unknown
+///
+/// This counter is roughly equivalent to `InUnknownProtos` in
+/// `/proc/net/snmp`. Packets passed to the module are not counted.
+pub fn unknown_proto_dropped() -> Result<u64> {
+    IOContext::failable_api(|ctx| Ok(ctx.sockets.unknown_proto.dropped))
+}
+
 impl Drop for RawIpSocket {
     fn drop(&mut self) {
         IOContext::try_with_current(|ctx| ctx.drop_raw_ip_socket(self.fd));
@@ -124,7 +157,25 @@ impl IOContext {
             return Err(Error::new(ErrorKind::InvalidInput, "no socket under fd"));
         };
 
-        self.send_ip_packet(socket.interface.clone(), pkt, true)
+        self.send_ip_packet_with(socket.interface.clone(), pkt, true, None, socket.mark)
+    }
+
+    /// Handles an IP packet of a protocol, that is neither handled by
+    /// inet nor bound by a raw socket. Returns whether the packet was consumed.
+    pub(crate) fn recv_unknown_proto(&mut self, pkt: IpPacketRef, ifid: IfId) -> bool {
+        if self.sockets.unknown_proto.passthrough {
+            return false;
+        }
+
+        let proto = match pkt {
+            IpPacketRef::V4(pkt) => pkt.proto,
+            IpPacketRef::V6(pkt) => pkt.next_header,
+        };
+        tracing::warn!("dropping packet with unknown protocol {proto}");
+
+        self.sockets.unknown_proto.dropped += 1;
+        self.icmp_protocol_unreachable(ifid, pkt);
+        true
     }
 
     fn drop_raw_ip_socket(&mut self, fd: Fd) {
@@ -14,6 +14,7 @@ use crate::{
     IOContext,
 };
 
+use des::runtime::random;
 use inet_types::ip::IpPacketRef;
 use tokio::sync::{
     mpsc::{self, Receiver, Sender},
@@ -47,6 +48,27 @@ pub(crate) struct ListenerHandle {
     pub(crate) backlog: Arc<AtomicU32>,
     pub(crate) config: TcpSocketConfig,
     pub(crate) interests: Vec<TcpInterestGuard>,
+    pub(crate) secret: u64,
+    pub(crate) fast_open_secret: u64,
+    pub(crate) stats: TcpListenerStats,
+}
+
+/// Counters of incoming connection requests of a [`TcpListener`].
+///
+/// Obtained using [`TcpListener::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct TcpListenerStats {
+    /// The number of SYNs received by the listener.
+    pub syn_received: u64,
+    /// The number of SYNs dropped, since the backlog was full.
+    pub syn_dropped: u64,
+    /// The number of SYNACKs answered with a SYN cookie instead of a backlog entry.
+    pub syn_cookies_sent: u64,
+    /// The number of connections established using a valid SYN cookie.
+    pub syn_cookies_validated: u64,
+    /// The number of ACKs carrying an invalid or expired SYN cookie.
+    pub syn_cookies_rejected: u64,
 }
 
 type IncomingConnection = Result<(TcpStream, oneshot::Receiver<Result<()>>)>;
@@ -137,6 +159,20 @@ impl TcpListener {
         })
     }
 
+    /// Returns the counters of incoming connection requests of this listener.
+    ///
+    /// If SYN cookies are enabled in the [`TcpConfig`](crate::tcp::TcpConfig), SYNs
+    /// exceeding the backlog are answered statelessly instead of being dropped.
+    pub fn stats(&self) -> Result<TcpListenerStats> {
+        IOContext::with_current(|ctx| {
+            if let Some(handle) = ctx.tcp.binds.get(&self.fd) {
+                Ok(handle.stats)
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
     /// Sets the value for the IP_TTL option on this socket.
     ///
     /// This value sets the time-to-live field that is used in every packet sent from this socket.
@@ -191,6 +227,9 @@ impl IOContext {
             backlog: backlog.clone(),
             tx,
             interests: Vec::new(),
+            secret: random(),
+            fast_open_secret: random(),
+            stats: TcpListenerStats::default(),
 
             config: config.unwrap_or(self.tcp.config.listener(addr)),
         };
@@ -2,7 +2,7 @@ use super::{TcpListener, TcpStream, TcpStreamInner};
 use crate::dns::lookup_host;
 use crate::socket::{Fd, SocketDomain, SocketType};
 use crate::tcp::interest::TcpInterest;
-use crate::tcp::TcpSocketConfig;
+use crate::tcp::{CongestionAlgorithm, KeepaliveConfig, TcpSocketConfig};
 use crate::IOContext;
 use std::cell::RefCell;
 use std::io::{Error, ErrorKind, Result};
@@ -60,6 +60,27 @@ impl TcpSocket {
         Ok(self.config.borrow().inital_seq_no)
     }
 
+    /// Sets the congestion control algorithm of this socket.
+    ///
+    /// If `None` is provided, congestion control is disabled and
+    /// the sender is only limited by the receivers window.
+    pub fn set_congestion_control(&self, algorithm: Option<CongestionAlgorithm>) -> Result<()> {
+        let mut config = self.config.borrow_mut();
+        config.cong_ctrl = algorithm.is_some();
+        if let Some(algorithm) = algorithm {
+            config.cong_algorithm = algorithm;
+        }
+        Ok(())
+    }
+
+    /// Gets the congestion control algorithm of this socket.
+    ///
+    /// For more information about this option, see [set_congestion_control](TcpSocket::set_congestion_control).
+    pub fn congestion_control(&self) -> Result<Option<CongestionAlgorithm>> {
+        let config = self.config.borrow();
+        Ok(config.cong_ctrl.then(|| config.cong_algorithm.clone()))
+    }
+
     /// Allows the socket to bind to an in-use address.
     ///
     /// Behavior is platform specific.
@@ -129,6 +150,9 @@ impl TcpSocket {
     ///
     /// If SO_LINGER is not specified, and the socket is closed, the system handles the call
     /// in a way that allows the process to continue as quickly as possible.
+    ///
+    /// If the linger duration is zero, closing the socket aborts the connection
+    /// by sending a RST, discarding all buffered data.
     pub fn set_linger(&self, dur: Option<Duration>) -> Result<()> {
         self.config.borrow_mut().linger = dur;
         Ok(())
@@ -141,6 +165,22 @@ impl TcpSocket {
         Ok(self.config.borrow().linger)
     }
 
+    /// Enables or disables keepalive probes on this socket, by setting the SO_KEEPALIVE option.
+    ///
+    /// If enabled, idle connections are probed periodically. Should the peer not
+    /// respond to any of the probes, the connection is aborted.
+    pub fn set_keepalive(&self, keepalive: Option<KeepaliveConfig>) -> Result<()> {
+        self.config.borrow_mut().keepalive = keepalive;
+        Ok(())
+    }
+
+    /// Gets the keepalive configuration of this socket.
+    ///
+    /// For more information about this option, see [set_keepalive](TcpSocket::set_keepalive).
+    pub fn keepalive(&self) -> Result<Option<KeepaliveConfig>> {
+        Ok(self.config.borrow().keepalive)
+    }
+
     // Gets the local address of this socket.
     ///
     /// Will fail on windows if called before bind
@@ -182,7 +222,12 @@ impl TcpSocket {
         let fd = self.fd;
         self.fd = 0;
         let this = IOContext::with_current(|ctx| {
-            ctx.tcp_create_and_connect_socket(peer, Some(self.config.borrow().clone()), Some(fd))
+            ctx.tcp_create_and_connect_socket(
+                peer,
+                Some(self.config.borrow().clone()),
+                Some(fd),
+                None,
+            )
 
             // // // ctx.tcp_bind_stream(peer, Some(self.config.into_inner()))
             // // ctx.bsd_bind_peer(self.fd, peer)?;
@@ -7,16 +7,17 @@ use crate::{
     tcp::{
         interest::{TcpInterest, TcpInterestGuard},
         types::{TcpEvent, TcpState, TcpSyscall},
-        TcpSocketConfig, TransmissionControlBlock,
+        KeepaliveConfig, TcpInfo, TcpSocketConfig, TcpTimer, TransmissionControlBlock,
     },
     IOContext,
 };
 use std::{
     io::{Error, ErrorKind, Result},
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6},
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
@@ -50,12 +51,38 @@ impl TcpStream {
     /// until a connection is successful. If none of the addresses result in a successful connection,
     /// the error returned from the last connection attempt (the last address) is returned.
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream> {
+        Self::connect_inner(addr, None).await
+    }
+
+    /// Opens a TCP connection to a remote host, using TCP Fast Open (RFC 7413).
+    ///
+    /// If a fast open cookie of the remote host is known, `data` is send with the SYN,
+    /// saving a round trip. Otherwise a cookie is requested for future connections,
+    /// and `data` is send once the connection is established. In both cases, `data`
+    /// is written to the stream before this function returns.
+    ///
+    /// The remote host must enable fast open for its listener,
+    /// see [`TcpConfig::fast_open`](crate::tcp::TcpConfig).
+    pub async fn connect_with_data<A: ToSocketAddrs>(addr: A, data: &[u8]) -> Result<TcpStream> {
+        let tx_buffer_size = IOContext::with_current(|ctx| ctx.tcp.config.tx_buffer_size);
+        if data.len() > tx_buffer_size as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "fast open data exceeds send buffer",
+            ));
+        }
+
+        Self::connect_inner(addr, Some(data)).await
+    }
+
+    async fn connect_inner<A: ToSocketAddrs>(addr: A, data: Option<&[u8]>) -> Result<TcpStream> {
         let addrs = lookup_host(addr).await?;
         let mut last_err = None;
 
         for peer in addrs {
-            let this =
-                IOContext::with_current(|ctx| ctx.tcp_create_and_connect_socket(peer, None, None))?;
+            let this = IOContext::with_current(|ctx| {
+                ctx.tcp_create_and_connect_socket(peer, None, None, data.map(<[u8]>::to_vec))
+            })?;
 
             loop {
                 // Initiate connect by sending a message (better repeat)
@@ -96,6 +123,81 @@ impl TcpStream {
         IOContext::with_current(|ctx| ctx.get_socket_peer(self.inner.fd))
     }
 
+    /// Returns a snapshot of the internal connection state, similar to TCP_INFO.
+    ///
+    /// This includes RTT estimates, the congestion window and transfer statistics.
+    pub fn info(&self) -> Result<TcpInfo> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get(&self.inner.fd) {
+                Ok(ctrl.info())
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
+    /// Sets the value of the TCP_NODELAY option on this socket.
+    ///
+    /// If set, this option disables the Nagle algorithm. This means that segments are always
+    /// sent as soon as possible, even if there is only a small amount of data. When not set,
+    /// data is buffered until there is a sufficient amount to send out,
+    /// thereby avoiding the frequent sending of small packets.
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<()> {
+        IOContext::with_current(|ctx| ctx.tcp_set_nodelay(self.inner.fd, nodelay))
+    }
+
+    /// Gets the value of the TCP_NODELAY option on this socket.
+    ///
+    /// For more information about this option, see [set_nodelay](TcpStream::set_nodelay).
+    pub fn nodelay(&self) -> Result<bool> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get(&self.inner.fd) {
+                Ok(ctrl.nodelay)
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
+    /// Enables or disables keepalive probes on this stream, by setting the SO_KEEPALIVE option.
+    ///
+    /// If enabled, idle connections are probed periodically. Should the peer not
+    /// respond to any of the probes, the connection is aborted and further reads and
+    /// writes fail with [`ErrorKind::TimedOut`].
+    pub fn set_keepalive(&self, keepalive: Option<KeepaliveConfig>) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get_mut(&self.inner.fd) {
+                ctrl.keepalive = keepalive;
+                if ctrl.state == TcpState::Established
+                    && ctrl.tx_last_ack_no == ctrl.tx_next_send_seq_no
+                    && ctrl.timer_kind != TcpTimer::Persist
+                {
+                    if keepalive.is_some() {
+                        ctrl.set_keepalive_timer();
+                    } else if ctrl.timer_kind == TcpTimer::Keepalive {
+                        ctrl.cancel_timer();
+                    }
+                }
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
+    /// Gets the keepalive configuration of this stream.
+    ///
+    /// For more information about this option, see [set_keepalive](TcpStream::set_keepalive).
+    pub fn keepalive(&self) -> Result<Option<KeepaliveConfig>> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get(&self.inner.fd) {
+                Ok(ctrl.keepalive)
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
     /// Waits for any of the requested ready states.
     ///
     /// This function is usually paired with try_read() or try_write().
@@ -160,6 +262,55 @@ impl TcpStream {
         IOContext::with_current(|ctx| ctx.tcp_try_write(self.inner.fd, buf))
     }
 
+    /// Shuts down the read, write, or both halves of this connection.
+    ///
+    /// Shutting down the write half sends a FIN to the peer, once all buffered data
+    /// was send. The read half remains usable, so that the peer may still respond.
+    /// Shutting down the read half causes all future reads to return `Ok(0)`.
+    ///
+    /// Note that [`AsyncWriteExt::shutdown`](tokio::io::AsyncWriteExt::shutdown)
+    /// is equivalent to `shutdown(Shutdown::Write)`.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        IOContext::with_current(|ctx| ctx.tcp_shutdown(self.inner.fd, how))
+    }
+
+    /// Aborts the connection, by sending a RST to the peer.
+    ///
+    /// All buffered data is discarded. The peer observes [`ErrorKind::ConnectionReset`]
+    /// on its next read or write. This is equivalent to dropping the stream
+    /// with a linger duration of zero.
+    pub fn abort(self) -> Result<()> {
+        IOContext::with_current(|ctx| ctx.tcp_abort(self.inner.fd))
+    }
+
+    /// Sets the linger duration of this socket by setting the SO_LINGER option.
+    ///
+    /// If the linger duration is zero, dropping the stream aborts the connection
+    /// by sending a RST, discarding all buffered data.
+    pub fn set_linger(&self, dur: Option<Duration>) -> Result<()> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get_mut(&self.inner.fd) {
+                ctrl.linger = dur;
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
+    /// Reads the linger duration for this socket by getting the SO_LINGER option.
+    ///
+    /// For more information about this option, see [set_linger](TcpStream::set_linger).
+    pub fn linger(&self) -> Result<Option<Duration>> {
+        IOContext::with_current(|ctx| {
+            if let Some(ctrl) = ctx.tcp.streams.get(&self.inner.fd) {
+                Ok(ctrl.linger)
+            } else {
+                Err(Error::new(ErrorKind::Other, "Lost Tcp"))
+            }
+        })
+    }
+
     /// Splits a `TcpStream` into a read half and a write half, which can be used to read and write the stream concurrently.
     pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
         (
@@ -228,7 +379,7 @@ impl AsyncWrite for TcpStream {
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Poll::Ready(Ok(())) // TODO ?
+        Poll::Ready(self.shutdown(Shutdown::Write))
     }
 }
 
@@ -266,6 +417,7 @@ impl IOContext {
         peer: SocketAddr,
         config: Option<TcpSocketConfig>,
         fd: Option<Fd>,
+        fast_open_data: Option<Vec<u8>>,
     ) -> Result<TcpStream> {
         let (fd, config) = if let Some(fd) = fd {
             // check whether socket was bound.
@@ -313,6 +465,7 @@ impl IOContext {
 
         self.bind_peer(fd, peer);
         let mut ctrl = TransmissionControlBlock::new(fd, self.get_socket_addr(fd)?, config);
+        ctrl.fast_open_data = fast_open_data;
         let span = ctrl.span.clone();
         let _g = span.entered();
 
@@ -362,4 +515,43 @@ impl IOContext {
     pub(super) fn tcp_drop_stream(&mut self, fd: Fd) {
         self.tcp_syscall(fd, TcpSyscall::Close());
     }
+
+    pub(super) fn tcp_abort(&mut self, fd: Fd) -> Result<()> {
+        if !self.tcp.streams.contains_key(&fd) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "socket dropped - invalid fd",
+            ));
+        }
+
+        self.tcp_syscall(fd, TcpSyscall::Abort());
+        Ok(())
+    }
+
+    pub(super) fn tcp_shutdown(&mut self, fd: Fd, how: Shutdown) -> Result<()> {
+        let Some(ctrl) = self.tcp.streams.get_mut(&fd) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "socket dropped - invalid fd",
+            ));
+        };
+
+        if matches!(
+            ctrl.state,
+            TcpState::Closed | TcpState::Listen | TcpState::SynSent | TcpState::SynRcvd
+        ) {
+            return Err(Error::new(ErrorKind::NotConnected, "socket not connected"));
+        }
+
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            ctrl.rx_shutdown = true;
+            ctrl.rx_read_interests.drain(..).for_each(|g| g.wake());
+        }
+
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.tcp_syscall(fd, TcpSyscall::Shutdown());
+        }
+
+        Ok(())
+    }
 }
@@ -6,7 +6,7 @@ use super::TcpStream;
 
 use crate::io::{Interest, Ready};
 use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result};
-use std::net::SocketAddr;
+use std::net::{Shutdown, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::*;
@@ -245,6 +245,8 @@ impl AsyncWrite for OwnedWriteHalf {
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Poll::Ready(Ok(())) // TODO ?
+        Poll::Ready(IOContext::with_current(|ctx| {
+            ctx.tcp_shutdown(self.inner.fd, Shutdown::Write)
+        }))
     }
 }
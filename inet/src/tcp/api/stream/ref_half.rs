@@ -6,7 +6,7 @@ use super::TcpStream;
 
 use crate::io::{Interest, Ready};
 use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result};
-use std::net::SocketAddr;
+use std::net::{Shutdown, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::*;
@@ -230,6 +230,8 @@ impl AsyncWrite for WriteHalf<'_> {
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Poll::Ready(Ok(())) // TODO ?
+        Poll::Ready(IOContext::with_current(|ctx| {
+            ctx.tcp_shutdown(self.stream.inner.fd, Shutdown::Write)
+        }))
     }
 }
@@ -25,8 +25,18 @@ impl TcpBuffer {
     }
 
     pub fn len_continous(&self) -> usize {
-        // TODO
-        self.len().min(self.slices.valid_slice_len() as usize)
+        let end = self.slices.valid_slice_end();
+        self.len().min(end.wrapping_sub(self.read_head) as usize)
+    }
+
+    /// Returns all received ranges `left..right` that are not connected to
+    /// the continous readable slice, in ascending order.
+    pub fn out_of_order_ranges(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.slices
+            .slices
+            .iter()
+            .skip(1)
+            .map(|(seq_no, len)| (*seq_no, seq_no.wrapping_add(*len)))
     }
 
     pub fn cap(&self) -> usize {
@@ -68,7 +78,10 @@ impl TcpBuffer {
 
         // TODO: not wrapping safe
         let end_seq_no = end_seq_no.min(max_seq_no);
-        let k = end_seq_no - seq_no;
+        // Segments starting beyond the buffer capacity are not written
+        let Some(k) = end_seq_no.checked_sub(seq_no) else {
+            return 0;
+        };
 
         self.slices.add(seq_no, k);
         for i in 0..k {
@@ -168,6 +181,13 @@ impl TcpBufferSlices {
     pub(super) fn valid_slice_len(&self) -> u32 {
         self.slices.first().map(|(_, len)| *len).unwrap_or(0)
     }
+
+    pub(super) fn valid_slice_end(&self) -> u32 {
+        self.slices
+            .first()
+            .map(|(seq_no, len)| seq_no.wrapping_add(*len))
+            .unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
@@ -212,6 +232,14 @@ mod tests {
         assert_eq!(r, 0);
     }
 
+    #[test]
+    fn write_beyond_capacity() {
+        let mut tcp = TcpBuffer::new(128, 1000);
+        assert_eq!(tcp.write(&[1; 32], 1200), 0);
+        assert_eq!(tcp.write(&[1; 32], 1112), 16);
+        assert_eq!(tcp.len_continous(), 0);
+    }
+
     #[test]
     fn full_buffer_staggered_read() {
         let mut tcp = TcpBuffer::new(128, 1000);
@@ -294,6 +322,32 @@ mod tests {
         assert_eq!(tcp.len_continous(), 96);
     }
 
+    #[test]
+    fn full_buffer_out_of_order_ranges() {
+        let mut tcp = TcpBuffer::new(256, 1000);
+        assert_eq!(tcp.write(&[1; 32], 1000), 32);
+        assert_eq!(tcp.write(&[3; 32], 1064), 32);
+        assert_eq!(tcp.write(&[5; 32], 1128), 32);
+
+        assert_eq!(
+            tcp.out_of_order_ranges().collect::<Vec<_>>(),
+            vec![(1064, 1096), (1128, 1160)]
+        );
+
+        // Reading the continous slice must not expose the gap
+        let mut buf = [0; 64];
+        assert_eq!(tcp.read(&mut buf), 32);
+        assert_eq!(tcp.len_continous(), 0);
+        assert_eq!(tcp.read(&mut buf), 0);
+
+        assert_eq!(tcp.write(&[2; 32], 1032), 32);
+        assert_eq!(tcp.out_of_order_ranges().collect::<Vec<_>>(), vec![(1128, 1160)]);
+        assert_eq!(tcp.len_continous(), 64);
+        assert_eq!(tcp.read(&mut buf), 64);
+        assert_eq!(&buf[..32], [2; 32]);
+        assert_eq!(&buf[32..], [3; 32]);
+    }
+
     #[test]
     #[should_panic]
     fn full_buffer_too_small_seq_no() {
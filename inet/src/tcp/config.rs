@@ -1,5 +1,6 @@
 use des::runtime::random;
 
+use super::CongestionAlgorithm;
 use crate::IOContext;
 use std::io::{Error, ErrorKind, Result};
 use std::net::SocketAddr;
@@ -10,6 +11,11 @@ use std::time::Duration;
 pub struct TcpConfig {
     pub rst_on_syn: bool,
     pub nack: bool,
+    pub sack: bool,
+    pub window_scaling: bool,
+    pub timestamps: bool,
+    pub ecn: bool,
+    pub mtu_probing: bool,
 
     pub rx_buffer_size: u32,
     pub tx_buffer_size: u32,
@@ -20,11 +26,16 @@ pub struct TcpConfig {
     pub timeout: Duration,
     pub timewait: Duration,
     pub listener_backlog: u32,
+    pub syn_cookies: bool,
+    pub fast_open: bool,
     pub syn_sent_thresh: usize,
     pub cong_ctrl: bool,
+    pub cong_algorithm: CongestionAlgorithm,
 
     pub linger: Option<Duration>,
     pub nodelay: bool,
+    pub ack_delay: Option<Duration>,
+    pub keepalive: Option<KeepaliveConfig>,
 
     pub reuseport: bool,
     pub reuseaddr: bool,
@@ -32,21 +43,53 @@ pub struct TcpConfig {
     pub debug: bool,
 }
 
+/// The configuration of TCP keepalive probes (SO_KEEPALIVE).
+///
+/// Once a connection was idle for `idle`, a probe is send every `interval`
+/// until the peer responds. If `probes` consecutive probes remain unanswered,
+/// the connection is aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeepaliveConfig {
+    pub idle: Duration,
+    pub interval: Duration,
+    pub probes: u32,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(7200),
+            interval: Duration::from_secs(75),
+            probes: 9,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 #[allow(unused)]
 pub(crate) struct TcpSocketConfig {
     pub addr: SocketAddr,
     pub linger: Option<Duration>,
+    pub keepalive: Option<KeepaliveConfig>,
 
     pub listen_backlog: u32,
+    pub syn_cookies: bool,
+    pub fast_open: bool,
     pub rx_buffer_size: u32,
     pub tx_buffer_size: u32,
     pub reuseaddr: bool,
     pub reuseport: bool,
 
     pub cong_ctrl: bool,
+    pub cong_algorithm: CongestionAlgorithm,
+    pub sack: bool,
+    pub window_scaling: bool,
+    pub timestamps: bool,
+    pub ecn: bool,
+    pub mtu_probing: bool,
     pub connect_timeout: Duration,
     pub nodelay: bool,
+    pub ack_delay: Option<Duration>,
 
     pub ttl: u32,
     pub inital_seq_no: u32,
@@ -60,8 +103,11 @@ impl TcpConfig {
         TcpSocketConfig {
             addr: "0.0.0.0:0".parse::<SocketAddr>().unwrap(),
             linger: self.linger,
+            keepalive: self.keepalive,
 
             listen_backlog: self.listener_backlog,
+            syn_cookies: self.syn_cookies,
+            fast_open: self.fast_open,
             rx_buffer_size: self.rx_buffer_size,
             tx_buffer_size: self.tx_buffer_size,
             reuseaddr: self.reuseaddr,
@@ -69,12 +115,19 @@ impl TcpConfig {
 
             connect_timeout: Duration::from_secs(2),
             nodelay: self.nodelay,
+            ack_delay: self.ack_delay,
 
             ttl: self.ttl,
             inital_seq_no: random(),
             mss: self.mss,
 
             cong_ctrl: self.cong_ctrl,
+            cong_algorithm: self.cong_algorithm.clone(),
+            sack: self.sack,
+            window_scaling: self.window_scaling,
+            timestamps: self.timestamps,
+            ecn: self.ecn,
+            mtu_probing: self.mtu_probing,
             debug: self.debug,
         }
     }
@@ -83,8 +136,11 @@ impl TcpConfig {
         TcpSocketConfig {
             addr: "[::0]:0".parse::<SocketAddr>().unwrap(),
             linger: self.linger,
+            keepalive: self.keepalive,
 
             listen_backlog: self.listener_backlog,
+            syn_cookies: self.syn_cookies,
+            fast_open: self.fast_open,
             rx_buffer_size: self.rx_buffer_size,
             tx_buffer_size: self.tx_buffer_size,
             reuseaddr: self.reuseaddr,
@@ -92,12 +148,19 @@ impl TcpConfig {
 
             connect_timeout: Duration::from_secs(2),
             nodelay: self.nodelay,
+            ack_delay: self.ack_delay,
 
             ttl: self.ttl,
             inital_seq_no: random(),
             mss: self.mss,
 
             cong_ctrl: self.cong_ctrl,
+            cong_algorithm: self.cong_algorithm.clone(),
+            sack: self.sack,
+            window_scaling: self.window_scaling,
+            timestamps: self.timestamps,
+            ecn: self.ecn,
+            mtu_probing: self.mtu_probing,
             debug: self.debug,
         }
     }
@@ -106,8 +169,11 @@ impl TcpConfig {
         TcpSocketConfig {
             addr,
             linger: self.linger,
+            keepalive: self.keepalive,
 
             listen_backlog: self.listener_backlog,
+            syn_cookies: self.syn_cookies,
+            fast_open: self.fast_open,
             rx_buffer_size: self.rx_buffer_size,
             tx_buffer_size: self.tx_buffer_size,
             reuseaddr: self.reuseaddr,
@@ -115,12 +181,19 @@ impl TcpConfig {
 
             connect_timeout: Duration::from_secs(2),
             nodelay: self.nodelay,
+            ack_delay: self.ack_delay,
 
             ttl: self.ttl,
             inital_seq_no: random(),
             mss: self.mss,
 
             cong_ctrl: self.cong_ctrl,
+            cong_algorithm: self.cong_algorithm.clone(),
+            sack: self.sack,
+            window_scaling: self.window_scaling,
+            timestamps: self.timestamps,
+            ecn: self.ecn,
+            mtu_probing: self.mtu_probing,
             debug: self.debug,
         }
     }
@@ -129,8 +202,11 @@ impl TcpConfig {
         TcpSocketConfig {
             addr,
             linger: None,
+            keepalive: self.keepalive,
 
             listen_backlog: 1,
+            syn_cookies: false,
+            fast_open: false,
             rx_buffer_size: 2048,
             tx_buffer_size: 2048,
             reuseaddr: false,
@@ -138,12 +214,19 @@ impl TcpConfig {
 
             connect_timeout: Duration::from_secs(2),
             nodelay: true,
+            ack_delay: self.ack_delay,
 
             ttl: 64,
             inital_seq_no: random(),
             mss: 1024,
 
             cong_ctrl: self.cong_ctrl,
+            cong_algorithm: self.cong_algorithm.clone(),
+            sack: self.sack,
+            window_scaling: self.window_scaling,
+            timestamps: self.timestamps,
+            ecn: self.ecn,
+            mtu_probing: self.mtu_probing,
             debug: self.debug,
         }
     }
@@ -154,10 +237,17 @@ impl Default for TcpConfig {
         Self {
             rst_on_syn: true,
             nack: false,
+            sack: false,
+            window_scaling: false,
+            timestamps: false,
+            ecn: false,
+            mtu_probing: false,
 
             rx_buffer_size: 0b1 << 15,
             tx_buffer_size: 0b1 << 15,
             listener_backlog: 32,
+            syn_cookies: false,
+            fast_open: false,
 
             mss: 1024,
 
@@ -166,9 +256,12 @@ impl Default for TcpConfig {
             timewait: Duration::from_secs(1),
             syn_sent_thresh: 3,
             cong_ctrl: false,
+            cong_algorithm: CongestionAlgorithm::Reno,
 
             linger: None,
             nodelay: true,
+            ack_delay: None,
+            keepalive: None,
             reuseaddr: true,
             reuseport: true,
 
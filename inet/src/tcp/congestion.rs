@@ -0,0 +1,585 @@
+use des::time::SimTime;
+use std::{fmt, hash::Hash, sync::Arc, time::Duration};
+
+/// A snapshot of the connection state, passed to the hooks
+/// of a [`CongestionControl`] algorithm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CongestionState {
+    /// The negotiated maximum segment size.
+    pub mss: u32,
+    /// The number of bytes send, but not yet acknowledged.
+    pub flight_size: u32,
+    /// The highest cumulative acknowledgement number received.
+    pub ack_no: u32,
+    /// The sequence number of the next byte to be send.
+    pub next_seq_no: u32,
+    /// The smoothed round trip time.
+    pub srtt: Duration,
+    /// The current simulation time.
+    pub now: SimTime,
+}
+
+/// A congestion control algorithm, that limits the amount of
+/// unacknowledged data in flight.
+///
+/// The algorithm is driven by the TCP sender, which calls the hooks
+/// for acknowledgements, duplicate acknowledgements, retransmission
+/// timeouts and detected losses. The resulting congestion window is
+/// used as an upper bound for the send window.
+pub trait CongestionControl: fmt::Debug {
+    /// The name of the algorithm, used for diagnostics.
+    fn name(&self) -> &'static str;
+
+    /// The current congestion window in bytes.
+    fn cwnd(&self) -> u32;
+
+    /// The current slow start threshold in bytes.
+    fn ssthresh(&self) -> u32;
+
+    /// Indicates whether the algorithm is currently recovering from a loss.
+    fn in_recovery(&self) -> bool {
+        false
+    }
+
+    /// Resets the algorithm, either on connection establishment or on reset.
+    fn reset(&mut self, state: &CongestionState);
+
+    /// Called for each acknowledgement that acknowledges `acked` new bytes.
+    fn on_ack(&mut self, state: &CongestionState, acked: u32);
+
+    /// Called for each duplicate acknowledgement. `count` is the number
+    /// of consecutive duplicate acknowledgements, including this one.
+    fn on_dup_ack(&mut self, state: &CongestionState, count: u32);
+
+    /// Called once a segment loss was detected by duplicate acknowledgements.
+    fn on_loss(&mut self, state: &CongestionState);
+
+    /// Called once the retransmission timer expired.
+    fn on_rto(&mut self, state: &CongestionState);
+
+    /// Called once the peer echoed a congestion experienced mark (RFC 3168),
+    /// at most once per window of data.
+    ///
+    /// By default, this is treated like a detected loss.
+    fn on_ecn(&mut self, state: &CongestionState) {
+        self.on_loss(state);
+    }
+}
+
+/// A constructor for a custom [`CongestionControl`] algorithm, called
+/// with the maximum segment size for each new connection.
+#[derive(Clone)]
+pub struct CongestionControlFactory(Arc<dyn Fn(u16) -> Box<dyn CongestionControl> + Send + Sync>);
+
+impl CongestionControlFactory {
+    /// Creates a new factory from a constructor function.
+    pub fn new(f: impl Fn(u16) -> Box<dyn CongestionControl> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    fn ptr(&self) -> *const () {
+        Arc::as_ptr(&self.0).cast::<()>()
+    }
+}
+
+impl fmt::Debug for CongestionControlFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CongestionControlFactory")
+            .field(&self.ptr())
+            .finish()
+    }
+}
+
+impl PartialEq for CongestionControlFactory {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr() == other.ptr()
+    }
+}
+
+impl Eq for CongestionControlFactory {}
+
+impl Hash for CongestionControlFactory {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ptr().hash(state)
+    }
+}
+
+/// The congestion control algorithm used by a TCP socket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CongestionAlgorithm {
+    /// TCP Reno (RFC 5681).
+    #[default]
+    Reno,
+    /// TCP NewReno (RFC 6582).
+    NewReno,
+    /// TCP CUBIC (RFC 8312).
+    Cubic,
+    /// A user defined algorithm.
+    Custom(CongestionControlFactory),
+}
+
+impl CongestionAlgorithm {
+    pub(crate) fn build(&self, mss: u16) -> Box<dyn CongestionControl> {
+        match self {
+            Self::Reno => Box::new(Reno::new(mss)),
+            Self::NewReno => Box::new(NewReno::new(mss)),
+            Self::Cubic => Box::new(Cubic::new(mss)),
+            Self::Custom(factory) => (factory.0)(mss),
+        }
+    }
+}
+
+const INITIAL_SSTHRESH_SEGMENTS: u32 = 8;
+
+fn loss_ssthresh(state: &CongestionState) -> u32 {
+    (state.flight_size / 2).max(2 * state.mss)
+}
+
+/// TCP Reno with slow start, congestion avoidance
+/// and fast recovery as described in RFC 5681.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reno {
+    cwnd: u32,
+    ssthresh: u32,
+    avoid_counter: u32,
+    recovery: bool,
+}
+
+impl Reno {
+    /// Creates a new instance with an inital window of one segment.
+    pub fn new(mss: u16) -> Self {
+        Self {
+            cwnd: mss as u32,
+            ssthresh: INITIAL_SSTHRESH_SEGMENTS * mss as u32,
+            avoid_counter: 0,
+            recovery: false,
+        }
+    }
+
+    fn increase(&mut self, mss: u32, acked: u32) {
+        if self.cwnd < self.ssthresh {
+            // Slow start
+            self.cwnd += acked.min(mss);
+        } else {
+            // Congestion avoidance, one segment per window
+            self.avoid_counter += acked;
+            if self.avoid_counter >= self.cwnd {
+                self.avoid_counter -= self.cwnd;
+                self.cwnd += mss;
+            }
+        }
+    }
+}
+
+impl CongestionControl for Reno {
+    fn name(&self) -> &'static str {
+        "reno"
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn in_recovery(&self) -> bool {
+        self.recovery
+    }
+
+    fn reset(&mut self, state: &CongestionState) {
+        *self = Self::new(state.mss as u16);
+    }
+
+    fn on_ack(&mut self, state: &CongestionState, acked: u32) {
+        if self.recovery {
+            // Deflate the window after fast recovery
+            self.recovery = false;
+            self.cwnd = self.ssthresh;
+            return;
+        }
+        self.increase(state.mss, acked);
+    }
+
+    fn on_dup_ack(&mut self, state: &CongestionState, _count: u32) {
+        if self.recovery {
+            // Window inflation, each dup ACK indicates a segment that left the network
+            self.cwnd += state.mss;
+        }
+    }
+
+    fn on_loss(&mut self, state: &CongestionState) {
+        if self.recovery {
+            return;
+        }
+        self.ssthresh = loss_ssthresh(state);
+        self.cwnd = self.ssthresh + 3 * state.mss;
+        self.avoid_counter = 0;
+        self.recovery = true;
+    }
+
+    fn on_rto(&mut self, state: &CongestionState) {
+        self.ssthresh = loss_ssthresh(state);
+        self.cwnd = state.mss;
+        self.avoid_counter = 0;
+        self.recovery = false;
+    }
+
+    fn on_ecn(&mut self, state: &CongestionState) {
+        if self.recovery {
+            return;
+        }
+        // Nothing was lost, so no fast recovery is required
+        self.ssthresh = loss_ssthresh(state);
+        self.cwnd = self.ssthresh;
+        self.avoid_counter = 0;
+    }
+}
+
+/// TCP NewReno, a modification of Reno that stays in fast recovery
+/// until all data outstanding at the time of the loss is acknowledged (RFC 6582).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewReno {
+    reno: Reno,
+    recover: u32,
+}
+
+impl NewReno {
+    /// Creates a new instance with an inital window of one segment.
+    pub fn new(mss: u16) -> Self {
+        Self {
+            reno: Reno::new(mss),
+            recover: 0,
+        }
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn name(&self) -> &'static str {
+        "newreno"
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.reno.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.reno.ssthresh
+    }
+
+    fn in_recovery(&self) -> bool {
+        self.reno.recovery
+    }
+
+    fn reset(&mut self, state: &CongestionState) {
+        *self = Self::new(state.mss as u16);
+    }
+
+    fn on_ack(&mut self, state: &CongestionState, acked: u32) {
+        if !self.reno.recovery {
+            self.reno.increase(state.mss, acked);
+            return;
+        }
+
+        if state.ack_no >= self.recover {
+            // Full acknowledgement, exit fast recovery
+            self.reno.recovery = false;
+            self.reno.cwnd = self
+                .reno
+                .ssthresh
+                .min(state.flight_size.max(state.mss) + state.mss);
+        } else {
+            // Partial acknowledgement, deflate by the amount of new data
+            self.reno.cwnd = self.reno.cwnd.saturating_sub(acked);
+            if acked >= state.mss {
+                self.reno.cwnd += state.mss;
+            }
+            self.reno.cwnd = self.reno.cwnd.max(state.mss);
+        }
+    }
+
+    fn on_dup_ack(&mut self, state: &CongestionState, count: u32) {
+        self.reno.on_dup_ack(state, count);
+    }
+
+    fn on_loss(&mut self, state: &CongestionState) {
+        if self.reno.recovery {
+            // Allready recovering from a loss in this window
+            return;
+        }
+        self.recover = state.next_seq_no;
+        self.reno.on_loss(state);
+    }
+
+    fn on_rto(&mut self, state: &CongestionState) {
+        self.recover = state.next_seq_no;
+        self.reno.on_rto(state);
+    }
+
+    fn on_ecn(&mut self, state: &CongestionState) {
+        self.reno.on_ecn(state);
+    }
+}
+
+const CUBIC_C: f64 = 0.4;
+const CUBIC_BETA: f64 = 0.7;
+
+/// TCP CUBIC, which grows the window as a cubic function of the
+/// time since the last congestion event (RFC 8312).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubic {
+    cwnd: u32,
+    ssthresh: u32,
+    w_max: f64,
+    w_last_max: f64,
+    k: f64,
+    epoch_start: Option<SimTime>,
+    avoid_counter: u32,
+    recovery: bool,
+}
+
+impl Cubic {
+    /// Creates a new instance with an inital window of one segment.
+    pub fn new(mss: u16) -> Self {
+        Self {
+            cwnd: mss as u32,
+            ssthresh: INITIAL_SSTHRESH_SEGMENTS * mss as u32,
+            w_max: 0.0,
+            w_last_max: 0.0,
+            k: 0.0,
+            epoch_start: None,
+            avoid_counter: 0,
+            recovery: false,
+        }
+    }
+
+    fn w_cubic(&self, t: f64) -> f64 {
+        CUBIC_C * (t - self.k).powi(3) + self.w_max
+    }
+
+    fn w_est(&self, t: f64, rtt: f64) -> f64 {
+        self.w_max * CUBIC_BETA + 3.0 * (1.0 - CUBIC_BETA) / (1.0 + CUBIC_BETA) * (t / rtt)
+    }
+
+    fn congestion_event(&mut self, state: &CongestionState) {
+        let mss = state.mss as f64;
+        let cwnd = self.cwnd as f64 / mss;
+
+        // Fast convergence
+        self.w_max = if cwnd < self.w_last_max {
+            cwnd * (1.0 + CUBIC_BETA) / 2.0
+        } else {
+            cwnd
+        };
+        self.w_last_max = cwnd;
+
+        self.ssthresh = ((cwnd * CUBIC_BETA * mss) as u32).max(2 * state.mss);
+        self.k = (self.w_max * (1.0 - CUBIC_BETA) / CUBIC_C).cbrt();
+        self.epoch_start = None;
+        self.avoid_counter = 0;
+    }
+}
+
+impl CongestionControl for Cubic {
+    fn name(&self) -> &'static str {
+        "cubic"
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn in_recovery(&self) -> bool {
+        self.recovery
+    }
+
+    fn reset(&mut self, state: &CongestionState) {
+        *self = Self::new(state.mss as u16);
+    }
+
+    fn on_ack(&mut self, state: &CongestionState, acked: u32) {
+        if self.recovery {
+            self.recovery = false;
+            self.cwnd = self.ssthresh;
+            return;
+        }
+
+        if self.cwnd < self.ssthresh {
+            // Slow start
+            self.cwnd += acked.min(state.mss);
+            return;
+        }
+
+        let mss = state.mss as f64;
+        // A congestion avoidance epoch without a previous loss
+        // starts at the current window.
+        let epoch_start = *self.epoch_start.get_or_insert(state.now);
+        if self.w_max == 0.0 {
+            self.w_max = self.cwnd as f64 / mss;
+            self.k = 0.0;
+        }
+
+        let t = (state.now - epoch_start).as_secs_f64();
+        let rtt = state.srtt.as_secs_f64().max(f64::EPSILON);
+        let cwnd = self.cwnd as f64 / mss;
+
+        let target = self.w_cubic(t + rtt).max(self.w_est(t, rtt));
+        if target > cwnd {
+            // Increase by (target - cwnd) / cwnd segments per ACK
+            self.avoid_counter += ((target - cwnd) / cwnd * mss) as u32;
+        } else {
+            // Minimal growth in the plateau region
+            self.avoid_counter += (mss / (100.0 * cwnd)) as u32;
+        }
+
+        if self.avoid_counter >= state.mss {
+            self.cwnd += self.avoid_counter - self.avoid_counter % state.mss;
+            self.avoid_counter %= state.mss;
+        }
+    }
+
+    fn on_dup_ack(&mut self, state: &CongestionState, _count: u32) {
+        if self.recovery {
+            self.cwnd += state.mss;
+        }
+    }
+
+    fn on_loss(&mut self, state: &CongestionState) {
+        if self.recovery {
+            return;
+        }
+        self.congestion_event(state);
+        self.cwnd = self.ssthresh;
+        self.recovery = true;
+    }
+
+    fn on_rto(&mut self, state: &CongestionState) {
+        self.congestion_event(state);
+        self.cwnd = state.mss;
+        self.recovery = false;
+    }
+
+    fn on_ecn(&mut self, state: &CongestionState) {
+        if self.recovery {
+            return;
+        }
+        self.congestion_event(state);
+        self.cwnd = self.ssthresh;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(flight_size: u32, ack_no: u32, next_seq_no: u32, now: f64) -> CongestionState {
+        CongestionState {
+            mss: 1000,
+            flight_size,
+            ack_no,
+            next_seq_no,
+            srtt: Duration::from_millis(100),
+            now: SimTime::from(Duration::from_secs_f64(now)),
+        }
+    }
+
+    #[test]
+    fn reno_slow_start_and_avoidance() {
+        let mut reno = Reno::new(1000);
+        assert_eq!(reno.cwnd(), 1000);
+
+        for _ in 0..7 {
+            reno.on_ack(&state(0, 0, 0, 0.0), 1000);
+        }
+        assert_eq!(reno.cwnd(), 8000);
+        assert_eq!(reno.ssthresh(), 8000);
+
+        // Congestion avoidance needs a full window of ACKs
+        for _ in 0..7 {
+            reno.on_ack(&state(0, 0, 0, 0.0), 1000);
+        }
+        assert_eq!(reno.cwnd(), 8000);
+        reno.on_ack(&state(0, 0, 0, 0.0), 1000);
+        assert_eq!(reno.cwnd(), 9000);
+    }
+
+    #[test]
+    fn reno_fast_recovery() {
+        let mut reno = Reno::new(1000);
+        reno.on_loss(&state(10_000, 0, 10_000, 0.0));
+        assert!(reno.in_recovery());
+        assert_eq!(reno.ssthresh(), 5000);
+        assert_eq!(reno.cwnd(), 8000);
+
+        reno.on_dup_ack(&state(10_000, 0, 10_000, 0.0), 4);
+        assert_eq!(reno.cwnd(), 9000);
+
+        reno.on_ack(&state(0, 10_000, 10_000, 0.0), 10_000);
+        assert!(!reno.in_recovery());
+        assert_eq!(reno.cwnd(), 5000);
+
+        reno.on_rto(&state(5000, 10_000, 15_000, 0.0));
+        assert_eq!(reno.cwnd(), 1000);
+        assert_eq!(reno.ssthresh(), 2500);
+    }
+
+    #[test]
+    fn reno_ecn_without_recovery() {
+        let mut reno = Reno::new(1000);
+        reno.cwnd = 10_000;
+
+        reno.on_ecn(&state(10_000, 0, 10_000, 0.0));
+        assert!(!reno.in_recovery());
+        assert_eq!(reno.ssthresh(), 5000);
+        assert_eq!(reno.cwnd(), 5000);
+
+        // Congestion avoidance continues immediately
+        reno.on_ack(&state(9000, 1000, 10_000, 0.0), 1000);
+        assert_eq!(reno.cwnd(), 5000);
+    }
+
+    #[test]
+    fn newreno_partial_ack() {
+        let mut newreno = NewReno::new(1000);
+        newreno.on_loss(&state(10_000, 0, 10_000, 0.0));
+        assert!(newreno.in_recovery());
+
+        newreno.on_ack(&state(8000, 2000, 10_000, 0.0), 2000);
+        assert!(newreno.in_recovery());
+        assert_eq!(newreno.cwnd(), 7000);
+
+        newreno.on_ack(&state(0, 10_000, 10_000, 0.0), 8000);
+        assert!(!newreno.in_recovery());
+        assert_eq!(newreno.cwnd(), 2000);
+    }
+
+    #[test]
+    fn cubic_regrows_to_w_max() {
+        let mut cubic = Cubic::new(1000);
+        cubic.cwnd = 20_000;
+        cubic.ssthresh = 10_000;
+
+        cubic.on_loss(&state(20_000, 0, 20_000, 1.0));
+        assert_eq!(cubic.cwnd(), 14_000);
+        assert_eq!(cubic.ssthresh(), 14_000);
+        cubic.on_ack(&state(0, 20_000, 20_000, 1.0), 1000);
+        assert!(!cubic.in_recovery());
+
+        // One window of ACKs per RTT, should reach w_max after K seconds
+        let mut t = 1.0;
+        while t < 1.0 + cubic.k {
+            let mut s = state(0, 20_000, 20_000, t);
+            s.srtt = Duration::from_secs(1);
+            for _ in 0..cubic.cwnd() / 1000 {
+                cubic.on_ack(&s, 1000);
+            }
+            t += 1.0;
+        }
+        assert_eq!(cubic.cwnd(), 20_000);
+    }
+}
@@ -0,0 +1,133 @@
+use des::time::SimTime;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+
+// A SYN cookie encodes the state of a half-open connection into the
+// inital sequence number of the SYNACK (RFC 4987):
+//
+// - bits 31..27: a coarse timestamp, incremented every 64s
+// - bits 26..24: an index into SYN_COOKIE_MSS
+// - bits 23..0:  a keyed hash over the connection and the timestamp
+
+/// The MSS values that can be encoded in a SYN cookie.
+const SYN_COOKIE_MSS: [u16; 8] = [64, 256, 536, 1024, 1220, 1300, 1440, 1460];
+
+/// The duration of a single timestamp tick of a SYN cookie, in seconds.
+const SYN_COOKIE_TICK: u64 = 64;
+
+/// The number of ticks a SYN cookie remains valid.
+const SYN_COOKIE_MAX_AGE: u32 = 1;
+
+fn syn_cookie_counter() -> u32 {
+    (SimTime::now().as_secs() / SYN_COOKIE_TICK) as u32 & 0x1f
+}
+
+fn syn_cookie_hash(secret: u64, src: SocketAddr, dest: SocketAddr, isn: u32, t: u32) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    (secret, src, dest, isn, t).hash(&mut hasher);
+    hasher.finish() as u32 & 0x00ff_ffff
+}
+
+/// Creates a SYN cookie for a SYN from `src` to `dest` with the inital
+/// sequence number `isn`, announcing a MSS of `mss`.
+///
+/// The encoded MSS is rounded down to the closest supported value.
+pub(crate) fn syn_cookie(
+    secret: u64,
+    src: SocketAddr,
+    dest: SocketAddr,
+    isn: u32,
+    mss: u16,
+) -> u32 {
+    let idx = SYN_COOKIE_MSS.iter().rposition(|v| *v <= mss).unwrap_or(0) as u32;
+    let t = syn_cookie_counter();
+
+    (t << 27) | (idx << 24) | syn_cookie_hash(secret, src, dest, isn, t)
+}
+
+/// Validates a SYN cookie, returned by the ACK completing a handshake.
+///
+/// Returns the encoded MSS, if the cookie is valid and has not expired.
+pub(crate) fn check_syn_cookie(
+    secret: u64,
+    src: SocketAddr,
+    dest: SocketAddr,
+    isn: u32,
+    cookie: u32,
+) -> Option<u16> {
+    let t = cookie >> 27;
+    let age = syn_cookie_counter().wrapping_sub(t) & 0x1f;
+    if age > SYN_COOKIE_MAX_AGE {
+        return None;
+    }
+
+    if cookie & 0x00ff_ffff != syn_cookie_hash(secret, src, dest, isn, t) {
+        return None;
+    }
+
+    Some(SYN_COOKIE_MSS[((cookie >> 24) & 0b111) as usize])
+}
+
+/// Creates a TCP Fast Open cookie for the client `client` (RFC 7413).
+///
+/// Since the cookie only depends on the client address, it remains valid
+/// until the secret of the listener changes.
+pub(crate) fn fast_open_cookie(secret: u64, client: IpAddr) -> Vec<u8> {
+    let mut hasher = DefaultHasher::new();
+    (secret, client).hash(&mut hasher);
+    hasher.finish().to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs() -> (SocketAddr, SocketAddr) {
+        (
+            "10.0.0.1:4000".parse().unwrap(),
+            "10.0.0.2:80".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn syn_cookie_roundtrip() {
+        let (src, dest) = addrs();
+        let cookie = syn_cookie(0xdead_beef, src, dest, 1000, 1460);
+        assert_eq!(
+            check_syn_cookie(0xdead_beef, src, dest, 1000, cookie),
+            Some(1460)
+        );
+
+        // The MSS is rounded down
+        let cookie = syn_cookie(0xdead_beef, src, dest, 1000, 1400);
+        assert_eq!(
+            check_syn_cookie(0xdead_beef, src, dest, 1000, cookie),
+            Some(1300)
+        );
+    }
+
+    #[test]
+    fn syn_cookie_rejects_forged() {
+        let (src, dest) = addrs();
+        let cookie = syn_cookie(0xdead_beef, src, dest, 1000, 1460);
+
+        assert_eq!(check_syn_cookie(0xdead_beef, src, dest, 1001, cookie), None);
+        assert_eq!(check_syn_cookie(0xdead_beef, dest, src, 1000, cookie), None);
+        assert_eq!(check_syn_cookie(0xcafe_babe, src, dest, 1000, cookie), None);
+        assert_eq!(
+            check_syn_cookie(0xdead_beef, src, dest, 1000, cookie ^ 1),
+            None
+        );
+    }
+
+    #[test]
+    fn fast_open_cookie_per_client() {
+        let (src, dest) = addrs();
+        let cookie = fast_open_cookie(0xdead_beef, src.ip());
+        assert_eq!(cookie.len(), 8);
+        assert_eq!(cookie, fast_open_cookie(0xdead_beef, src.ip()));
+        assert_ne!(cookie, fast_open_cookie(0xdead_beef, dest.ip()));
+        assert_ne!(cookie, fast_open_cookie(0xcafe_babe, src.ip()));
+    }
+}
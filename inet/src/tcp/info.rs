@@ -0,0 +1,44 @@
+use super::TcpState;
+use std::time::Duration;
+
+/// A snapshot of the internal state of a TCP connection, similar to TCP_INFO.
+///
+/// Obtained using [`TcpStream::info`](crate::TcpStream::info).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct TcpInfo {
+    /// The state of the connection state machine.
+    pub state: TcpState,
+    /// The negotiated maximum segment size.
+    pub mss: u16,
+
+    /// The smoothed round trip time.
+    pub rtt: Duration,
+    /// The round trip time variance.
+    pub rttvar: Duration,
+    /// The current retransmission timeout.
+    pub rto: Duration,
+
+    /// The congestion window, if congestion control is enabled.
+    pub cwnd: Option<u32>,
+    /// The slow start threshold, if congestion control is enabled.
+    pub ssthresh: Option<u32>,
+
+    /// The number of bytes send, including retransmissions.
+    pub bytes_sent: u64,
+    /// The number of bytes acknowledged by the peer.
+    pub bytes_acked: u64,
+    /// The number of bytes retransmitted.
+    pub bytes_retransmitted: u64,
+    /// The number of segments retransmitted.
+    pub retransmits: u32,
+    /// Whether explicit congestion notification was negotiated.
+    pub ecn: bool,
+    /// The number of window reductions caused by ECN echos.
+    pub ecn_reductions: u32,
+
+    /// The window advertised to the peer.
+    pub recv_window: u32,
+    /// The window advertised by the peer.
+    pub send_window: u32,
+}
@@ -26,7 +26,10 @@ use crate::{
     socket::{Fd, SocketIfaceBinding, SocketType},
 };
 use inet_types::{
-    ip::{IpPacket, IpPacketRef, IpVersion, Ipv4Flags, Ipv4Packet, Ipv6Packet},
+    ip::{
+        IpPacket, IpPacketRef, IpVersion, Ipv4Flags, Ipv4Packet, Ipv6Packet, ECN_CE, ECN_ECT0,
+        ECN_NOT_ECT,
+    },
     tcp::{TcpFlags, TcpOption, TcpPacket, PROTO_TCP},
 };
 
@@ -36,13 +39,22 @@ pub use self::buffer::*;
 mod config;
 pub use self::config::*;
 
+mod congestion;
+pub use self::congestion::*;
+
+mod cookie;
+use self::cookie::*;
+
+mod info;
+pub use self::info::*;
+
 mod types;
-pub(crate) use types::TcpState;
+pub use types::TcpState;
 use types::*;
 
 pub(super) mod api;
 use api::*;
-pub use api::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, ReuniteError, WriteHalf};
+pub use api::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, ReuniteError, TcpListenerStats, WriteHalf};
 
 mod interest;
 use interest::*;
@@ -53,6 +65,7 @@ pub(crate) struct Tcp {
     pub config: TcpConfig,
     pub binds: FxHashMap<Fd, ListenerHandle>,
     pub streams: FxHashMap<Fd, TransmissionControlBlock>,
+    pub fast_open_cookies: FxHashMap<IpAddr, Vec<u8>>,
 }
 
 #[derive(Debug)]
@@ -63,9 +76,12 @@ pub(crate) struct TransmissionControlBlock {
     peer_addr: SocketAddr,
     dropped: bool,
     span: Span,
+    error: Option<Error>, // a pending error, reported by the next read or write
 
     // # Handshake
     syn_resend_counter: usize,
+    fast_open_cookie: Option<Vec<u8>>, // the valid cookie of the peer (listener side)
+    fast_open_data: Option<Vec<u8>>,   // data to be send with the SYN (client side)
 
     rtt_probe: SimTime,
     rtt_probe_seq_no: u32,
@@ -83,6 +99,11 @@ pub(crate) struct TransmissionControlBlock {
     tx_max_send_seq_no: u32, // the maximum byte that may be sent, based on the flow-control window
     tx_dup_ack_counter: u32,
     tx_write_interests: Vec<TcpInterestGuard>,
+    tx_sack_scoreboard: Vec<(u32, u32)>, // ranges above tx_last_ack_no the peer has selectivly acked
+    tx_sack_recovery_seq_no: u32,        // the end of the last hole retransmitted due to SACK information
+    tx_window_shift: u8,                 // the scaling applied to windows advertised by the peer
+    tx_max_sent_seq_no: u32,             // the highest seq_no send so far, to detect retransmits
+    tx_timeouts: u32,                    // the number of consecutive RTOs without progress
 
     // # Recv buffer
     rx_state: TcpReceiverState,
@@ -90,26 +111,56 @@ pub(crate) struct TransmissionControlBlock {
     rx_last_recv_seq_no: u32,
     rx_fin_seq_no: u32,
     rx_read_interests: Vec<TcpInterestGuard>,
+    rx_last_out_of_order_seq_no: u32, // the seq_no of the most recent out of order segment
+    rx_window_shift: u8,              // the scaling applied to our advertised windows
+    rx_ack_pending: u32,              // the number of received segments not yet acknowledged
+    rx_ack_timer: u16,                // the id of the current delayed ACK timer
+    rx_shutdown: bool,                // whether reads were shut down by the application
+
+    // # Timestamps
+    ts: bool,
+    ts_recent: u32, // the most recent valid timestamp of the peer, echoed in TSecr
+
+    // # ECN
+    ecn: bool,
+    ecn_echo: bool,          // CE was received, echo ECE until CWR is received
+    ecn_cwr: bool,           // the window was reduced, signal CWR with the next segment
+    ecn_recover_seq_no: u32, // no further reductions until this seq_no is acked
+
+    // # Keepalive
+    keepalive: Option<KeepaliveConfig>,
+    keepalive_probes: u32, // the number of unanswered keepalive probes
+    last_recv: SimTime,
 
     // # Congestions
     congestion_ctrl: bool,
-    congestion_window: u32,
-    ssthresh: u32,
-    congestion_avoid_counter: u32,
-    slow_start: bool,
+    congestion: Box<dyn CongestionControl>,
 
     // # Parameters
     timeout: Duration,
     timewait: Duration,
     timer: u16,
+    timer_kind: TcpTimer, // the purpose of the current timer
+    persist_backoff: u32, // the number of zero window probes without window update
     fd: Fd,
     inital_seq_no: u32,
-    mss: u16,
+    mss: u16,            // the effective segment size, limited by the path MTU
+    negotiated_mss: u16, // the segment size agreed upon during the handshake
+    mss_clamp: u16,      // the segment size limit after detecting a PMTU black hole
+    mtu_probing: bool,
     ttl: u8,
+    sack: bool,
+    window_scaling: bool,
+    nodelay: bool,
+    ack_delay: Option<Duration>,
+    linger: Option<Duration>,
 
     // # Metrics
-    sender_send_bytes: usize,
-    sender_ack_bytes: usize,
+    sender_send_bytes: u64,
+    sender_ack_bytes: u64,
+    sender_retransmitted_bytes: u64,
+    sender_retransmits: u32,
+    sender_ecn_reductions: u32,
 
     debug: bool,
 
@@ -117,6 +168,13 @@ pub(crate) struct TransmissionControlBlock {
     established: Option<oneshot::Sender<Result<()>>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TcpTimer {
+    Data,      // retransmissions and state timeouts
+    Keepalive, // probes of idle connections
+    Persist,   // probes of zero windows
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum TcpSenderState {
     Opening,       // syn
@@ -140,6 +198,7 @@ impl Tcp {
             config: TcpConfig::default(),
             binds: FxHashMap::with_hasher(FxBuildHasher::default()),
             streams: FxHashMap::with_hasher(FxBuildHasher::default()),
+            fast_open_cookies: FxHashMap::with_hasher(FxBuildHasher::default()),
         }
     }
 }
@@ -155,8 +214,11 @@ impl TransmissionControlBlock {
             local_addr: addr,
             peer_addr: peer,
             span,
+            error: None,
 
             syn_resend_counter: 0,
+            fast_open_cookie: None,
+            fast_open_data: None,
 
             rtt_probe: SimTime::ZERO,
             rtt_probe_seq_no: 0,
@@ -173,29 +235,65 @@ impl TransmissionControlBlock {
             tx_max_send_seq_no: 0,
             tx_dup_ack_counter: 0,
             tx_write_interests: Vec::new(),
+            tx_sack_scoreboard: Vec::new(),
+            tx_sack_recovery_seq_no: 0,
+            tx_window_shift: 0,
+            tx_max_sent_seq_no: 0,
+            tx_timeouts: 0,
 
             rx_state: TcpReceiverState::Closed,
-            rx_buffer: TcpBuffer::new(config.tx_buffer_size as usize, 0),
+            rx_buffer: TcpBuffer::new(config.rx_buffer_size as usize, 0),
             rx_last_recv_seq_no: 0,
             rx_fin_seq_no: 0,
             rx_read_interests: Vec::new(),
+            rx_last_out_of_order_seq_no: 0,
+            rx_window_shift: if config.window_scaling {
+                window_shift_for(config.rx_buffer_size)
+            } else {
+                0
+            },
+            rx_ack_pending: 0,
+            rx_ack_timer: 0,
+            rx_shutdown: false,
+
+            ts: config.timestamps,
+            ts_recent: 0,
+
+            ecn: config.ecn,
+            ecn_echo: false,
+            ecn_cwr: false,
+            ecn_recover_seq_no: 0,
+
+            keepalive: config.keepalive,
+            keepalive_probes: 0,
+            last_recv: SimTime::ZERO,
 
             congestion_ctrl: config.cong_ctrl,
-            congestion_window: config.mss as u32,
-            ssthresh: 8 * config.mss as u32,
-            congestion_avoid_counter: 0,
-            slow_start: true,
+            congestion: config.cong_algorithm.build(config.mss),
 
             timeout: Duration::from_secs(1),
             timewait: Duration::from_secs(1),
             timer: 0,
+            timer_kind: TcpTimer::Data,
+            persist_backoff: 0,
             fd,
             inital_seq_no: config.inital_seq_no,
             mss: config.mss,
+            negotiated_mss: config.mss,
+            mss_clamp: u16::MAX,
+            mtu_probing: config.mtu_probing,
             ttl: config.ttl as u8,
+            sack: config.sack,
+            window_scaling: config.window_scaling,
+            nodelay: config.nodelay,
+            ack_delay: config.ack_delay,
+            linger: config.linger,
 
             sender_send_bytes: 0,
             sender_ack_bytes: 0,
+            sender_retransmitted_bytes: 0,
+            sender_retransmits: 0,
+            sender_ecn_reductions: 0,
 
             debug: config.debug,
 
@@ -204,6 +302,49 @@ impl TransmissionControlBlock {
     }
 }
 
+/// The smallest shift, so that a window of `size` bytes can be advertised (RFC 7323).
+fn window_shift_for(size: u32) -> u8 {
+    const MAX_WINDOW_SHIFT: u8 = 14;
+
+    let mut shift = 0;
+    while (size >> shift) > u16::MAX as u32 && shift < MAX_WINDOW_SHIFT {
+        shift += 1;
+    }
+    shift
+}
+
+/// The message typ of delayed ACK timers, to distinguish them from the connection timer.
+const TIMER_DELAYED_ACK: u8 = 1;
+
+/// The message typ signaling, that the firewall rejected an outgoing segment.
+const TIMER_REJECTED: u8 = 2;
+
+/// Schedules the abort of a connection, if the firewall rejected a segment.
+/// The abort is deferred, since segments are send at any point of the state machine.
+fn check_rejected(fd: Fd, result: Result<()>) {
+    if result.is_err_and(|e| e.kind() == ErrorKind::PermissionDenied) {
+        schedule_in(
+            Message::new()
+                .kind(KIND_IO_TIMEOUT)
+                .typ(TIMER_REJECTED)
+                .content(fd)
+                .build(),
+            Duration::ZERO,
+        );
+    }
+}
+
+/// The number of consecutive timeouts, after which a PMTU black hole is suspected.
+const BLACK_HOLE_TIMEOUTS: u32 = 2;
+
+/// The smallest MSS used after detecting a PMTU black hole (RFC 1122).
+const BASE_MSS: u16 = 536;
+
+/// The timestamp clock used for the TSval of outgoing segments, ticking in milliseconds.
+fn ts_clock() -> u32 {
+    SimTime::now().as_millis() as u32
+}
+
 fn is_valid_dest_for(socket_addr: &SocketAddr, packet_addr: &SocketAddr) -> bool {
     if socket_addr.ip().is_unspecified() {
         return socket_addr.port() == packet_addr.port();
@@ -265,8 +406,10 @@ impl IOContext {
             return true;
         }
 
-        // ONLY SYN
-        if !tcp_pkt.flags.syn || tcp_pkt.flags.ack {
+        // ONLY SYN, or an ACK completing a handshake using SYN cookies
+        let is_syn = tcp_pkt.flags.syn && !tcp_pkt.flags.ack;
+        let is_ack = tcp_pkt.flags.ack && !tcp_pkt.flags.syn && !tcp_pkt.flags.rst;
+        if !is_syn && !is_ack {
             return true;
         }
 
@@ -281,7 +424,14 @@ impl IOContext {
             }
 
             let fd = **fd;
-            return self.tcp_handle_incoming_connection(src, dest, fd, ip_packet, tcp_pkt);
+            if is_ack {
+                return self.tcp_handle_syn_cookie_ack(src, dest, fd, ip_packet, tcp_pkt);
+            }
+            return self.tcp_handle_incoming_connection(src, dest, fd, ifid, ip_packet, tcp_pkt);
+        }
+
+        if !is_syn {
+            return true;
         }
 
         // (2) No active stream socket, maybe listen socket is possible
@@ -301,6 +451,26 @@ impl IOContext {
         self.syscall(fd, TcpSyscall::DestinationUnreachable(e))
     }
 
+    pub(crate) fn tcp_path_mtu_update(&mut self, fd: Fd, mtu: u16) {
+        let Some(mut ctrl) = self.tcp.streams.remove(&fd) else {
+            return;
+        };
+
+        let mss = ctrl.mss_for_mtu(mtu);
+        if mss < ctrl.mss {
+            tracing::debug!("path mtu lowered to {mtu}, reducing mss {} -> {mss}", ctrl.mss);
+            ctrl.mss = mss;
+
+            // The segment that triggered the ICMP error was dropped, so resend
+            // without waiting for the RTO, or reducing the congestion window
+            if ctrl.tx_last_ack_no < ctrl.tx_next_send_seq_no {
+                self.retransmit_unacked(&mut ctrl);
+            }
+        }
+
+        self.return_ctrl(fd, ctrl);
+    }
+
     pub(crate) fn tcp_timeout(&mut self, fd: Fd, msg: Message) {
         self.process_timeout(fd, msg)
     }
@@ -325,7 +495,9 @@ impl IOContext {
                 interface.add_write_interest(ctrl.fd);
             }
 
-            self.send_ip_packet(socket.interface.clone(), pkt, true);
+            let result =
+                self.send_ip_packet_with(socket.interface.clone(), pkt, true, None, socket.mark);
+            check_rejected(fd, result);
         } else {
             if !ctrl.tx_queue.is_empty() {
                 interface.add_write_interest(ctrl.fd);
@@ -340,6 +512,7 @@ impl IOContext {
         src: SocketAddr,
         dest: SocketAddr,
         fd: Fd,
+        ifid: IfId,
         ip_packet: IpPacketRef,
         tcp_pkt: TcpPacket,
     ) -> bool {
@@ -348,7 +521,48 @@ impl IOContext {
             return false;
         };
         let config = handle.config.clone();
+        handle.stats.syn_received += 1;
         if handle.backlog.load(Ordering::SeqCst) >= config.listen_backlog {
+            if !config.syn_cookies {
+                handle.stats.syn_dropped += 1;
+                return true;
+            }
+
+            // Answer without allocating any state, the connection
+            // will be restored from the ACK to this SYNACK
+            let peer_mss = tcp_pkt
+                .options
+                .iter()
+                .find_map(|v| {
+                    if let TcpOption::MaximumSegmentSize(mss) = v {
+                        Some(*mss)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(536);
+            let cookie = syn_cookie(handle.secret, src, dest, tcp_pkt.seq_no, peer_mss);
+            handle.stats.syn_cookies_sent += 1;
+
+            tracing::trace!("backlog full, sending SYNACK with cookie {}", cookie);
+
+            // Options that are not encoded in the cookie must not be negotiated
+            let syn_ack = TcpPacket {
+                src_port: dest.port(),
+                dest_port: src.port(),
+                seq_no: cookie,
+                ack_no: tcp_pkt.seq_no + 1,
+                flags: TcpFlags::new().syn(true).ack(true),
+                window: config.rx_buffer_size.min(u16::MAX as u32) as u16,
+                urgent_ptr: 0,
+                options: vec![
+                    TcpOption::MaximumSegmentSize(config.mss),
+                    TcpOption::EndOfOptionsList(),
+                ],
+                content: Vec::new(),
+            };
+            let syn_ack = ip_packet.response(syn_ack.to_vec().unwrap());
+            self.send_ip_packet(SocketIfaceBinding::Bound(ifid), syn_ack, true);
             return true;
         }
         handle.backlog.fetch_add(1, Ordering::SeqCst);
@@ -379,6 +593,13 @@ impl IOContext {
             }
         };
 
+        // Connections with fast open data can be accepted immediately
+        if let Some(ctrl) = self.tcp.streams.get_mut(&stream.0.inner.fd) {
+            if ctrl.state == TcpState::SynRcvd && ctrl.rx_state == TcpReceiverState::Established {
+                ctrl.established.take().map(|v| v.send(Ok(())));
+            }
+        }
+
         let handle = self.tcp.binds.get_mut(&fd).unwrap();
         handle.tx.try_send(Ok((stream.0, rx)));
 
@@ -396,12 +617,18 @@ impl IOContext {
         let stream_socket = self.dup_socket(fd)?;
         self.bind_peer(stream_socket, src)?;
 
+        let fast_open = config.fast_open;
         let mut ctrl = TransmissionControlBlock::new(
             stream_socket,
             self.get_socket_addr(stream_socket)?,
             config,
         );
 
+        if fast_open {
+            let secret = self.tcp.binds.get(&fd).map(|h| h.fast_open_secret);
+            ctrl.fast_open_cookie = secret.map(|secret| fast_open_cookie(secret, src.ip()));
+        }
+
         {
             let mut span = ctrl.span.clone();
             let mut _g = span.entered();
@@ -427,6 +654,90 @@ impl IOContext {
         ))
     }
 
+    fn tcp_handle_syn_cookie_ack(
+        &mut self,
+        src: SocketAddr,
+        dest: SocketAddr,
+        fd: Fd,
+        ip_packet: IpPacketRef,
+        tcp_pkt: TcpPacket,
+    ) -> bool {
+        let Some(handle) = self.tcp.binds.get_mut(&fd) else {
+            tracing::error!("found tcp socket, but missing tcp listener");
+            return false;
+        };
+        if !handle.config.syn_cookies {
+            return true;
+        }
+
+        // The connection can only be restored, if it can be queued
+        if handle.backlog.load(Ordering::SeqCst) >= handle.config.listen_backlog {
+            return true;
+        }
+
+        let isn = tcp_pkt.seq_no.wrapping_sub(1);
+        let cookie = tcp_pkt.ack_no.wrapping_sub(1);
+        let Some(mss) = check_syn_cookie(handle.secret, src, dest, isn, cookie) else {
+            tracing::trace!("invalid SYN cookie {} from {}", cookie, src);
+            handle.stats.syn_cookies_rejected += 1;
+            return true;
+        };
+
+        handle.stats.syn_cookies_validated += 1;
+        handle.backlog.fetch_add(1, Ordering::SeqCst);
+
+        let mut config = handle.config.clone();
+        config.inital_seq_no = cookie;
+
+        let r =
+            self.tcp_handle_syn_cookie_ack_inner(src, fd, config, (isn, mss), ip_packet, tcp_pkt);
+
+        let handle = self.tcp.binds.get_mut(&fd).unwrap();
+        handle.tx.try_send(r);
+
+        true
+    }
+
+    fn tcp_handle_syn_cookie_ack_inner(
+        &mut self,
+        src: SocketAddr,
+        fd: Fd,
+        config: TcpSocketConfig,
+        (isn, mss): (u32, u16),
+        ip_packet: IpPacketRef,
+        tcp_pkt: TcpPacket,
+    ) -> Result<(TcpStream, oneshot::Receiver<Result<()>>)> {
+        let stream_socket = self.dup_socket(fd)?;
+        self.bind_peer(stream_socket, src)?;
+
+        let mut ctrl = TransmissionControlBlock::new(
+            stream_socket,
+            self.get_socket_addr(stream_socket)?,
+            config,
+        );
+
+        {
+            let span = ctrl.span.clone();
+            let _g = span.entered();
+
+            self.process_state_closed(&mut ctrl, TcpEvent::SysListen());
+            ctrl.restore_syn_cookie(src, isn, mss);
+        }
+
+        self.tcp.streams.insert(stream_socket, ctrl);
+        tracing::trace!("incoming connection restored from SYN cookie");
+
+        let rx = self.tcp_await_established(stream_socket)?;
+        self.process_packet(stream_socket, ip_packet, tcp_pkt);
+
+        Ok((
+            TcpStream {
+                inner: Arc::new(TcpStreamInner { fd: stream_socket }),
+            },
+            rx,
+        ))
+    }
+
     fn tcp_send_packet(&mut self, ctrl: &mut TransmissionControlBlock, ip: IpPacket) {
         if ctrl.tx_queue.len() > 32 {
             tracing::error!("clearing output queue");
@@ -438,11 +749,14 @@ impl IOContext {
         let Some(interface) = self.ifaces.get_mut(&socket.interface.unwrap_ifid()) else { return };
 
         if !interface.is_busy() {
-            self.send_ip_packet(
+            let result = self.send_ip_packet_with(
                 socket.interface.clone(),
                 ctrl.tx_queue.pop_front().unwrap(),
                 true,
+                None,
+                socket.mark,
             );
+            check_rejected(ctrl.fd, result);
         } else {
             interface.add_write_interest(ctrl.fd);
         }
@@ -462,6 +776,21 @@ impl IOContext {
         // assert_eq!(ip.dest(), ctrl.local_addr.ip());
         assert_eq!(pkt.dest_port, ctrl.local_addr.port());
 
+        // Any segment proves that the peer is still alive
+        ctrl.last_recv = SimTime::now();
+        ctrl.keepalive_probes = 0;
+
+        // Congestion experienced is echoed, until the peer reduced its window
+        if ctrl.ecn && !pkt.flags.syn {
+            if pkt.flags.cwr {
+                ctrl.ecn_echo = false;
+            }
+            if ip.ecn() == ECN_CE {
+                tracing::trace!("received CE mark, echoing ECE");
+                ctrl.ecn_echo = true;
+            }
+        }
+
         // Missing PERM
         let event = if pkt.flags.rst {
             TcpEvent::Rst((ip.src(), ip.dest(), pkt))
@@ -504,6 +833,27 @@ impl IOContext {
         let span = ctrl.span.clone();
         let _g = span.entered();
 
+        if msg.header().typ == TIMER_DELAYED_ACK {
+            if msg.header().id == ctrl.rx_ack_timer && ctrl.rx_ack_pending > 0 {
+                tracing::trace!("delayed ACK timer expired");
+                let (ack_no, window) = (ctrl.rx_last_recv_seq_no, ctrl.recv_window());
+                self.send_ack(&mut ctrl, ack_no + 1, window);
+            }
+            self.return_ctrl(fd, ctrl);
+            return;
+        }
+
+        if msg.header().typ == TIMER_REJECTED {
+            if ctrl.state != TcpState::Closed {
+                tracing::trace!("aborting due to segment rejected by firewall");
+                let e = || Error::new(ErrorKind::PermissionDenied, "packet rejected by firewall");
+                ctrl.established.take().map(|v| v.send(Err(e())));
+                ctrl.abort(e());
+            }
+            self.return_ctrl(fd, ctrl);
+            return;
+        }
+
         // TODO: this extra if should not be nessecary
         // if ctrl.state != TcpState::TimeWait {
         if msg.header().id != ctrl.timer {
@@ -541,10 +891,25 @@ impl IOContext {
         let event = match syscall {
             TcpSyscall::Listen() => TcpEvent::SysListen(),
             TcpSyscall::Open(peer) => TcpEvent::SysOpen(peer),
+            TcpSyscall::Close() if ctrl.linger == Some(Duration::ZERO) => {
+                // SO_LINGER with a zero timeout, discards all data (BSD semantics)
+                ctrl.dropped = true;
+                self.abort_connection(&mut ctrl);
+                self.return_ctrl(fd, ctrl);
+                return;
+            }
             TcpSyscall::Close() => {
                 ctrl.dropped = true;
                 TcpEvent::SysClose()
             }
+            TcpSyscall::Abort() => {
+                ctrl.dropped = true;
+                self.abort_connection(&mut ctrl);
+                self.return_ctrl(fd, ctrl);
+                return;
+            }
+            // A half-close, the socket remains readable
+            TcpSyscall::Shutdown() => TcpEvent::SysClose(),
 
             TcpSyscall::DestinationUnreachable(e) => {
                 ctrl.dropped = true;
@@ -619,9 +984,25 @@ impl IOContext {
 
                 let mut pkt = ctrl.create_packet(TcpPacketId::Syn, ctrl.tx_next_send_seq_no, 0);
                 pkt.options = ctrl.syn_options();
+                pkt.flags = pkt.flags.ece(ctrl.ecn).cwr(ctrl.ecn);
                 ctrl.tx_next_send_seq_no += 1;
 
-                pkt.window = ctrl.recv_window();
+                pkt.window = ctrl.syn_window();
+
+                // TCP Fast Open: Send data with the SYN if a cookie is known,
+                // or request a cookie for the next connection otherwise
+                if let Some(ref data) = ctrl.fast_open_data {
+                    let cookie = self.tcp.fast_open_cookies.get(&peer.ip()).cloned();
+                    if let Some(ref cookie) = cookie {
+                        let n = data.len().min(ctrl.mss as usize);
+                        pkt.content = data[..n].to_vec();
+                        ctrl.sender_send_bytes += n as u64;
+                    }
+
+                    let eol = pkt.options.len() - 1;
+                    pkt.options
+                        .insert(eol, TcpOption::FastOpenCookie(cookie.unwrap_or_default()));
+                }
 
                 tracing::trace!("Sending SYN {{ seq_no: {} }}", pkt.seq_no);
                 ctrl.rtt_probe = SimTime::now();
@@ -633,6 +1014,12 @@ impl IOContext {
                 // syscall reply
             }
             TcpEvent::SysClose() => {}
+            // Segments still in flight, once a connection was aborted
+            TcpEvent::Syn(_)
+            | TcpEvent::Ack(_)
+            | TcpEvent::Data(_)
+            | TcpEvent::Fin(_)
+            | TcpEvent::Rst(_) => {}
             _ => unimplemented!(),
         }
     }
@@ -659,13 +1046,42 @@ impl IOContext {
                 ctrl.tx_max_send_seq_no = ctrl.tx_next_send_seq_no + syn.window as u32;
                 ctrl.apply_syn_options(&syn.options);
 
+                // An ECN-setup SYN has both ECE and CWR set (RFC 3168)
+                ctrl.ecn = ctrl.ecn && syn.flags.ece && syn.flags.cwr;
+
+                // TCP Fast Open: Data is accepted with a valid cookie, otherwise
+                // the peer is provided with a cookie for future connections
+                let mut fast_open_cookie = None;
+                if let Some(valid) = ctrl.fast_open_cookie.take() {
+                    match syn.options.iter().find_map(|v| match v {
+                        TcpOption::FastOpenCookie(cookie) => Some(cookie),
+                        _ => None,
+                    }) {
+                        Some(cookie) if *cookie == valid => {
+                            let n = ctrl.rx_buffer.append(&syn.content);
+                            ctrl.rx_last_recv_seq_no += n as u32;
+                            tracing::trace!("accepted {} bytes of fast open data", n);
+
+                            // The connection is usable before the handshake completes
+                            ctrl.tx_state = TcpSenderState::Established;
+                            ctrl.rx_state = TcpReceiverState::Established;
+                        }
+                        Some(_) => fast_open_cookie = Some(TcpOption::FastOpenCookie(valid)),
+                        None => {}
+                    }
+                }
+
                 let mut pkt = ctrl.create_packet(
                     TcpPacketId::Syn,
                     ctrl.tx_next_send_seq_no,
                     ctrl.rx_last_recv_seq_no + 1,
                 );
                 pkt.options = ctrl.syn_options();
-                pkt.window = ctrl.recv_window();
+                if let Some(option) = fast_open_cookie {
+                    pkt.options.insert(pkt.options.len() - 1, option);
+                }
+                pkt.flags = pkt.flags.ece(ctrl.ecn);
+                pkt.window = ctrl.syn_window();
                 ctrl.tx_next_send_seq_no += 1;
 
                 tracing::trace!(
@@ -697,10 +1113,32 @@ impl IOContext {
                 ctrl.rx_buffer.bump(pkt.seq_no + 1);
                 ctrl.apply_syn_options(&pkt.options);
 
+                // An ECN-setup SYNACK has only ECE set (RFC 3168)
+                ctrl.ecn = ctrl.ecn && pkt.flags.ack && pkt.flags.ece && !pkt.flags.cwr;
+
                 if pkt.flags.ack {
                     ctrl.tx_last_ack_no = pkt.ack_no;
                     ctrl.tx_next_send_buffer_seq_no = ctrl.tx_next_send_seq_no;
-                    ctrl.tx_max_send_seq_no = pkt.ack_no + pkt.window as u32; //
+                    ctrl.tx_max_send_seq_no = pkt.ack_no + ctrl.peer_window(&pkt); //
+
+                    if let Some(cookie) = pkt.options.iter().find_map(|v| match v {
+                        TcpOption::FastOpenCookie(cookie) if !cookie.is_empty() => Some(cookie),
+                        _ => None,
+                    }) {
+                        tracing::trace!("received fast open cookie from {}", src);
+                        self.tcp.fast_open_cookies.insert(src, cookie.clone());
+                    }
+
+                    if let Some(data) = ctrl.fast_open_data.take() {
+                        // Data not acknowledged by the SYNACK is sent regularly
+                        let n = ctrl.tx_buffer.append(&data) as u32;
+                        ctrl.tx_next_send_buffer_seq_no += n;
+
+                        let acked = pkt.ack_no.wrapping_sub(ctrl.tx_next_send_seq_no).min(n);
+                        ctrl.tx_buffer.free(acked as usize);
+                        ctrl.tx_next_send_seq_no += acked;
+                        ctrl.sender_ack_bytes += acked as u64;
+                    }
 
                     self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
 
@@ -728,11 +1166,16 @@ impl IOContext {
                     );
                     ctrl.state = TcpState::Established;
                     ctrl.established.take().map(|v| v.send(Ok(())));
+                    ctrl.set_keepalive_timer();
+
+                    if ctrl.send_buffer_len() > 0 {
+                        self.do_sending(ctrl);
+                    }
                 } else {
                     tracing::trace!("simultaneous handshake, transition to tcp::synrecv");
 
                     self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
-                    ctrl.tx_max_send_seq_no = ctrl.tx_last_ack_no + pkt.window as u32;
+                    ctrl.tx_max_send_seq_no = ctrl.tx_last_ack_no + ctrl.peer_window(&pkt);
                     ctrl.tx_next_send_buffer_seq_no = ctrl.tx_next_send_seq_no;
                     ctrl.state = TcpState::SynRcvd;
                 }
@@ -754,7 +1197,10 @@ impl IOContext {
                     return;
                 }
 
-                let pkt = ctrl.create_packet(TcpPacketId::Syn, ctrl.tx_next_send_seq_no - 1, 0);
+                let mut pkt = ctrl.create_packet(TcpPacketId::Syn, ctrl.tx_next_send_seq_no - 1, 0);
+                pkt.options = ctrl.syn_options();
+                pkt.flags = pkt.flags.ece(ctrl.ecn).cwr(ctrl.ecn);
+                pkt.window = ctrl.syn_window();
                 tracing::trace!("retransmitting SYN {{ seq_no: {} }}", pkt.seq_no);
                 self.tcp_send_packet(ctrl, ctrl.ip_packet_for(pkt));
                 ctrl.set_timer(ctrl.timeout);
@@ -794,19 +1240,23 @@ impl IOContext {
             TcpEvent::Syn(_) => (),
             TcpEvent::Fin(_) => (),
             TcpEvent::Data((src, dest, pkt)) | TcpEvent::Ack((src, dest, pkt)) => {
-                // Own addition
-                ctrl.tx_last_ack_no = pkt.ack_no;
+                // Own addition, data send before the handshake completed
+                // is acknowledged when handling the data
+                ctrl.tx_last_ack_no = ctrl.inital_seq_no + 1;
 
-                if ctrl.tx_last_ack_no + pkt.window as u32 - 1 > ctrl.tx_max_send_seq_no {
-                    ctrl.tx_max_send_seq_no = pkt.ack_no + pkt.window as u32;
+                if ctrl.tx_last_ack_no + ctrl.peer_window(&pkt) - 1 > ctrl.tx_max_send_seq_no {
+                    ctrl.tx_max_send_seq_no = pkt.ack_no + ctrl.peer_window(&pkt);
                 }
 
                 ctrl.cancel_timer();
 
-                let rtt = SimTime::now() - ctrl.rtt_probe;
-                ctrl.timeout = Duration::from_secs_f64(rtt.as_secs_f64() * 4.0);
-                ctrl.add_inital_rtt_sample(rtt.as_secs_f64());
-                ctrl.rtt_probe = SimTime::MAX;
+                // Connections restored from a SYN cookie have no RTT probe
+                if ctrl.rtt_probe != SimTime::MAX {
+                    let rtt = SimTime::now() - ctrl.rtt_probe;
+                    ctrl.timeout = Duration::from_secs_f64(rtt.as_secs_f64() * 4.0);
+                    ctrl.add_inital_rtt_sample(rtt.as_secs_f64());
+                    ctrl.rtt_probe = SimTime::MAX;
+                }
 
                 // syscall estab ind
                 ctrl.tx_state = TcpSenderState::Established;
@@ -826,6 +1276,7 @@ impl IOContext {
 
                 ctrl.state = TcpState::Established;
                 ctrl.established.take().map(|v| v.send(Ok(())));
+                ctrl.set_keepalive_timer();
 
                 self.handle_data(ctrl, src, dest, pkt)
             }
@@ -835,7 +1286,8 @@ impl IOContext {
                     ctrl.tx_next_send_seq_no - 1,
                     ctrl.rx_last_recv_seq_no + 1,
                 );
-                pkt.window = ctrl.recv_window();
+                pkt.flags = pkt.flags.ece(ctrl.ecn);
+                pkt.window = ctrl.syn_window();
 
                 ctrl.syn_resend_counter += 1;
                 if ctrl.syn_resend_counter >= 3 {
@@ -884,6 +1336,7 @@ impl IOContext {
     #[instrument(level = "trace", name = "tcp_established", skip_all)]
     fn process_state_established(&mut self, ctrl: &mut TransmissionControlBlock, event: TcpEvent) {
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::SysClose() => {
                 // Handle dropped
                 // Active close - consider self client
@@ -891,6 +1344,10 @@ impl IOContext {
                 // -> self must still ack the data send by the server, but no more windows
                 // -> self may posses data in the send buffer that must still be send.
                 tracing::trace!("declaring local closing intention");
+                if ctrl.tx_state != TcpSenderState::Established {
+                    // allready shut down
+                    return;
+                }
 
                 // (0) Declere closing intention
                 ctrl.tx_state = TcpSenderState::WaitForStream;
@@ -937,9 +1394,7 @@ impl IOContext {
             | TcpEvent::Perm((src, dest, pkt)) => {
                 self.handle_data(ctrl, src, dest, pkt);
             }
-            TcpEvent::Timeout() => {
-                self.handle_data_timeout(ctrl);
-            }
+            TcpEvent::Timeout() => self.handle_timeout(ctrl),
             TcpEvent::SysSend() | TcpEvent::SysRecv() => todo!(),
 
             // Own addition
@@ -957,6 +1412,7 @@ impl IOContext {
     fn process_state_fin_wait1(&mut self, ctrl: &mut TransmissionControlBlock, event: TcpEvent) {
         // Consider self client
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::Fin((src, dest, pkt)) => {
                 // Got FIN from server before ACK of FIN
                 // Simultaneous Close
@@ -975,10 +1431,14 @@ impl IOContext {
 
                 // (2) Wait for peer FIN acknowledge
                 ctrl.state = TcpState::Closing;
+
+                // (3) No more data will arrive, so wake readers to fail with 0
+                ctrl.rx_state = TcpReceiverState::Closed;
+                ctrl.rx_read_interests.drain(..).for_each(|g| g.wake());
             }
             TcpEvent::Timeout() => {
                 // self is client - so data may need to be send before close.
-                self.handle_data_timeout(ctrl)
+                self.handle_timeout(ctrl)
             }
             TcpEvent::Data((src, dest, pkt))
             | TcpEvent::Ack((src, dest, pkt))
@@ -992,16 +1452,19 @@ impl IOContext {
 
                 // (0) Check for ACK of FIN (seq_no = nss + 1)
                 let ack_of_fin = pkt.flags.ack && pkt.ack_no == ctrl.tx_next_send_seq_no;
+
+                // (1) The peer may still send data after a half-close
+                self.handle_data(ctrl, src, dest, pkt);
+
                 if ack_of_fin {
-                    // (1) Switch to finwait2 to prevent simultaneous close
+                    // (2) Switch to finwait2 to prevent simultaneous close
                     // -> Since ACK of FIN was send before FIN peer must be in estab
                     // thus now close_wait
-                    tracing::trace!("got ACK of FIN #{}", pkt.ack_no);
+                    tracing::trace!("got ACK of FIN #{}", ctrl.tx_next_send_seq_no);
                     ctrl.state = TcpState::FinWait2;
-                } else {
-                    self.handle_data(ctrl, src, dest, pkt);
                 }
             }
+            TcpEvent::SysClose() => {}
             TcpEvent::SysRecv() => {
                 unimplemented!()
             }
@@ -1014,10 +1477,18 @@ impl IOContext {
         // consider self client
         // consider non-simultaneous close
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::Fin((src, dest, pkt)) => {
                 // Active close
                 // Wait for FIN indicating that server has decided to close.
 
+                if ctrl.rx_last_recv_seq_no + 1 != pkt.seq_no {
+                    // Data before the FIN is missing, the peer will retransmit the FIN
+                    tracing::warn!("got FIN #{}, but data is missing", pkt.seq_no);
+                    self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+                    return;
+                }
+
                 // (0) Handle last ACK of FINACK
                 tracing::trace!("got FIN #{}, going to time-wait", pkt.seq_no);
                 ctrl.rx_last_recv_seq_no = pkt.seq_no;
@@ -1028,14 +1499,19 @@ impl IOContext {
 
                 // (2) Switch to Time-Wait to handle timeouts for final ACKs
                 ctrl.state = TcpState::TimeWait;
+
+                // (3) No more data will arrive, so wake readers to fail with 0
+                ctrl.rx_state = TcpReceiverState::Closed;
+                ctrl.rx_read_interests.drain(..).for_each(|g| g.wake());
             }
-            TcpEvent::Timeout() => self.handle_data_timeout(ctrl),
+            TcpEvent::Timeout() => self.handle_timeout(ctrl),
             TcpEvent::Data((src, dest, pkt))
             | TcpEvent::Ack((src, dest, pkt))
             | TcpEvent::Perm((src, dest, pkt)) => {
                 // Since server has not yet been closed, data may be send
                 self.handle_data(ctrl, src, dest, pkt)
             }
+            TcpEvent::SysClose() => {}
             TcpEvent::SysRecv() => unimplemented!(),
             _ => unimplemented!(),
         }
@@ -1046,6 +1522,7 @@ impl IOContext {
         // consider self client or server (both client believe)
         // both parties send FIN, expect ACK
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::Timeout() => {
                 // Both parties closed -> no data
                 // -> but ack resend may be nessecary
@@ -1063,6 +1540,7 @@ impl IOContext {
                     ctrl.state = TcpState::TimeWait;
                 }
             }
+            TcpEvent::SysClose() => {}
             TcpEvent::SysRecv() => unimplemented!(),
             TcpEvent::SysOpen(_) | TcpEvent::SysListen() => unimplemented!(),
             _ => unimplemented!(),
@@ -1074,6 +1552,7 @@ impl IOContext {
         // consider self client or at least client believe
         // assume either LAST ACK or LAST FIN was allready send
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::Timeout() => {
                 // After waiting for errors ensure close the socket.
                 ctrl.reset_connection_pars();
@@ -1097,26 +1576,27 @@ impl IOContext {
         // consider self server
         // client will no longer receive data, but may still send
         match event {
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
             TcpEvent::Timeout() => {
                 // ACK resend must be handled to get to ACK of FIN
-                self.handle_data_timeout(ctrl)
+                self.handle_timeout(ctrl)
             }
             TcpEvent::SysClose() => {
                 // Once the application agrees to close
-                // send ACK of FIN
-
-                // (0) Send own FIN
-                let pkt = ctrl.create_packet(
-                    TcpPacketId::Fin,
-                    ctrl.tx_next_send_seq_no,
-                    ctrl.rx_last_recv_seq_no,
-                );
-                self.tcp_send_packet(ctrl, ctrl.ip_packet_for(pkt));
+                // send own FIN, after the remaining data was send
+                if ctrl.tx_state != TcpSenderState::Established {
+                    return;
+                }
 
-                // (2) Wait for ACK
-                // -> client will only send ACK as response to FIN
-                ctrl.set_data_timer();
-                ctrl.state = TcpState::LastAck;
+                // (0) Declare closing intention, the FIN will switch to LastAck
+                ctrl.tx_state = TcpSenderState::WaitForStream;
+                self.do_sending(ctrl)
+            }
+            TcpEvent::Ack((src, dest, pkt))
+            | TcpEvent::Data((src, dest, pkt))
+            | TcpEvent::Perm((src, dest, pkt)) => {
+                // The peer only closed its sending half, so it still acknowledges data
+                self.handle_data(ctrl, src, dest, pkt);
             }
             TcpEvent::Fin(_) => {
                 // DO nothgin
@@ -1129,12 +1609,21 @@ impl IOContext {
     fn process_state_last_ack(&mut self, ctrl: &mut TransmissionControlBlock, event: TcpEvent) {
         // consider self server
         match event {
-            TcpEvent::Ack(_) => {
-                // (0) Each last ack will be only for FIN (else simultaneous close)
+            TcpEvent::Rst((_, _, pkt)) => self.handle_rst(ctrl, pkt),
+            TcpEvent::Ack((src, dest, pkt)) => {
+                // (0) Data send after a half-close may still be unacknowledged
+                if pkt.ack_no != ctrl.tx_next_send_seq_no {
+                    self.handle_data(ctrl, src, dest, pkt);
+                    return;
+                }
+
+                // (1) ACK of FIN
+                ctrl.cancel_timer();
                 ctrl.reset_connection_pars();
                 ctrl.state = TcpState::Closed;
                 tracing::trace!("Closed");
             }
+            TcpEvent::SysClose() => {}
             TcpEvent::Fin(fin) => {
                 // we are the server:
                 // the ACKofFIN we send was lost, thus resent it
@@ -1142,15 +1631,9 @@ impl IOContext {
             }
             TcpEvent::Timeout() => {
                 // we are the server:
-                // our FIN was not yet acked, thus it was lost
+                // our FIN (or data before it) was not yet acked, thus it was lost
                 // resend it
-                let pkt = ctrl.create_packet(
-                    TcpPacketId::Fin,
-                    ctrl.tx_next_send_seq_no,
-                    ctrl.rx_last_recv_seq_no,
-                );
-                self.tcp_send_packet(ctrl, ctrl.ip_packet_for(pkt));
-                ctrl.set_data_timer();
+                self.handle_timeout(ctrl);
             }
             _ => todo!("unknonw event: {:?}", event),
         }
@@ -1173,8 +1656,45 @@ impl IOContext {
         //     pkt.content.len()
         // );
 
+        // (0) Timestamps: PAWS and TS.Recent (RFC 7323)
+        let timestamp = ctrl.ts.then(|| pkt.timestamp()).flatten();
+        if let Some((ts_val, _)) = timestamp {
+            if (ts_val.wrapping_sub(ctrl.ts_recent) as i32) < 0 {
+                tracing::warn!(
+                    "dropping segment seq_no: {} with old timestamp {} (recent {})",
+                    pkt.seq_no,
+                    ts_val,
+                    ctrl.ts_recent
+                );
+                if !pkt.content.is_empty() {
+                    self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+                }
+                return;
+            }
+
+            if pkt.seq_no <= ctrl.rx_last_recv_seq_no + 1 {
+                ctrl.ts_recent = ts_val;
+            }
+        }
+
         // (A) Handle acknowledgement information
         if pkt.flags.ack {
+            if ctrl.sack {
+                ctrl.apply_sack_options(&pkt.options);
+            }
+
+            // An ECN echo is handled like a loss, but at most once per window (RFC 3168)
+            if ctrl.ecn && pkt.flags.ece && pkt.ack_no > ctrl.ecn_recover_seq_no {
+                tracing::trace!("received ECE, reducing congestion window");
+                if ctrl.congestion_ctrl {
+                    let state = ctrl.congestion_state();
+                    ctrl.congestion.on_ecn(&state);
+                }
+                ctrl.ecn_cwr = true;
+                ctrl.ecn_recover_seq_no = ctrl.tx_next_send_seq_no;
+                ctrl.sender_ecn_reductions += 1;
+            }
+
             // let buf_full = self.send_queue == self.send_buffer.size();
             let buf_full = ctrl.tx_buffer.rem() == 0;
 
@@ -1187,13 +1707,22 @@ impl IOContext {
                 let n = pkt.ack_no - ctrl.tx_last_ack_no;
                 ctrl.tx_buffer.free(n as usize);
 
+                // The FIN consumes a sequence number, but is no data
+                let fin = ctrl.tx_next_send_buffer_seq_no;
+                ctrl.sender_ack_bytes +=
+                    (pkt.ack_no.min(fin) - ctrl.tx_last_ack_no.min(fin)) as u64;
+
                 tracing::trace!(
                     "freeing acked data: {} bytes starting at {}",
                     n,
                     ctrl.tx_last_ack_no
                 );
 
-                if pkt.ack_no == ctrl.rtt_probe_seq_no {
+                if let Some((_, ts_ecr)) = timestamp {
+                    // Each ACK of new data echos the timestamp of the segment it was triggered by
+                    let rtt = ts_clock().wrapping_sub(ts_ecr);
+                    ctrl.add_rtt_sample(rtt as f64 / 1000.0);
+                } else if pkt.ack_no == ctrl.rtt_probe_seq_no {
                     let dur = SimTime::now() - ctrl.rtt_probe;
                     ctrl.add_rtt_sample(dur.as_secs_f64());
 
@@ -1207,35 +1736,35 @@ impl IOContext {
 
                 // freeBuffers
                 ctrl.tx_last_ack_no = pkt.ack_no;
+                ctrl.tx_timeouts = 0;
+                ctrl.tx_sack_scoreboard.retain_mut(|(left, right)| {
+                    *left = (*left).max(pkt.ack_no);
+                    *right > pkt.ack_no
+                });
 
                 if ctrl.tx_last_ack_no < ctrl.tx_next_send_seq_no {
                     ctrl.set_data_timer()
                 } else {
                     // opti
                     ctrl.tx_next_send_seq_no = ctrl.tx_last_ack_no;
+                    if ctrl.state == TcpState::Established {
+                        ctrl.set_keepalive_timer();
+                    }
                 }
 
                 if ctrl.congestion_ctrl {
-                    if ctrl.congestion_window < ctrl.ssthresh {
-                        // Slow start
-                        ctrl.congestion_window += ctrl.mss as u32;
-                        ctrl.congestion_avoid_counter = ctrl.congestion_window;
+                    let state = ctrl.congestion_state();
+                    ctrl.congestion.on_ack(&state, n);
 
-                        // ctrl.debug_cong_window
-                        //     .collect(ctrl.congestion_window as f64);
-                    } else {
-                        // AIMD
-                        ctrl.congestion_avoid_counter =
-                            ctrl.congestion_avoid_counter.saturating_sub(n as u32);
-                        if ctrl.congestion_avoid_counter == 0 {
-                            ctrl.congestion_window += ctrl.mss as u32;
-                            // FIXME: custom addition may be a bad idea but we will see.
-                            ctrl.congestion_window = ctrl.congestion_window.min(ctrl.send_window());
-                            ctrl.congestion_avoid_counter = ctrl.congestion_window;
-                        }
-
-                        // ctrl.debug_cong_window
-                        //     .collect(ctrl.congestion_window as f64);
+                    // ctrl.debug_cong_window
+                    //     .collect(ctrl.congestion.cwnd() as f64);
+
+                    // A partial acknowledgement during fast recovery indicates
+                    // that the next segment was lost as well (RFC 6582)
+                    if ctrl.congestion.in_recovery()
+                        && ctrl.tx_last_ack_no < ctrl.tx_next_send_seq_no
+                    {
+                        self.fast_retransmit(ctrl);
                     }
                 }
 
@@ -1250,29 +1779,54 @@ impl IOContext {
                 // - either multiple acks were send, bc missing data segemnt
                 // - or multiple ACKs indicate other changes, like window updats
 
-                let is_win_update = ctrl.tx_max_send_seq_no != pkt.ack_no + pkt.window as u32;
+                let is_win_update = ctrl.tx_max_send_seq_no != pkt.ack_no + ctrl.peer_window(&pkt);
 
                 if pkt.ack_no == ctrl.tx_last_ack_no
                     && pkt.ack_no < ctrl.tx_next_send_seq_no
+                    && pkt.content.is_empty()
                     && !is_win_update
                 {
-                    if ctrl.tx_dup_ack_counter == 2 {
-                        tracing::error!("received duplicated ack, resetting to {}", pkt.ack_no);
-                        // resent this packet specificly
-                        ctrl.congestion_window += ctrl.congestion_window / 2;
-                        self.handle_data_timeout(ctrl);
-                        ctrl.tx_dup_ack_counter = 0;
-                    } else {
-                        ctrl.tx_dup_ack_counter += 1;
+                    ctrl.tx_dup_ack_counter += 1;
+                    if ctrl.congestion_ctrl {
+                        let state = ctrl.congestion_state();
+                        ctrl.congestion.on_dup_ack(&state, ctrl.tx_dup_ack_counter);
+                    }
+
+                    // Fast retransmit on the third duplicate ACK (RFC 5681), further
+                    // duplicate ACKs inflate the congestion window, so that new data
+                    // can be send during fast recovery.
+                    if ctrl.tx_dup_ack_counter == 3 {
+                        tracing::warn!(
+                            "received 3 duplicate acks, fast retransmit of {}",
+                            pkt.ack_no
+                        );
+                        if ctrl.congestion_ctrl {
+                            let state = ctrl.congestion_state();
+                            ctrl.congestion.on_loss(&state);
+                        }
+
+                        self.fast_retransmit(ctrl);
+                    } else if ctrl.tx_dup_ack_counter > 3
+                        && ctrl.sack
+                        && !ctrl.tx_sack_scoreboard.is_empty()
+                    {
+                        // Each further duplicate ACK may free room in the pipe
+                        self.retransmit_sack_holes(ctrl, false);
                     }
                 }
             }
 
-            ctrl.tx_max_send_seq_no = pkt.ack_no + pkt.window as u32;
+            ctrl.tx_max_send_seq_no = pkt.ack_no + ctrl.peer_window(&pkt);
             self.do_sending(ctrl);
         }
 
-        // (B) Handle data part
+        // (B) Keepalive probes reuse the last acknowledged seq_no, and must be answered
+        if pkt.content.is_empty() && pkt.seq_no == ctrl.rx_last_recv_seq_no && !pkt.flags.syn {
+            tracing::trace!("responding to keepalive probe");
+            self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+        }
+
+        // (C) Handle data part
         if !pkt.content.is_empty() {
             if pkt.seq_no != ctrl.rx_last_recv_seq_no + 1 {
                 tracing::warn!(
@@ -1281,11 +1835,20 @@ impl IOContext {
                     pkt.content.len(),
                     ctrl.rx_last_recv_seq_no + 1,
                 );
+
+                // With SACK, future segments are buffered and reported
+                // to the sender, so that only the gaps must be retransmitted.
+                // Segments starting outside the receive window are dropped.
+                let rcv_nxt = ctrl.rx_last_recv_seq_no + 1;
+                let offset = pkt.seq_no.wrapping_sub(rcv_nxt);
+                if ctrl.sack && offset != 0 && offset < ctrl.recv_window() {
+                    ctrl.rx_buffer.write(&pkt.content, pkt.seq_no);
+                    ctrl.rx_last_out_of_order_seq_no = pkt.seq_no;
+                }
+
                 // if ctrl.rx_dup_ack_counter < 1 {
                 self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
                 // }
-
-                // FIXME: if NACK is implemented, dont do that
                 return;
             }
 
@@ -1301,7 +1864,11 @@ impl IOContext {
 
             // (1) Insert the packet into the receiver_buffer
             let n = ctrl.rx_buffer.write(&pkt.content, pkt.seq_no);
-            ctrl.rx_last_recv_seq_no = pkt.seq_no + pkt.content.len() as u32 - 1;
+
+            // The packet may have closed a gap to allready buffered out of order segments
+            ctrl.rx_last_recv_seq_no = (pkt.seq_no + pkt.content.len() as u32 - 1).max(
+                ctrl.rx_buffer.read_head() + ctrl.rx_buffer.len_continous() as u32 - 1,
+            );
 
             // TODO:
             assert_eq!(
@@ -1317,8 +1884,8 @@ impl IOContext {
                 ctrl.rx_read_interests.drain(..).for_each(|g| g.wake());
             }
 
-            // (3) Acknowledge the data that was send
-            self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+            // (3) Acknowledge the data that was send, possibly delayed
+            self.ack_data(ctrl, next > prev + n);
 
             // ctrl.receiver_buffer.state();
 
@@ -1352,7 +1919,7 @@ impl IOContext {
 
         let max_seq_no = if ctrl.congestion_ctrl {
             ctrl.tx_max_send_seq_no
-                .min(ctrl.tx_last_ack_no + ctrl.congestion_window)
+                .min(ctrl.tx_last_ack_no + ctrl.congestion.cwnd())
         } else {
             ctrl.tx_max_send_seq_no
         };
@@ -1364,6 +1931,39 @@ impl IOContext {
         while ctrl.tx_next_send_seq_no < max_seq_no
             && ctrl.tx_next_send_seq_no < ctrl.tx_next_send_buffer_seq_no
         {
+            // Segments selectivly acked by the peer must not be send again
+            let mut size = (max_seq_no - ctrl.tx_next_send_seq_no) as usize;
+            if ctrl.sack {
+                let seq_no = ctrl.tx_next_send_seq_no;
+                if let Some(&(_, right)) = ctrl
+                    .tx_sack_scoreboard
+                    .iter()
+                    .find(|(left, right)| *left <= seq_no && seq_no < *right)
+                {
+                    ctrl.tx_next_send_seq_no = right;
+                    continue;
+                }
+                if let Some(&(left, _)) = ctrl
+                    .tx_sack_scoreboard
+                    .iter()
+                    .find(|(left, _)| seq_no < *left)
+                {
+                    size = size.min((left - seq_no) as usize);
+                }
+            }
+
+            // Nagle's algorithm (RFC 896): while data is unacknowledged, small
+            // segments are held back, until a full segment can be send.
+            let avail = size.min(ctrl.send_buffer_len() as usize);
+            if !ctrl.nodelay
+                && avail < ctrl.mss as usize
+                && ctrl.tx_last_ack_no < ctrl.tx_next_send_seq_no
+                && ctrl.tx_state != TcpSenderState::WaitForStream
+            {
+                tracing::trace!("delaying {} bytes due to nagle's algorithm", avail);
+                break;
+            }
+
             // send buffer set timeout
             // reschedule timer
             // get_data_packet
@@ -1371,48 +1971,25 @@ impl IOContext {
 
             ctrl.set_data_timer();
 
-            // (0) Only send fragments within the remaining window and mtu limitiations
-            // CHECKME: change max_seq_no - next_send to max_buf
-            let size = (ctrl.mss as usize)
-                .min((max_seq_no - ctrl.tx_next_send_seq_no) as usize)
-                .min(ctrl.send_buffer_len() as usize);
-            let mut buf = vec![0u8; size];
-
-            // (1) Peek the data from the sender_buffer (n = size)
-            let n = ctrl.tx_buffer.peek_at(&mut buf, ctrl.tx_next_send_seq_no);
-            buf.truncate(n);
+            // (0) Send a segment within the remaining window and mtu limitiations
+            let n = self.send_data_segment(ctrl, ctrl.tx_next_send_seq_no, size);
 
             if ctrl.rtt_probe == SimTime::MAX {
                 // choose this packet as rtt probe
                 ctrl.rtt_probe = SimTime::now();
-                ctrl.rtt_probe_seq_no = ctrl.tx_next_send_seq_no + n as u32;
+                ctrl.rtt_probe_seq_no = ctrl.tx_next_send_seq_no + n;
             }
 
-            let is_last_sendable = ctrl.send_buffer_len() == n as u32;
-            tracing::trace!(
-                "sending {} bytes beginning at {}",
-                n,
-                ctrl.tx_next_send_seq_no,
-            );
-
-            // (2) Create a TCPData packet with the data embedded.
-            let mut tcp = TcpPacket {
-                src_port: ctrl.local_addr.port(),
-                dest_port: ctrl.peer_addr.port(),
-                seq_no: ctrl.tx_next_send_seq_no,
-                ack_no: ctrl.rx_last_recv_seq_no,
-                flags: TcpFlags::new().ack(true).psh(is_last_sendable),
-                window: ctrl.recv_window(),
-                urgent_ptr: 0,
-                options: Vec::new(),
-                content: buf,
-            };
-
-            // (3) Forward the packet to the socket output.
-            self.tcp_send_packet(ctrl, ctrl.ip_packet_for(tcp));
+            // (1) Increment the sequence number on success
+            ctrl.tx_next_send_seq_no += n;
+        }
 
-            // (4) Increment the sequence number on success
-            ctrl.tx_next_send_seq_no += n as u32;
+        // The peer closed its window, so probe it periodically, in case
+        // that the window update gets lost (RFC 1122)
+        if ctrl.is_zero_window() && ctrl.timer_kind != TcpTimer::Persist {
+            tracing::trace!("peer advertised a zero window, starting persist timer");
+            ctrl.persist_backoff = 0;
+            ctrl.set_persist_timer();
         }
 
         if ctrl.tx_state == TcpSenderState::WaitForStream
@@ -1423,6 +2000,76 @@ impl IOContext {
         }
     }
 
+    fn send_data_segment(
+        &mut self,
+        ctrl: &mut TransmissionControlBlock,
+        seq_no: u32,
+        max_size: usize,
+    ) -> u32 {
+        // (0) Only send fragments within the remaining window and mtu limitiations
+        // CHECKME: change max_seq_no - next_send to max_buf
+        // The path MTU may grow again, once a cached PMTU expires
+        let path_mss = ctrl.mss_for_mtu(self.path_mtu(ctrl.peer_addr.ip()));
+        ctrl.mss = ctrl.negotiated_mss.min(ctrl.mss_clamp).min(path_mss);
+        let size = (ctrl.mss as usize)
+            .min(max_size)
+            .min(ctrl.tx_next_send_buffer_seq_no.wrapping_sub(seq_no) as usize);
+        let mut buf = vec![0u8; size];
+
+        // (1) Peek the data from the sender_buffer (n = size)
+        let n = ctrl.tx_buffer.peek_at(&mut buf, seq_no);
+        buf.truncate(n);
+
+        let is_last_sendable = ctrl.tx_next_send_buffer_seq_no == seq_no + n as u32;
+        tracing::trace!("sending {} bytes beginning at {}", n, seq_no);
+
+        // (2) Create a TCPData packet with the data embedded.
+        let tcp = TcpPacket {
+            src_port: ctrl.local_addr.port(),
+            dest_port: ctrl.peer_addr.port(),
+            seq_no,
+            ack_no: ctrl.rx_last_recv_seq_no + 1,
+            flags: TcpFlags::new()
+                .ack(true)
+                .psh(is_last_sendable)
+                .cwr(ctrl.ecn_cwr),
+            window: ctrl.advertised_window(ctrl.recv_window()),
+            urgent_ptr: 0,
+            options: Vec::new(),
+            content: buf,
+        };
+        ctrl.ecn_cwr = false;
+
+        // (3) Update the metrics, bytes below the highest seq_no are retransmissions
+        let retransmitted = ctrl.tx_max_sent_seq_no.saturating_sub(seq_no).min(n as u32);
+        if retransmitted > 0 {
+            ctrl.sender_retransmitted_bytes += retransmitted as u64;
+            ctrl.sender_retransmits += 1;
+        }
+        ctrl.tx_max_sent_seq_no = ctrl.tx_max_sent_seq_no.max(seq_no + n as u32);
+        ctrl.sender_send_bytes += n as u64;
+
+        // (4) Forward the packet to the socket output, any delayed ACK piggybacks
+        ctrl.rx_ack_pending = 0;
+        self.tcp_send_packet(ctrl, ctrl.ip_packet_for(tcp));
+        n as u32
+    }
+
+    pub(super) fn tcp_set_nodelay(&mut self, fd: Fd, nodelay: bool) -> Result<()> {
+        let Some(mut ctrl) = self.tcp.streams.remove(&fd) else {
+            return Err(Error::new(ErrorKind::Other, "Lost Tcp"));
+        };
+
+        // Segments held back by Nagle's algorithm must be send now
+        ctrl.nodelay = nodelay;
+        if nodelay && ctrl.state == TcpState::Established {
+            self.do_sending(&mut ctrl);
+        }
+
+        self.tcp.streams.insert(fd, ctrl);
+        Ok(())
+    }
+
     fn send_client_fin(&mut self, ctrl: &mut TransmissionControlBlock) {
         tracing::trace!("Initiating shutdown with FIN #{}", ctrl.tx_next_send_seq_no);
         let pkt = ctrl.create_packet(
@@ -1434,11 +2081,14 @@ impl IOContext {
         self.tcp_send_packet(ctrl, ctrl.ip_packet_for(pkt));
         ctrl.tx_next_send_seq_no += 1;
 
-        // (1) Switch to FinWait1 expecting ACK of FIN
+        // (1) Switch to FinWait1 / LastAck expecting ACK of FIN
         ctrl.tx_state = TcpSenderState::Closing;
-        if ctrl.state == TcpState::Established {
-            ctrl.state = TcpState::FinWait1;
+        match ctrl.state {
+            TcpState::Established => ctrl.state = TcpState::FinWait1,
+            TcpState::CloseWait => ctrl.state = TcpState::LastAck,
+            _ => {}
         }
+        ctrl.set_data_timer();
     }
 
     pub(self) fn tcp_try_write(&mut self, fd: Fd, buf: &[u8]) -> Result<usize> {
@@ -1450,8 +2100,14 @@ impl IOContext {
         let span = ctrl.span.clone();
         let _g = span.entered();
 
-        // (1) If the socket is closing, send no more data
-        if ctrl.state as u8 > TcpState::Established as u8
+        if let Some(e) = ctrl.error.take() {
+            self.tcp.streams.insert(fd, ctrl);
+            return Err(e);
+        }
+
+        // (1) If the socket is closing, send no more data. A peer that
+        // half-closed the connection can still receive data.
+        if (ctrl.state as u8 > TcpState::Established as u8 && ctrl.state != TcpState::CloseWait)
             || ctrl.tx_state != TcpSenderState::Established
         {
             self.tcp.streams.insert(fd, ctrl);
@@ -1484,6 +2140,16 @@ impl IOContext {
         let span = ctrl.span.clone();
         let _g = span.entered();
 
+        if let Some(e) = ctrl.error.take() {
+            self.tcp.streams.insert(fd, ctrl);
+            return Err(e);
+        }
+
+        if ctrl.rx_shutdown {
+            self.tcp.streams.insert(fd, ctrl);
+            return Ok(0);
+        }
+
         // (1) Check for need for window updates.
         let was_full = ctrl.rx_buffer.len() == ctrl.rx_buffer.cap();
 
@@ -1545,6 +2211,14 @@ impl IOContext {
         Ok(n)
     }
 
+    fn handle_timeout(&mut self, ctrl: &mut TransmissionControlBlock) {
+        match ctrl.timer_kind {
+            TcpTimer::Data => self.handle_data_timeout(ctrl),
+            TcpTimer::Keepalive => self.handle_keepalive_timeout(ctrl),
+            TcpTimer::Persist => self.handle_persist_timeout(ctrl),
+        }
+    }
+
     fn handle_data_timeout(&mut self, ctrl: &mut TransmissionControlBlock) {
         tracing::trace!(
             "data timeout, missing ack for {}..{}",
@@ -1552,29 +2226,97 @@ impl IOContext {
             ctrl.tx_next_send_seq_no
         );
 
-        // TODO: Handle permit packets
-        // FIXME: +1 was there but makes no sense, so i removed it, lets see what breaks
-        ctrl.tx_next_send_seq_no = ctrl.tx_last_ack_no;
-        ctrl.cancel_timer();
-        ctrl.set_data_timer();
-
         // Reset congestion control
+        if ctrl.congestion_ctrl {
+            let state = ctrl.congestion_state();
+            ctrl.congestion.on_rto(&state);
+        }
+
+        // Repeated timeouts may indicate a PMTU black hole, where large segments
+        // are dropped without an ICMP error, so fall back to a smaller MSS (RFC 4821)
+        ctrl.tx_timeouts += 1;
+        if ctrl.mtu_probing && ctrl.tx_timeouts >= BLACK_HOLE_TIMEOUTS && ctrl.mss > BASE_MSS {
+            let mss = (ctrl.mss / 2).max(BASE_MSS);
+            tracing::warn!("suspecting pmtu black hole, reducing mss {} -> {mss}", ctrl.mss);
+            ctrl.mss = mss;
+            ctrl.mss_clamp = mss;
+            ctrl.tx_timeouts = 0;
+        }
 
         // ctrl.debug_cong_window
-        //     .collect(ctrl.congestion_window as f64);
-        // ctrl.debug_ssthresh.collect(ctrl.ssthresh as f64);
+        //     .collect(ctrl.congestion.cwnd() as f64);
+        // ctrl.debug_ssthresh.collect(ctrl.congestion.ssthresh() as f64);
 
-        // // TCP THAOHE
-        // ctrl.ssthresh = ctrl.congestion_window / 2;
-        // ctrl.congestion_window = ctrl.mss as u32;
+        // The peer may have discarded selectivly acked data (RFC 6675)
+        ctrl.tx_sack_scoreboard.clear();
+        self.retransmit_unacked(ctrl);
+    }
 
-        // TCP RENO
-        ctrl.congestion_window = (ctrl.congestion_window / 2).max(ctrl.mss as u32);
-        ctrl.ssthresh = ctrl.congestion_window;
+    fn handle_keepalive_timeout(&mut self, ctrl: &mut TransmissionControlBlock) {
+        let Some(keepalive) = ctrl.keepalive else {
+            return;
+        };
 
-        // ctrl.debug_cong_window
-        //     .collect(ctrl.congestion_window as f64);
-        // ctrl.debug_ssthresh.collect(ctrl.ssthresh as f64);
+        // The peer was active since the timer was scheduled
+        if ctrl.keepalive_probes == 0 && SimTime::now() - ctrl.last_recv < keepalive.idle {
+            ctrl.set_keepalive_timer();
+            return;
+        }
+
+        if ctrl.keepalive_probes >= keepalive.probes {
+            tracing::warn!(
+                "aborting connection after {} unanswered keepalive probes",
+                ctrl.keepalive_probes
+            );
+            ctrl.abort(Error::new(
+                ErrorKind::TimedOut,
+                "connection timed out - keepalive",
+            ));
+            return;
+        }
+
+        ctrl.keepalive_probes += 1;
+        tracing::trace!("sending keepalive probe #{}", ctrl.keepalive_probes);
+        self.send_probe(ctrl);
+
+        ctrl.set_timer(keepalive.interval);
+        ctrl.timer_kind = TcpTimer::Keepalive;
+    }
+
+    fn handle_persist_timeout(&mut self, ctrl: &mut TransmissionControlBlock) {
+        // The window was reopened in the meantime
+        if !ctrl.is_zero_window() {
+            ctrl.cancel_timer();
+            self.do_sending(ctrl);
+            return;
+        }
+
+        ctrl.persist_backoff += 1;
+        tracing::trace!("sending zero window probe #{}", ctrl.persist_backoff);
+        self.send_probe(ctrl);
+
+        ctrl.set_persist_timer();
+    }
+
+    /// Sends an ACK with an allready acknowledged seq_no, which
+    /// forces the peer to respond with its current window.
+    fn send_probe(&mut self, ctrl: &mut TransmissionControlBlock) {
+        let mut pkt = ctrl.create_packet(
+            TcpPacketId::Ack,
+            ctrl.tx_next_send_seq_no - 1,
+            ctrl.rx_last_recv_seq_no + 1,
+        );
+        pkt.window = ctrl.advertised_window(ctrl.recv_window());
+        self.tcp_send_packet(ctrl, ctrl.ip_packet_for(pkt));
+    }
+
+    fn retransmit_unacked(&mut self, ctrl: &mut TransmissionControlBlock) {
+        // TODO: Handle permit packets
+        // FIXME: +1 was there but makes no sense, so i removed it, lets see what breaks
+        ctrl.tx_next_send_seq_no = ctrl.tx_last_ack_no;
+        ctrl.tx_sack_recovery_seq_no = ctrl.tx_last_ack_no;
+        ctrl.cancel_timer();
+        ctrl.set_data_timer();
 
         // Edge case: FIN send but data missing
         // reset fin state so that the no send data can be consideed
@@ -1585,15 +2327,164 @@ impl IOContext {
         self.do_sending(ctrl);
     }
 
-    fn send_ack(&mut self, ctrl: &mut TransmissionControlBlock, next_expected: u32, win: u16) {
+    fn fast_retransmit(&mut self, ctrl: &mut TransmissionControlBlock) {
+        // Resent the first unacknowledged segment, or only the missing
+        // segments if the peer provided SACK information
+        if ctrl.sack && !ctrl.tx_sack_scoreboard.is_empty() {
+            self.retransmit_sack_holes(ctrl, true);
+            return;
+        }
+
+        if ctrl.tx_last_ack_no >= ctrl.tx_next_send_buffer_seq_no {
+            // Only the FIN is unacknowledged, the data timer will handle it
+            return;
+        }
+
+        tracing::trace!("retransmitting segment at {}", ctrl.tx_last_ack_no);
+        self.send_data_segment(ctrl, ctrl.tx_last_ack_no, ctrl.mss as usize);
+
+        // Karn: no RTT samples from retransmitted segments
+        ctrl.rtt_probe = SimTime::MAX;
+        ctrl.rtt_probe_seq_no = 0;
+
+        ctrl.cancel_timer();
+        ctrl.set_data_timer();
+    }
+
+    fn retransmit_sack_holes(&mut self, ctrl: &mut TransmissionControlBlock, entering: bool) {
+        // Only retransmit the gaps between the selectivly acked ranges,
+        // skipping gaps that were allready retransmitted in this recovery.
+        // Retransmissions are limited by the congestion window, except for
+        // the first segment when entering loss recovery (RFC 6675).
+        let cwnd = if ctrl.congestion_ctrl {
+            ctrl.congestion.cwnd()
+        } else {
+            u32::MAX
+        };
+        let mut force = entering;
+
+        let mut seq_no = ctrl.tx_last_ack_no.max(ctrl.tx_sack_recovery_seq_no);
+        let scoreboard = ctrl.tx_sack_scoreboard.clone();
+        'holes: for (left, right) in scoreboard {
+            while seq_no < left {
+                if !force && ctrl.sack_pipe() + ctrl.mss as u32 > cwnd {
+                    break 'holes;
+                }
+
+                tracing::trace!("retransmitting missing segment at {}", seq_no);
+                let n = self.send_data_segment(ctrl, seq_no, (left - seq_no) as usize);
+                if n == 0 {
+                    break;
+                }
+                force = false;
+                seq_no += n;
+                ctrl.tx_sack_recovery_seq_no = seq_no;
+            }
+            seq_no = seq_no.max(right);
+            ctrl.tx_sack_recovery_seq_no = seq_no;
+        }
+
+        if !entering {
+            return;
+        }
+        ctrl.cancel_timer();
+        ctrl.set_data_timer();
+    }
+
+    fn handle_rst(&mut self, ctrl: &mut TransmissionControlBlock, pkt: TcpPacket) {
+        // Only accept resets within the receive window, to prevent
+        // old or blind resets from aborting the connection (RFC 5961)
+        let left = ctrl.rx_last_recv_seq_no.wrapping_add(1);
+        if pkt.seq_no.wrapping_sub(left) > ctrl.recv_window() {
+            tracing::warn!("ignoring RST with out of window seq_no {}", pkt.seq_no);
+            return;
+        }
+
+        tracing::warn!("connection reset by peer (RST)");
+        let closing = matches!(
+            ctrl.state,
+            TcpState::Closing | TcpState::LastAck | TcpState::TimeWait
+        );
+        ctrl.abort(Error::new(
+            ErrorKind::ConnectionReset,
+            "connection reset by peer",
+        ));
+
+        // The connection was allready closed by both sides
+        if closing {
+            ctrl.error = None;
+        }
+    }
+
+    /// Aborts the connection by sending a RST to the peer. All buffered data is discarded.
+    fn abort_connection(&mut self, ctrl: &mut TransmissionControlBlock) {
+        let synchronized = !matches!(
+            ctrl.state,
+            TcpState::Closed | TcpState::Listen | TcpState::SynSent
+        );
+        if synchronized {
+            tracing::trace!("aborting connection with RST #{}", ctrl.tx_next_send_seq_no);
+            let mut pkt = ctrl.create_packet(
+                TcpPacketId::Ack,
+                ctrl.tx_next_send_seq_no,
+                ctrl.rx_last_recv_seq_no + 1,
+            );
+            pkt.flags = pkt.flags.rst(true);
+
+            // The RST must not be queued behind discarded data
+            ctrl.tx_queue.clear();
+            if let Some(socket) = self.sockets.get(&ctrl.fd) {
+                let pkt = ctrl.ip_packet_for(pkt);
+                self.send_ip_packet_with(socket.interface.clone(), pkt, true, None, socket.mark);
+            }
+        }
+
+        ctrl.abort(Error::new(
+            ErrorKind::ConnectionAborted,
+            "connection aborted",
+        ));
+    }
+
+    fn send_ack(&mut self, ctrl: &mut TransmissionControlBlock, next_expected: u32, win: u32) {
         assert!(next_expected > 0);
         let mut ack = ctrl.create_packet(TcpPacketId::Ack, ctrl.tx_next_send_seq_no, next_expected);
         if win > 0 {
-            ack.window = win;
+            ack.window = ctrl.advertised_window(win);
+        }
+        if ctrl.sack {
+            ack.options = ctrl.sack_options();
         }
 
+        ctrl.rx_ack_pending = 0;
         self.tcp_send_packet(ctrl, ctrl.ip_packet_for(ack));
     }
+
+    /// Acknowledges received in-order data, delaying the ACK if configured (RFC 1122).
+    ///
+    /// An ACK is send for at least every second segment, or immediately
+    /// if the segment filled a gap in the sequence space.
+    fn ack_data(&mut self, ctrl: &mut TransmissionControlBlock, filled_gap: bool) {
+        let Some(ack_delay) = ctrl.ack_delay else {
+            self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+            return;
+        };
+
+        ctrl.rx_ack_pending += 1;
+        if ctrl.rx_ack_pending >= 2 || filled_gap {
+            self.send_ack(ctrl, ctrl.rx_last_recv_seq_no + 1, ctrl.recv_window());
+        } else {
+            ctrl.rx_ack_timer = ctrl.rx_ack_timer.wrapping_add(1);
+            schedule_in(
+                Message::new()
+                    .kind(KIND_IO_TIMEOUT)
+                    .typ(TIMER_DELAYED_ACK)
+                    .id(ctrl.rx_ack_timer)
+                    .content(ctrl.fd)
+                    .build(),
+                ack_delay,
+            );
+        }
+    }
 }
 
 impl TransmissionControlBlock {
@@ -1627,15 +2518,37 @@ impl TransmissionControlBlock {
         // self.debug_rto.collect(self.rto);
     }
 
-    fn ip_packet_for(&self, tcp: TcpPacket) -> IpPacket {
+    fn ip_packet_for(&self, mut tcp: TcpPacket) -> IpPacket {
+        // Only data segments are ECN capable, pure ACKs could be lost unnoticed
+        let ecn = if self.ecn && !tcp.content.is_empty() {
+            ECN_ECT0
+        } else {
+            ECN_NOT_ECT
+        };
+        if self.ecn && !tcp.flags.syn {
+            tcp.flags.ece = self.ecn_echo;
+        }
+
+        if self.ts {
+            // Every segment carries a timestamp, once negotiated
+            let option = TcpOption::Timestamp(ts_clock(), self.ts_recent);
+            match tcp.options.last() {
+                Some(TcpOption::EndOfOptionsList()) => {
+                    tcp.options.insert(tcp.options.len() - 1, option)
+                }
+                _ => tcp.options.extend([option, TcpOption::EndOfOptionsList()]),
+            }
+        }
+
         let content = tcp.to_vec().unwrap();
         match self.local_addr {
             SocketAddr::V4(local) => IpPacket::V4(Ipv4Packet {
                 dscp: 0,
-                enc: 0,
+                enc: ecn,
                 identification: 0,
+                // Segments are never fragmented, to allow path MTU discovery
                 flags: Ipv4Flags {
-                    df: false,
+                    df: true,
                     mf: false,
                 },
                 fragment_offset: 0,
@@ -1652,7 +2565,7 @@ impl TransmissionControlBlock {
                 content,
             }),
             SocketAddr::V6(local) => IpPacket::V6(Ipv6Packet {
-                traffic_class: 0,
+                traffic_class: ecn,
                 flow_label: 0,
                 hop_limit: 64,
                 next_header: PROTO_TCP,
@@ -1688,10 +2601,88 @@ impl TransmissionControlBlock {
     }
 
     fn syn_options(&self) -> Vec<TcpOption> {
-        vec![
-            TcpOption::MaximumSegmentSize(self.mss),
-            TcpOption::EndOfOptionsList(),
-        ]
+        let mut options = vec![TcpOption::MaximumSegmentSize(self.mss)];
+        if self.sack {
+            options.push(TcpOption::SackPermitted());
+        }
+        // The option is send even without scaling our own window, to allow
+        // the peer to scale its window (RFC 7323)
+        if self.window_scaling {
+            options.push(TcpOption::WindowScaling(self.rx_window_shift));
+        }
+        options.push(TcpOption::EndOfOptionsList());
+        options
+    }
+
+    fn sack_options(&self) -> Vec<TcpOption> {
+        // Leave room for other options in the 40 byte option space
+        const MAX_SACK_BLOCKS: usize = 3;
+
+        let mut blocks = self.rx_buffer.out_of_order_ranges().collect::<Vec<_>>();
+        if blocks.is_empty() {
+            return Vec::new();
+        }
+
+        // The block containing the most recent segment must be reported first (RFC 2018)
+        let recent = self.rx_last_out_of_order_seq_no;
+        if let Some(i) = blocks
+            .iter()
+            .position(|(left, right)| *left <= recent && recent < *right)
+        {
+            let block = blocks.remove(i);
+            blocks.insert(0, block);
+        }
+        blocks.truncate(MAX_SACK_BLOCKS);
+
+        vec![TcpOption::Sack(blocks), TcpOption::EndOfOptionsList()]
+    }
+
+    /// Estimates the number of bytes in flight during SACK loss recovery
+    /// (RFC 6675). Holes below the highest selectivly acked byte are
+    /// considered lost, unless they were allready retransmitted.
+    fn sack_pipe(&self) -> u32 {
+        let highest = self
+            .tx_sack_scoreboard
+            .last()
+            .map_or(self.tx_last_ack_no, |(_, right)| *right);
+        let mut pipe = self.tx_next_send_seq_no.saturating_sub(highest);
+
+        let mut seq_no = self.tx_last_ack_no;
+        for &(left, right) in &self.tx_sack_scoreboard {
+            pipe += self.tx_sack_recovery_seq_no.clamp(seq_no, left) - seq_no;
+            seq_no = right;
+        }
+        pipe
+    }
+
+    fn apply_sack_options(&mut self, options: &[TcpOption]) {
+        for option in options {
+            let TcpOption::Sack(blocks) = option else {
+                continue;
+            };
+
+            for &(left, right) in blocks {
+                if right <= self.tx_last_ack_no || right > self.tx_next_send_buffer_seq_no {
+                    continue;
+                }
+
+                let left = left.max(self.tx_last_ack_no);
+                let i = self
+                    .tx_sack_scoreboard
+                    .partition_point(|(l, _)| *l < left);
+                self.tx_sack_scoreboard.insert(i, (left, right));
+            }
+        }
+
+        // Merge overlapping ranges
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(self.tx_sack_scoreboard.len());
+        for (left, right) in self.tx_sack_scoreboard.drain(..) {
+            match merged.last_mut() {
+                Some(last) if left <= last.1 => last.1 = last.1.max(right),
+                _ => merged.push((left, right)),
+            }
+        }
+        self.tx_sack_scoreboard = merged;
     }
 
     fn apply_syn_options(&mut self, options: &[TcpOption]) {
@@ -1704,6 +2695,104 @@ impl TransmissionControlBlock {
         }) {
             self.mss = self.mss.min(*mss);
         }
+        self.negotiated_mss = self.mss;
+
+        self.sack = self.sack && options.contains(&TcpOption::SackPermitted());
+
+        // Window scaling is only active if both sides send the option
+        match options.iter().find_map(|v| {
+            if let TcpOption::WindowScaling(shift) = v {
+                Some(*shift)
+            } else {
+                None
+            }
+        }) {
+            Some(shift) if self.window_scaling => self.tx_window_shift = shift.min(14),
+            _ => {
+                self.window_scaling = false;
+                self.tx_window_shift = 0;
+                self.rx_window_shift = 0;
+            }
+        }
+
+        match options.iter().find_map(|v| {
+            if let TcpOption::Timestamp(ts_val, _) = v {
+                Some(*ts_val)
+            } else {
+                None
+            }
+        }) {
+            Some(ts_val) if self.ts => self.ts_recent = ts_val,
+            _ => self.ts = false,
+        }
+
+        let state = self.congestion_state();
+        self.congestion.reset(&state);
+    }
+
+    /// The largest MSS, so that segments fit into a packet of `mtu` bytes.
+    fn mss_for_mtu(&self, mtu: u16) -> u16 {
+        let ip_header = if self.local_addr.is_ipv4() { 20 } else { 40 };
+        let tcp_header = if self.ts { 20 + 12 } else { 20 };
+        mtu.saturating_sub(ip_header + tcp_header).max(1)
+    }
+
+    /// Restores a half-open connection from a SYN cookie, as if the SYN
+    /// from `peer` was received and answered with a SYNACK.
+    fn restore_syn_cookie(&mut self, peer: SocketAddr, isn: u32, mss: u16) {
+        self.peer_addr = peer;
+        self.span.record("peer", debug(self.peer_addr));
+
+        self.rx_last_recv_seq_no = isn;
+        self.rx_buffer.bump(isn + 1);
+
+        self.tx_next_send_seq_no = self.inital_seq_no + 1;
+        self.tx_buffer.bump(self.tx_next_send_seq_no);
+        self.tx_next_send_buffer_seq_no = self.tx_next_send_seq_no;
+        self.tx_max_send_seq_no = self.tx_next_send_seq_no;
+
+        // The SYNACK only announced the MSS, no other options
+        self.apply_syn_options(&[TcpOption::MaximumSegmentSize(mss)]);
+        self.ecn = false;
+        self.rtt_probe = SimTime::MAX;
+        self.state = TcpState::SynRcvd;
+    }
+
+    fn set_keepalive_timer(&mut self) {
+        let Some(keepalive) = self.keepalive else {
+            return;
+        };
+
+        let idle = SimTime::now() - self.last_recv;
+        self.set_timer(keepalive.idle.saturating_sub(idle));
+        self.timer_kind = TcpTimer::Keepalive;
+    }
+
+    fn set_persist_timer(&mut self) {
+        const MAX_PERSIST_TIMEOUT: f64 = 60.0;
+
+        // Exponential backoff, starting at the current RTO (RFC 1122)
+        let timeout = self.rto * 2f64.powi(self.persist_backoff.min(16) as i32);
+        self.set_timer(Duration::from_secs_f64(timeout.min(MAX_PERSIST_TIMEOUT)));
+        self.timer_kind = TcpTimer::Persist;
+    }
+
+    fn is_zero_window(&self) -> bool {
+        self.tx_next_send_seq_no < self.tx_next_send_buffer_seq_no
+            && self.tx_next_send_seq_no >= self.tx_max_send_seq_no
+            && self.tx_last_ack_no == self.tx_next_send_seq_no
+    }
+
+    fn abort(&mut self, e: Error) {
+        self.cancel_timer();
+        self.state = TcpState::Closed;
+        self.tx_state = TcpSenderState::Closed;
+        self.rx_state = TcpReceiverState::Closed;
+        self.error = Some(e);
+
+        // Wake all interests, so that they can observe the error
+        self.rx_read_interests.drain(..).for_each(|g| g.wake());
+        self.tx_write_interests.drain(..).for_each(|g| g.wake());
     }
 
     fn set_data_timer(&mut self) {
@@ -1712,6 +2801,7 @@ impl TransmissionControlBlock {
             SimTime::now() + self.timeout
         );
         self.timer += 1;
+        self.timer_kind = TcpTimer::Data;
         schedule_in(
             Message::new()
                 .kind(KIND_IO_TIMEOUT)
@@ -1725,6 +2815,7 @@ impl TransmissionControlBlock {
     fn cancel_timer(&mut self) {
         tracing::trace!("canceling data timer");
         self.timer += 1;
+        self.timer_kind = TcpTimer::Data;
     }
 
     fn send_buffer_len(&self) -> u32 {
@@ -1735,12 +2826,31 @@ impl TransmissionControlBlock {
         self.tx_max_send_seq_no - (self.tx_next_send_seq_no - 1)
     }
 
-    fn recv_window(&self) -> u16 {
-        self.rx_buffer.rem() as u16
+    fn recv_window(&self) -> u32 {
+        // Out of order data must not shrink the window right edge
+        (self.rx_buffer.cap() - self.rx_buffer.len_continous()) as u32
+    }
+
+    fn advertised_window(&self, win: u32) -> u16 {
+        (win >> self.rx_window_shift).min(u16::MAX as u32) as u16
+    }
+
+    fn syn_window(&self) -> u16 {
+        // The window field of SYN segments is never scaled
+        self.recv_window().min(u16::MAX as u32) as u16
+    }
+
+    fn peer_window(&self, pkt: &TcpPacket) -> u32 {
+        if pkt.flags.syn {
+            pkt.window as u32
+        } else {
+            (pkt.window as u32) << self.tx_window_shift
+        }
     }
 
     fn set_timer(&mut self, expiration: Duration) {
         self.timer += 1;
+        self.timer_kind = TcpTimer::Data;
         schedule_in(
             Message::new()
                 .kind(KIND_IO_TIMEOUT)
@@ -1763,8 +2873,45 @@ impl TransmissionControlBlock {
 
         self.rx_last_recv_seq_no = 0;
         self.tx_max_send_seq_no = 0;
+        self.tx_max_sent_seq_no = 0;
+        self.tx_sack_scoreboard.clear();
+
+        let state = self.congestion_state();
+        self.congestion.reset(&state);
+    }
+
+    fn info(&self) -> TcpInfo {
+        TcpInfo {
+            state: self.state,
+            mss: self.mss,
 
-        self.congestion_window = self.mss as u32;
-        self.congestion_avoid_counter = 0;
+            rtt: Duration::from_secs_f64(self.srtt),
+            rttvar: Duration::from_secs_f64(self.rttvar),
+            rto: Duration::from_secs_f64(self.rto),
+
+            cwnd: self.congestion_ctrl.then(|| self.congestion.cwnd()),
+            ssthresh: self.congestion_ctrl.then(|| self.congestion.ssthresh()),
+
+            bytes_sent: self.sender_send_bytes,
+            bytes_acked: self.sender_ack_bytes,
+            bytes_retransmitted: self.sender_retransmitted_bytes,
+            retransmits: self.sender_retransmits,
+            ecn: self.ecn,
+            ecn_reductions: self.sender_ecn_reductions,
+
+            recv_window: self.recv_window(),
+            send_window: self.tx_max_send_seq_no.saturating_sub(self.tx_last_ack_no),
+        }
+    }
+
+    fn congestion_state(&self) -> CongestionState {
+        CongestionState {
+            mss: self.mss as u32,
+            flight_size: self.tx_next_send_seq_no.wrapping_sub(self.tx_last_ack_no),
+            ack_no: self.tx_last_ack_no,
+            next_seq_no: self.tx_next_send_seq_no,
+            srtt: Duration::from_secs_f64(self.srtt),
+            now: SimTime::now(),
+        }
     }
 }
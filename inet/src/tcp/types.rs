@@ -7,8 +7,9 @@ use inet_types::ip::IpPacketRef;
 
 use super::TcpPacket;
 
+/// The state of a TCP connection, as defined in RFC 793.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-pub(crate) enum TcpState {
+pub enum TcpState {
     #[default]
     Closed = 0,
     Listen = 1,
@@ -48,6 +49,8 @@ pub enum TcpSyscall {
     Open(SocketAddr),
     DestinationUnreachable(Error),
     Close(),
+    Shutdown(),
+    Abort(),
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(super) enum TcpPacketId {
@@ -377,6 +377,16 @@ impl UdpSocket {
         })
     }
 
+    /// Gets the current path MTU of the connected peer, by getting the IP_MTU option.
+    ///
+    /// The path MTU is bounded by the MTU of the outgoing link, and lowered
+    /// once a router reports that a datagram was to big for the next hop.
+    /// This method will fail if the socket is not connected.
+    pub fn path_mtu(&self) -> Result<u16> {
+        let peer = self.peer_addr()?;
+        IOContext::with_current(|ctx| Ok(ctx.path_mtu(peer.ip())))
+    }
+
     pub fn device(&self) -> Result<Option<InterfaceName>> {
         IOContext::with_current(|ctx| ctx.socket_device(self.fd))
     }
@@ -150,7 +150,7 @@ impl IOContext {
         }
     }
 
-    pub(super) fn udp_icmp_error(&mut self, fd: Fd, e: Error, ip: Ipv4Packet) {
+    pub(super) fn udp_icmp_error(&mut self, fd: Fd, e: Error, dest: IpAddr) {
         let Some(mng) = self.udp.binds.get_mut(&fd) else {
             return;
         };
@@ -159,7 +159,7 @@ impl IOContext {
             return;
         };
 
-        if ip.dest == addr.ip() {
+        if dest == addr.ip() {
             // TTL execeeded is correct
             let _ = mng.error.replace(e);
         }
@@ -241,16 +241,17 @@ impl IOContext {
 
         match (mng.local_addr.ip(), target.ip()) {
             (IpAddr::V4(local), IpAddr::V4(target)) => {
+                // Datagrams within the path MTU may not be fragmented, to
+                // detect smaller MTUs on the path (IP_PMTUDISC_WANT)
+                let ttl = mng.ttl;
+                let df = 20 + content.len() <= self.path_mtu(IpAddr::V4(target)) as usize;
                 let ip = Ipv4Packet {
                     dscp: 0,
                     enc: 0,
                     identification: 0,
-                    flags: Ipv4Flags {
-                        df: false,
-                        mf: false,
-                    },
+                    flags: Ipv4Flags { df, mf: false },
                     fragment_offset: 0,
-                    ttl: mng.ttl,
+                    ttl,
                     proto: PROTO_UDP,
 
                     src: local,
@@ -266,8 +267,9 @@ impl IOContext {
                 socket_info.send_q += buf.len();
 
                 let ifid = socket_info.interface.clone();
+                let mark = socket_info.mark;
 
-                self.send_ip_packet(ifid, IpPacket::V4(ip), true)?;
+                self.send_ip_packet_with(ifid, IpPacket::V4(ip), true, None, mark)?;
                 Ok(buf.len())
             }
             (IpAddr::V6(local), IpAddr::V6(target)) => {
@@ -275,7 +277,7 @@ impl IOContext {
                     traffic_class: 0,
                     flow_label: 0,
                     next_header: PROTO_UDP,
-                    hop_limit: 128,
+                    hop_limit: mng.ttl,
 
                     src: local,
                     dest: target,
@@ -290,8 +292,9 @@ impl IOContext {
                 socket_info.send_q += buf.len();
 
                 let ifid = socket_info.interface.clone();
+                let mark = socket_info.mark;
 
-                self.send_ip_packet(ifid, IpPacket::V6(ip), true)?;
+                self.send_ip_packet_with(ifid, IpPacket::V6(ip), true, None, mark)?;
                 Ok(buf.len())
             }
             _ => unreachable!(),
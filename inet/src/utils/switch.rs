@@ -44,13 +44,13 @@ impl Module for LinkLayerSwitch {
         let in_port = self.store_sender(&msg);
         let dest = MacAddress::from(msg.header().dest);
 
-        if dest.is_broadcast() {
+        if dest.is_multicast() {
             for i in 0..self.info.ports.len() {
                 if Some(i) == in_port {
                     continue;
                 }
 
-                // Broadcast ethernet packet.
+                // Broadcast or multicast ethernet packet.
                 if msg.can_cast::<ArpPacket>() {
                     self.forward(msg.dup::<ArpPacket>(), i);
                     continue;
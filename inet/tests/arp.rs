@@ -2,6 +2,7 @@ use des::{prelude::*, registry, time::sleep};
 use inet::{
     arp::arpa,
     interface::{add_interface, Interface, NetworkDevice},
+    ndp::neighbors,
     socket::RawIpSocket,
 };
 use inet_types::ip::{IpPacket, Ipv4Packet, Ipv6Packet};
@@ -72,8 +73,11 @@ impl AsyncModule for Node {
     }
 
     async fn at_sim_end(&mut self) {
-        let r = arpa().unwrap();
-        assert_eq!(r.len(), 6);
+        if self.ip.is_ipv4() {
+            assert_eq!(arpa().unwrap().len(), 6);
+        } else {
+            assert_eq!(neighbors().unwrap().len(), 6);
+        }
     }
 }
 
@@ -0,0 +1,276 @@
+use des::registry;
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{
+    firewall::*,
+    icmp::ping,
+    interface::*,
+    routing::set_default_gateway,
+    types::{icmp::PROTO_ICMP, tcp::PROTO_TCP, udp::PROTO_UDP},
+    TcpListener, TcpStream, UdpSocket,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const CLIENT: Ipv4Addr = Ipv4Addr::new(10, 0, 1, 2);
+const SERVER: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+const ROUTER_LHS: Ipv4Addr = Ipv4Addr::new(10, 0, 1, 1);
+const ROUTER_RHS: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 1);
+const MASK: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 0);
+
+fn rule_for_port(port: u16) -> FirewallRule {
+    FirewallRule {
+        iif: Some(InterfaceName::new("lhs")),
+        proto: Some(PROTO_TCP),
+        dest_ports: Some(port..=port),
+        ..FirewallRule::new(FirewallAction::Accept)
+    }
+}
+
+fn reject_rule() -> FirewallRule {
+    FirewallRule {
+        action: FirewallAction::Reject,
+        ..rule_for_port(81)
+    }
+}
+
+// A firewall between the client subnet 10.0.1.0/24 and the server
+// subnet 10.0.2.0/24, that only allows connections to port 80.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn at_sim_start(&mut self, _: usize) {
+        for (name, addr) in [("lhs", ROUTER_LHS), ("rhs", ROUTER_RHS)] {
+            let gate = format!("{name}_in");
+            add_interface(Interface::ethv4_named(
+                name,
+                NetworkDevice::eth_select(|p| p.input.name() == gate),
+                addr,
+                MASK,
+            ))
+            .unwrap();
+        }
+
+        let forward = FirewallChain::Forward;
+        add_firewall_rule(
+            forward,
+            FirewallRule::new(FirewallAction::Log("fwd: ".into())),
+        )
+        .unwrap();
+        add_firewall_rule(forward, rule_for_port(80)).unwrap();
+        add_firewall_rule(
+            forward,
+            FirewallRule {
+                iif: Some(InterfaceName::new("rhs")),
+                proto: Some(PROTO_TCP),
+                src_ports: Some(80..=80),
+                ..FirewallRule::new(FirewallAction::Accept)
+            },
+        )
+        .unwrap();
+        add_firewall_rule(forward, reject_rule()).unwrap();
+        set_firewall_policy(forward, FirewallAction::Drop).unwrap();
+
+        // Echo replies of the router are rejected
+        add_firewall_rule(
+            FirewallChain::Output,
+            FirewallRule {
+                proto: Some(PROTO_ICMP),
+                ..FirewallRule::new(FirewallAction::Reject)
+            },
+        )
+        .unwrap();
+
+        // Ports require a transport protocol, policies must be final,
+        // and packets can only be rejected once routed
+        let e = add_firewall_rule(
+            forward,
+            FirewallRule {
+                dest_ports: Some(80..=80),
+                ..FirewallRule::new(FirewallAction::Accept)
+            },
+        )
+        .unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        let e = set_firewall_policy(forward, FirewallAction::Reject).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        let e = add_firewall_rule(
+            FirewallChain::Postrouting,
+            FirewallRule::new(FirewallAction::Reject),
+        )
+        .unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        tracing::debug!("{}", msg.str());
+    }
+
+    fn at_sim_end(&mut self) {
+        let rules = firewall_rules(FirewallChain::Forward).unwrap();
+        assert_eq!(rules.len(), 4);
+
+        // All forwarded packets are logged
+        let (log, http, reply, reject) = (&rules[0], &rules[1], &rules[2], &rules[3]);
+        assert!(http.packets > 0 && reply.packets > 0);
+        assert!(log.packets > http.packets + reply.packets + reject.packets);
+
+        // Only the first SYN to port 81 was rejected
+        assert_eq!(reject.rule, reject_rule());
+        assert_eq!(reject.packets, 1);
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4_named(
+            "en0",
+            NetworkDevice::eth(),
+            SERVER,
+            MASK,
+        ))
+        .unwrap();
+        set_default_gateway(ROUTER_RHS).unwrap();
+
+        for port in [80, 81, 82] {
+            tokio::spawn(async move {
+                let lis = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+                    .await
+                    .unwrap();
+                let (mut stream, _) = lis.accept().await.unwrap();
+                assert_eq!(port, 80, "connection should have been filtered");
+
+                let mut buf = [0u8; 64];
+                let n = stream.read(&mut buf).await.unwrap();
+                stream.write_all(&buf[..n]).await.unwrap();
+            });
+        }
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4_named(
+            "en0",
+            NetworkDevice::eth(),
+            CLIENT,
+            MASK,
+        ))
+        .unwrap();
+        set_default_gateway(ROUTER_LHS).unwrap();
+
+        // Locally generated DNS queries and connections to port 83 are rejected
+        for (proto, port) in [(PROTO_UDP, 53), (PROTO_TCP, 83)] {
+            add_firewall_rule(
+                FirewallChain::Output,
+                FirewallRule {
+                    proto: Some(proto),
+                    dest_ports: Some(port..=port),
+                    ..FirewallRule::new(FirewallAction::Reject)
+                },
+            )
+            .unwrap();
+        }
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect((SERVER, 80)).await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            // Rejected connections are reset by the firewall
+            let e = TcpStream::connect((SERVER, 81)).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::ConnectionRefused);
+
+            // Dropped connections time out
+            let dropped =
+                des::time::timeout(Duration::from_secs(5), TcpStream::connect((SERVER, 82))).await;
+            assert!(dropped.is_err(), "connect should have timed out");
+
+            let sock = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+            let e = sock.send_to(b"query", (SERVER, 53)).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+            sock.send_to(b"query", (SERVER, 54)).await.unwrap();
+
+            let e = TcpStream::connect((SERVER, 83)).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+
+            let rules = firewall_rules(FirewallChain::Output).unwrap();
+            assert_eq!(rules[0].packets, 1);
+            assert_eq!(rules[1].packets, 1);
+
+            let pinged = des::time::timeout(Duration::from_secs(5), ping(ROUTER_LHS)).await;
+            assert!(pinged.is_err(), "ping should have timed out");
+
+            // Local pings fail to send
+            add_firewall_rule(
+                FirewallChain::Output,
+                FirewallRule {
+                    proto: Some(PROTO_ICMP),
+                    ..FirewallRule::new(FirewallAction::Reject)
+                },
+            )
+            .unwrap();
+            let e = ping(ROUTER_LHS).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn firewall_forward_chain() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
@@ -0,0 +1,178 @@
+use des::registry;
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{
+    icmp::ping,
+    interface::*,
+    ndp::{start_router_advertisements, PrefixAdvertisement, RouterAdvertisementConfig},
+    routing::{ip_forward, set_ip_forward, set_ip_forward_on},
+    types::ip::IpVersion,
+};
+use serial_test::serial;
+
+const CLIENT: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+const SERVER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 2);
+const REMOTE: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 3, 0, 0, 0, 0, 2);
+const ROUTER_LHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+const ROUTER_RHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+
+const MODE_ROUTER: usize = 0;
+const MODE_HOST: usize = 1;
+const MODE_IFACE: usize = 2;
+
+static MODE: AtomicUsize = AtomicUsize::new(0);
+
+// A node between the client subnet 2001:db8:1::/64 and the server
+// subnet 2001:db8:2::/64, that either routes or acts as a host.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn at_sim_start(&mut self, _: usize) {
+        for (name, addr) in [("lhs", ROUTER_LHS), ("rhs", ROUTER_RHS)] {
+            let gate = format!("{name}_in");
+            add_interface(Interface::ethv6_named(
+                name,
+                NetworkDevice::eth_select(|p| p.input.name() == gate),
+                addr,
+            ))
+            .unwrap();
+
+            start_router_advertisements(
+                &InterfaceName::new(name).id(),
+                RouterAdvertisementConfig {
+                    prefixes: vec![PrefixAdvertisement {
+                        autonomous: false,
+                        ..PrefixAdvertisement::new(addr, 64)
+                    }],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let lhs = InterfaceName::new("lhs").id();
+        let rhs = InterfaceName::new("rhs").id();
+        match MODE.load(SeqCst) {
+            MODE_HOST => set_ip_forward(IpVersion::V6, false).unwrap(),
+            MODE_IFACE => set_ip_forward_on(&lhs, IpVersion::V6, false).unwrap(),
+            _ => {}
+        }
+
+        let forwarding = MODE.load(SeqCst) == MODE_ROUTER;
+        assert_eq!(ip_forward(&lhs, IpVersion::V6).unwrap(), forwarding);
+        assert_eq!(
+            ip_forward(&rhs, IpVersion::V6).unwrap(),
+            MODE.load(SeqCst) != MODE_HOST
+        );
+        assert!(ip_forward(&lhs, IpVersion::V4).unwrap());
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        tracing::debug!("{}", msg.str());
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), SERVER)).unwrap();
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), CLIENT)).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            // Wait for the router advertisements
+            des::time::sleep(Duration::from_secs(1)).await;
+
+            // Packets addressed to the node itself are always accepted
+            ping(ROUTER_LHS).await.unwrap();
+
+            let server = des::time::timeout(Duration::from_secs(10), ping(SERVER)).await;
+            if MODE.load(SeqCst) == MODE_ROUTER {
+                assert_eq!(server.unwrap().unwrap().ttl, 63);
+
+                // Routers report destinations without a route
+                let e = ping(REMOTE).await.unwrap_err();
+                assert_eq!(e.kind(), ErrorKind::ConnectionRefused);
+            } else {
+                assert!(server.is_err(), "ping should have timed out");
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(mode: usize) {
+    inet::init();
+    MODE.store(mode, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
+
+#[test]
+#[serial]
+fn ip_forward_router_unreachable() {
+    run(MODE_ROUTER);
+}
+
+#[test]
+#[serial]
+fn ip_forward_disabled_on_host() {
+    run(MODE_HOST);
+}
+
+#[test]
+#[serial]
+fn ip_forward_disabled_on_iface() {
+    run(MODE_IFACE);
+}
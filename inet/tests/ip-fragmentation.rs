@@ -0,0 +1,187 @@
+use des::registry;
+use inet_types::ip::Ipv4Packet;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{interface::*, UdpSocket};
+use serial_test::serial;
+
+const SIZE: usize = 4000;
+const N: usize = 3;
+
+static DROP_FRAGMENT: AtomicBool = AtomicBool::new(false);
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+fn datagram(i: usize) -> Vec<u8> {
+    (0..SIZE).map(|j| ((i + j) % 251) as u8).collect()
+}
+
+// Checks that no packet exceeds the link MTU, and optionally drops
+// the second fragment of the first datagram.
+struct Link {
+    fragments: usize,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self { fragments: 0 }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        if let Some(ip) = msg.try_content::<Ipv4Packet>() {
+            assert!(20 + ip.content.len() <= MTU_ETHERNET as usize);
+        }
+
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let Some(ip) = msg.try_content::<Ipv4Packet>() else {
+                    return send(msg, "rhs_out");
+                };
+                if ip.flags.mf || ip.fragment_offset != 0 {
+                    self.fragments += 1;
+                    if DROP_FRAGMENT.load(SeqCst) && self.fragments == 2 {
+                        tracing::info!("dropping fragment at offset {}", ip.fragment_offset);
+                        return;
+                    }
+                }
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+
+    fn at_sim_end(&mut self) {
+        // Each datagram is split into 3 fragments
+        assert_eq!(self.fragments, N * 3);
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = UdpSocket::bind("0.0.0.0:2000").await.unwrap();
+            let expected = if DROP_FRAGMENT.load(SeqCst) { N - 1 } else { N };
+
+            let mut buf = [0u8; 2 * SIZE];
+            for _ in 0..expected {
+                let (n, from) = sock.recv_from(&mut buf).await.unwrap();
+                assert_eq!(n, SIZE);
+
+                // Datagrams are identified by their first byte
+                let i = buf[0] as usize;
+                assert_eq!(&buf[..n], datagram(i));
+                if DROP_FRAGMENT.load(SeqCst) {
+                    assert_ne!(i, 0, "incomplete datagram was delivered");
+                }
+
+                RECEIVED.fetch_add(1, SeqCst);
+                sock.send_to(&buf[..n], from).await.unwrap();
+            }
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+            for i in 0..N {
+                sock.send_to(&datagram(i), "69.0.0.100:2000").await.unwrap();
+                des::time::sleep(Duration::from_millis(100)).await;
+            }
+
+            let expected = if DROP_FRAGMENT.load(SeqCst) { N - 1 } else { N };
+            let mut buf = [0u8; 2 * SIZE];
+            for _ in 0..expected {
+                let n = sock.recv(&mut buf).await.unwrap();
+                assert_eq!(&buf[..n], datagram(buf[0] as usize));
+            }
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+    RECEIVED.store(0, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(60.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn ip_fragmentation_udp() {
+    DROP_FRAGMENT.store(false, SeqCst);
+    run();
+    assert_eq!(RECEIVED.load(SeqCst), N);
+}
+
+#[test]
+#[serial]
+fn ip_fragmentation_incomplete() {
+    DROP_FRAGMENT.store(true, SeqCst);
+    run();
+    assert_eq!(RECEIVED.load(SeqCst), N - 1);
+}
@@ -0,0 +1,329 @@
+use bytepack::{FromBytestream, ToBytestream};
+use des::registry;
+use inet_types::{
+    icmpv6::{
+        solicited_node_multicast, Icmpv6Packet, NdpOption, ALL_NODES_MULTICAST,
+        ALL_ROUTERS_MULTICAST, NDP_HOP_LIMIT, PROTO_ICMPV6,
+    },
+    iface::MacAddress,
+    ip::{Ipv6Packet, KIND_IPV6},
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    ndp::{neighbors, NeighborState},
+    UdpSocket,
+};
+use serial_test::serial;
+
+const CLIENT: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+const SERVER: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+const MISSING: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 3);
+const ROUTER: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0xff);
+const REMOTE: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 5);
+const ROUTER_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0xff];
+
+const MODE_RESOLVE: usize = 0;
+const MODE_NUD: usize = 1;
+const MODE_UNREACHABLE: usize = 2;
+const MODE_ROUTER: usize = 3;
+
+static MODE: AtomicUsize = AtomicUsize::new(0);
+static NS_MULTICAST: AtomicUsize = AtomicUsize::new(0);
+static NS_UNICAST: AtomicUsize = AtomicUsize::new(0);
+static NA_SOLICITED: AtomicUsize = AtomicUsize::new(0);
+static ROUTED: AtomicUsize = AtomicUsize::new(0);
+
+fn neighbor(ip: Ipv6Addr) -> Option<inet::ndp::NeighborEntry> {
+    neighbors().unwrap().into_iter().find(|n| n.ip == ip)
+}
+
+// Checks that all NDP traffic is well-formed. In router mode, the link
+// answers router solicitations and acts as the default router.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        let from_client = match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => true,
+            Some("rhs_in") => false,
+            _ => todo!(),
+        };
+        let out = if from_client { "rhs_out" } else { "lhs_out" };
+
+        let Some(ip) = msg.try_content::<Ipv6Packet>() else {
+            return send(msg, out);
+        };
+        let dest_mac = MacAddress::from(msg.header().dest);
+
+        if ip.next_header == PROTO_ICMPV6 {
+            let icmp = Icmpv6Packet::read_from_slice(&mut &ip.content[..]).unwrap();
+            if icmp.is_ndp() {
+                assert_eq!(ip.hop_limit, NDP_HOP_LIMIT);
+            }
+
+            match &icmp {
+                Icmpv6Packet::NeighborSolicitation { target, .. } => {
+                    assert_eq!(
+                        icmp.source_link_layer_addr(),
+                        Some(MacAddress::from(msg.header().src))
+                    );
+                    if ip.dest.is_multicast() {
+                        assert_eq!(ip.dest, solicited_node_multicast(*target));
+                        assert_eq!(dest_mac, MacAddress::ipv6_multicast(ip.dest));
+                        NS_MULTICAST.fetch_add(1, SeqCst);
+                    } else {
+                        assert_eq!(ip.dest, *target);
+                        NS_UNICAST.fetch_add(1, SeqCst);
+                    }
+                }
+                Icmpv6Packet::NeighborAdvertisement {
+                    solicited, target, ..
+                } => {
+                    assert!(*solicited);
+                    assert_eq!(ip.src, *target);
+                    assert!(!dest_mac.is_multicast());
+                    assert!(icmp.target_link_layer_addr().is_some());
+                    NA_SOLICITED.fetch_add(1, SeqCst);
+                }
+                Icmpv6Packet::RouterSolicitation { .. } => {
+                    assert_eq!(ip.dest, ALL_ROUTERS_MULTICAST);
+                    assert_eq!(dest_mac, MacAddress::ipv6_multicast(ip.dest));
+                    if MODE.load(SeqCst) == MODE_ROUTER && from_client {
+                        return self.advertise(msg.header().src);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if MODE.load(SeqCst) == MODE_ROUTER && ip.dest == REMOTE {
+            assert_eq!(dest_mac, MacAddress::from(ROUTER_MAC));
+            ROUTED.fetch_add(1, SeqCst);
+            return;
+        }
+
+        send(msg, out)
+    }
+}
+
+impl Link {
+    fn advertise(&mut self, client: [u8; 6]) {
+        let icmp = Icmpv6Packet::RouterAdvertisement {
+            cur_hop_limit: 64,
+            managed: false,
+            other: false,
+            router_lifetime: 1800,
+            reachable_time: 0,
+            retrans_timer: 0,
+            options: vec![
+                NdpOption::SourceLinkLayerAddress(ROUTER_MAC.into()),
+                NdpOption::Mtu(1400),
+                NdpOption::PrefixInformation {
+                    prefix_len: 64,
+                    on_link: true,
+                    autonomous: false,
+                    valid_lifetime: 86400,
+                    preferred_lifetime: 14400,
+                    prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0),
+                },
+            ],
+        };
+        let pkt = Ipv6Packet {
+            traffic_class: 0,
+            flow_label: 0,
+            next_header: PROTO_ICMPV6,
+            hop_limit: NDP_HOP_LIMIT,
+            src: ROUTER,
+            dest: ALL_NODES_MULTICAST,
+            content: icmp.to_vec().unwrap(),
+        };
+        let msg = Message::new()
+            .kind(KIND_IPV6)
+            .src(ROUTER_MAC)
+            .dest(client)
+            .content(pkt)
+            .build();
+        send(msg, "lhs_out")
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), SERVER)).unwrap();
+
+        let expected = match MODE.load(SeqCst) {
+            MODE_RESOLVE => 1,
+            MODE_NUD => 2,
+            _ => 0,
+        };
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = UdpSocket::bind(":::2000").await.unwrap();
+            let mut buf = [0u8; 1024];
+            for _ in 0..expected {
+                let (n, from) = sock.recv_from(&mut buf).await.unwrap();
+                assert_eq!(n, 42);
+                assert_eq!(from.ip(), CLIENT);
+            }
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), CLIENT)).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = UdpSocket::bind(":::0").await.unwrap();
+            let server = SocketAddrV6::new(SERVER, 2000, 0, 0);
+            let buf = [42; 42];
+
+            match MODE.load(SeqCst) {
+                MODE_RESOLVE => {
+                    sock.send_to(&buf, server).await.unwrap();
+                    des::time::sleep(Duration::from_secs(1)).await;
+
+                    let entry = neighbor(SERVER).unwrap();
+                    assert_eq!(entry.state, NeighborState::Reachable);
+                    assert!(!entry.mac.is_unspecified());
+                    assert!(!entry.router);
+                }
+                MODE_NUD => {
+                    sock.send_to(&buf, server).await.unwrap();
+                    des::time::sleep(Duration::from_secs(40)).await;
+                    assert_eq!(neighbor(SERVER).unwrap().state, NeighborState::Stale);
+
+                    // Traffic to a stale neighbor starts reachability probing
+                    sock.send_to(&buf, server).await.unwrap();
+                    assert_eq!(neighbor(SERVER).unwrap().state, NeighborState::Delay);
+
+                    des::time::sleep(Duration::from_secs(10)).await;
+                    assert_eq!(neighbor(SERVER).unwrap().state, NeighborState::Reachable);
+                }
+                MODE_UNREACHABLE => {
+                    let missing = SocketAddrV6::new(MISSING, 2000, 0, 0);
+                    sock.send_to(&buf, missing).await.unwrap();
+                    assert_eq!(neighbor(MISSING).unwrap().state, NeighborState::Incomplete);
+
+                    des::time::sleep(Duration::from_secs(5)).await;
+                    assert!(neighbor(MISSING).is_none());
+                }
+                MODE_ROUTER => {
+                    des::time::sleep(Duration::from_secs(1)).await;
+                    let entry = neighbor(ROUTER).unwrap();
+                    assert!(entry.router);
+                    assert_eq!(entry.mac, ROUTER_MAC.into());
+
+                    let state = interface_status(&InterfaceName::new("en1").id()).unwrap();
+                    assert_eq!(state.mtu, 1400);
+
+                    // Off-link destinations are send to the default router
+                    let remote = SocketAddrV6::new(REMOTE, 2000, 0, 0);
+                    sock.send_to(&buf, remote).await.unwrap();
+                }
+                _ => unreachable!(),
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(mode: usize) {
+    inet::init();
+    MODE.store(mode, SeqCst);
+    NS_MULTICAST.store(0, SeqCst);
+    NS_UNICAST.store(0, SeqCst);
+    NA_SOLICITED.store(0, SeqCst);
+    ROUTED.store(0, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
+
+#[test]
+#[serial]
+fn ndp_address_resolution() {
+    run(MODE_RESOLVE);
+    assert_eq!(NS_MULTICAST.load(SeqCst), 1);
+    assert_eq!(NA_SOLICITED.load(SeqCst), 1);
+}
+
+#[test]
+#[serial]
+fn ndp_neighbor_unreachability_detection() {
+    run(MODE_NUD);
+    assert_eq!(NS_MULTICAST.load(SeqCst), 1);
+    assert_eq!(NS_UNICAST.load(SeqCst), 1);
+    assert_eq!(NA_SOLICITED.load(SeqCst), 2);
+}
+
+#[test]
+#[serial]
+fn ndp_address_resolution_failure() {
+    run(MODE_UNREACHABLE);
+    assert_eq!(NS_MULTICAST.load(SeqCst), 3);
+    assert_eq!(NA_SOLICITED.load(SeqCst), 0);
+}
+
+#[test]
+#[serial]
+fn ndp_router_advertisement() {
+    run(MODE_ROUTER);
+    assert_eq!(ROUTED.load(SeqCst), 1);
+}
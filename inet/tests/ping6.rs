@@ -0,0 +1,143 @@
+use des::registry;
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    icmp::{ping, ping_with, traceroute, Trace},
+    interface::*,
+    ndp::{start_router_advertisements, PrefixAdvertisement, RouterAdvertisementConfig},
+};
+use serial_test::serial;
+
+const CLIENT: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+const SERVER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 2);
+const ROUTER_LHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+const ROUTER_RHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+
+// A router between the client subnet 2001:db8:1::/64 and the
+// server subnet 2001:db8:2::/64, announcing itself as default router.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn at_sim_start(&mut self, _: usize) {
+        for (name, addr) in [("lhs", ROUTER_LHS), ("rhs", ROUTER_RHS)] {
+            let gate = format!("{name}_in");
+            add_interface(Interface::ethv6_named(
+                name,
+                NetworkDevice::eth_select(|p| p.input.name() == gate),
+                addr,
+            ))
+            .unwrap();
+
+            start_router_advertisements(
+                &InterfaceName::new(name).id(),
+                RouterAdvertisementConfig {
+                    prefixes: vec![PrefixAdvertisement {
+                        autonomous: false,
+                        ..PrefixAdvertisement::new(addr, 64)
+                    }],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        tracing::debug!("{}", msg.str());
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), SERVER)).unwrap();
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), CLIENT)).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            // Wait for the router advertisements
+            des::time::sleep(Duration::from_secs(1)).await;
+
+            let router = ping(ROUTER_LHS).await.unwrap();
+            assert_eq!(router.ttl, 64);
+
+            let server = ping_with(SERVER, 5).await.unwrap();
+            assert_eq!(server.ttl, 63);
+            assert!(server.time_min > Duration::ZERO);
+            assert!(server.time_min <= server.time_avg);
+            assert!(server.time_avg <= server.time_max);
+            assert!(server.time_min > router.time_min);
+
+            // Group addresses cannot be pinged
+            let e = ping(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1))
+                .await
+                .unwrap_err();
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+
+            let trace = traceroute(SERVER).await.unwrap();
+            assert_eq!(trace.target, IpAddr::V6(SERVER));
+            assert_eq!(trace.nodes.len(), 1);
+            assert!(matches!(
+                trace.nodes[0],
+                Trace::Found { addr, .. } if addr == IpAddr::V6(ROUTER_LHS)
+            ));
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn ping6_and_traceroute6() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
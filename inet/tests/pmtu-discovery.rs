@@ -0,0 +1,244 @@
+use bytepack::ToBytestream;
+use des::registry;
+use inet_types::{
+    icmp::{IcmpDestinationUnreachableCode, IcmpPacket, IcmpType, PROTO_ICMP},
+    ip::{Ipv4Packet, KIND_IPV4},
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, TcpConfig},
+    TcpListener, TcpStream, UdpSocket,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const PATH_MTU: u16 = 1000;
+const LIMIT: usize = 20 * 1024;
+const DATAGRAM: usize = 1400;
+
+const MODE_ICMP: usize = 0;
+const MODE_BLACK_HOLE: usize = 1;
+const MODE_UDP: usize = 2;
+
+static MODE: AtomicUsize = AtomicUsize::new(0);
+static ICMP_SENT: AtomicUsize = AtomicUsize::new(0);
+
+// A router with a smaller MTU towards the server. Packets exceeding the MTU
+// with DF set are dropped, and answered with an ICMP error unless the router
+// is a black hole.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let Some(ip) = msg.try_content::<Ipv4Packet>() else {
+                    return send(msg, "rhs_out");
+                };
+
+                if 20 + ip.content.len() > PATH_MTU as usize {
+                    assert!(ip.flags.df, "packet could have been fragmented");
+                    if MODE.load(SeqCst) == MODE_BLACK_HOLE {
+                        return;
+                    }
+
+                    let icmp = IcmpPacket::new(
+                        IcmpType::DestinationUnreachable {
+                            next_hop_mtu: PATH_MTU,
+                            code: IcmpDestinationUnreachableCode::DatagramToBig,
+                        },
+                        ip,
+                    );
+                    let mut reply = ip.reverse();
+                    reply.src = Ipv4Addr::new(69, 0, 0, 1);
+                    reply.proto = PROTO_ICMP;
+                    reply.content = icmp.to_vec().unwrap();
+
+                    ICMP_SENT.fetch_add(1, SeqCst);
+                    let reply = Message::new()
+                        .kind(KIND_IPV4)
+                        .src(msg.header().dest)
+                        .dest(msg.header().src)
+                        .content(reply)
+                        .build();
+                    return send(reply, "lhs_out");
+                }
+
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            if MODE.load(SeqCst) == MODE_UDP {
+                let sock = UdpSocket::bind("0.0.0.0:2000").await.unwrap();
+                let mut buf = [0u8; 2 * DATAGRAM];
+                let (n, _) = sock.recv_from(&mut buf).await.unwrap();
+                assert_eq!(n, DATAGRAM);
+                done.store(true, SeqCst);
+                return;
+            }
+
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            let mut buf = [0u8; 1024];
+            let mut acc = 0;
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                for (i, byte) in buf[..n].iter().enumerate() {
+                    assert_eq!(*byte, ((acc + i) % 251) as u8);
+                }
+                acc += n;
+            }
+
+            assert_eq!(acc, LIMIT);
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(TcpConfig {
+            mss: 1460,
+            mtu_probing: MODE.load(SeqCst) == MODE_BLACK_HOLE,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            if MODE.load(SeqCst) == MODE_UDP {
+                let sock = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+                sock.connect("69.0.0.100:2000").await.unwrap();
+                assert_eq!(sock.path_mtu().unwrap(), MTU_ETHERNET);
+
+                // The first datagram is dropped, but the path MTU is learned
+                let data = vec![42; DATAGRAM];
+                sock.send(&data).await.unwrap();
+                des::time::sleep(Duration::from_secs(1)).await;
+                assert_eq!(sock.path_mtu().unwrap(), PATH_MTU);
+
+                // Now the datagram is fragmented at the source
+                sock.send(&data).await.unwrap();
+                done.store(true, SeqCst);
+                return;
+            }
+
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            stream.write_all(&data).await.unwrap();
+
+            let info = stream.info().unwrap();
+            assert!(info.mss + 40 <= PATH_MTU, "mss {}", info.mss);
+
+            done.store(true, SeqCst);
+            drop(stream);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+    ICMP_SENT.store(0, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(30.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn pmtu_discovery_tcp() {
+    MODE.store(MODE_ICMP, SeqCst);
+    run();
+    assert!(ICMP_SENT.load(SeqCst) >= 1);
+}
+
+#[test]
+#[serial]
+fn pmtu_black_hole_detection() {
+    MODE.store(MODE_BLACK_HOLE, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn pmtu_discovery_udp() {
+    MODE.store(MODE_UDP, SeqCst);
+    run();
+    assert_eq!(ICMP_SENT.load(SeqCst), 1);
+}
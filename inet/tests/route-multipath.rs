@@ -0,0 +1,128 @@
+use des::registry;
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{interface::*, routing::*, UdpSocket};
+use serial_test::serial;
+
+const MASK: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 0);
+const TARGET_SUBNET: Ipv4Addr = Ipv4Addr::new(10, 0, 3, 0);
+const TARGET: Ipv4Addr = Ipv4Addr::new(10, 0, 3, 1);
+
+// Adds the interfaces en0 and en1 in the subnets 10.0.1.0/24 and
+// 10.0.2.0/24, connected to the gates in0 and in1.
+fn add_interfaces(host: u8) {
+    for i in 0..2 {
+        let gate = format!("in{i}");
+        add_interface(Interface::ethv4_named(
+            format!("en{i}"),
+            NetworkDevice::eth_select(|p| p.input.name() == gate),
+            Ipv4Addr::new(10, 0, i + 1, host),
+            MASK,
+        ))
+        .unwrap();
+    }
+}
+
+struct Server {}
+
+impl Module for Server {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn at_sim_start(&mut self, _: usize) {
+        add_interfaces(1);
+    }
+}
+
+struct Client {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for Client {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interfaces(2);
+
+        // Two next hops with equal cost, via both interfaces
+        for i in 0..2 {
+            append_routing_entry(
+                TARGET_SUBNET,
+                MASK,
+                Ipv4Addr::new(10, 0, i + 1, 1),
+                &format!("en{i}"),
+                RouteOptions::default(),
+            )
+            .unwrap();
+        }
+        set_multipath_policy(MultipathPolicy::RoundRobin).unwrap();
+        assert_eq!(multipath_policy().unwrap(), MultipathPolicy::RoundRobin);
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let counters = |iface: &str| {
+                route_counters()
+                    .unwrap()
+                    .into_iter()
+                    .find(|c| c.entry.dest == TARGET_SUBNET && &*c.entry.iface == iface)
+                    .unwrap()
+                    .packets
+            };
+            assert_eq!(counters("en0"), 0);
+            assert_eq!(counters("en1"), 0);
+
+            let sock = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+            for _ in 0..4 {
+                sock.send_to(&[42; 42], (TARGET, 100)).await.unwrap();
+            }
+
+            // The datagrams are distributed over both next hops
+            assert_eq!(counters("en0"), 2);
+            assert_eq!(counters("en1"), 2);
+
+            // Removing the route clears its counters
+            remove_routing_entry(TARGET_SUBNET, MASK).unwrap();
+            assert!(!route_counters()
+                .unwrap()
+                .iter()
+                .any(|c| c.entry.dest == TARGET_SUBNET));
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn ipv4_multipath_route_counters() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/multipath.ndl", registry![Server, Client, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
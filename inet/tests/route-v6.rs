@@ -0,0 +1,155 @@
+use des::registry;
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{icmp::ping, interface::*, routing::*};
+use serial_test::serial;
+
+const CLIENT: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+const SERVER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 2);
+const ROUTER_LHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+const ROUTER_RHS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+const CLIENT_SUBNET: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+const MASK: Ipv6Addr = Ipv6Addr::new(0xffff, 0xffff, 0xffff, 0xffff, 0, 0, 0, 0);
+
+// A router between the client subnet 2001:db8:1::/64 and the server
+// subnet 2001:db8:2::/64, without router advertisements.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn at_sim_start(&mut self, _: usize) {
+        for (name, addr) in [("lhs", ROUTER_LHS), ("rhs", ROUTER_RHS)] {
+            let gate = format!("{name}_in");
+            add_interface(Interface::ethv6_named(
+                name,
+                NetworkDevice::eth_select(|p| p.input.name() == gate),
+                addr,
+            ))
+            .unwrap();
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        tracing::debug!("{}", msg.str());
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), SERVER)).unwrap();
+
+        // The return route is placed in a separate table
+        let table = add_routing_table().unwrap();
+        add_routing_entry_to(CLIENT_SUBNET, MASK, ROUTER_RHS, "en1", table).unwrap();
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), CLIENT)).unwrap();
+        set_default_gateway(ROUTER_LHS).unwrap();
+
+        let routes = route().unwrap();
+        assert!(routes.iter().any(|r| matches!(
+            r,
+            FwdEntry::V6(FwdEntryV6 {
+                gateway: Ipv6Gateway::Gateway(ROUTER_LHS),
+                ..
+            })
+        ) && r.dest() == Ipv6Addr::UNSPECIFIED));
+        assert!(routes.iter().any(|r| matches!(
+            r,
+            FwdEntry::V6(FwdEntryV6 {
+                gateway: Ipv6Gateway::Local,
+                ..
+            })
+        ) && r.dest() == CLIENT_SUBNET));
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let server = ping(SERVER).await.unwrap();
+            assert_eq!(server.ttl, 63);
+
+            remove_routing_entry(Ipv6Addr::UNSPECIFIED, Ipv6Addr::UNSPECIFIED).unwrap();
+            assert!(!route()
+                .unwrap()
+                .iter()
+                .any(|r| r.dest() == Ipv6Addr::UNSPECIFIED));
+            assert_eq!(
+                remove_routing_entry(Ipv6Addr::UNSPECIFIED, Ipv6Addr::UNSPECIFIED)
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotFound
+            );
+
+            // Unroutable targets fail the ping
+            let e = ping(SERVER).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::ConnectionRefused);
+
+            // A more specific route is added instead
+            add_routing_entry(
+                Ipv6Addr::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 0),
+                MASK,
+                ROUTER_LHS,
+                "en1",
+            )
+            .unwrap();
+            assert!(ping(SERVER).await.is_ok());
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn ipv6_static_routes() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
@@ -0,0 +1,168 @@
+use des::registry;
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{interface::*, routing::*};
+use serial_test::serial;
+
+const SUBNET: Ipv4Addr = Ipv4Addr::new(10, 0, 3, 0);
+const MASK: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 0);
+const PRIMARY: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+const BACKUP: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 2);
+
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        tracing::debug!("{}", msg.str());
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+fn gateway_for(entries: &[FwdEntry]) -> Option<IpAddr> {
+    entries
+        .iter()
+        .filter(|e| e.dest() == SUBNET && e.mask() == MASK)
+        .min_by_key(|e| e.metric())
+        .and_then(|e| e.gateway())
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4_named(
+            "en0",
+            NetworkDevice::eth(),
+            Ipv4Addr::new(192, 168, 0, 100),
+            Ipv4Addr::new(255, 255, 255, 0),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut watcher = watch_routes().unwrap();
+
+            add_routing_entry_with(
+                SUBNET,
+                MASK,
+                BACKUP,
+                "en0",
+                RouteOptions {
+                    metric: 100,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            add_routing_entry_with(
+                SUBNET,
+                MASK,
+                PRIMARY,
+                "en0",
+                RouteOptions {
+                    metric: 10,
+                    lifetime: Some(Duration::from_secs(5)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            let RouteEvent::Added { entry, .. } = watcher.recv().await.unwrap() else {
+                panic!("expected added route")
+            };
+            assert_eq!(entry.gateway(), Some(IpAddr::V4(BACKUP)));
+            let RouteEvent::Added { entry, .. } = watcher.recv().await.unwrap() else {
+                panic!("expected added route")
+            };
+            assert_eq!(entry.gateway(), Some(IpAddr::V4(PRIMARY)));
+            assert_eq!(gateway_for(&route().unwrap()), Some(IpAddr::V4(PRIMARY)));
+
+            // The primary route expires
+            let RouteEvent::Removed { entry, .. } = watcher.recv().await.unwrap() else {
+                panic!("expected removed route")
+            };
+            assert_eq!(entry.gateway(), Some(IpAddr::V4(PRIMARY)));
+            assert_eq!(SimTime::now(), SimTime::from(Duration::from_secs(5)));
+            assert_eq!(gateway_for(&route().unwrap()), Some(IpAddr::V4(BACKUP)));
+
+            replace_routing_entry(SUBNET, MASK, PRIMARY, "en0", RouteOptions::default()).unwrap();
+            assert!(matches!(
+                watcher.try_recv().unwrap(),
+                RouteEvent::Removed { entry, .. } if entry.gateway() == Some(IpAddr::V4(BACKUP))
+            ));
+            assert!(matches!(
+                watcher.try_recv().unwrap(),
+                RouteEvent::Added { entry, .. } if entry.gateway() == Some(IpAddr::V4(PRIMARY))
+            ));
+
+            remove_routing_entry(SUBNET, MASK).unwrap();
+            assert!(matches!(
+                watcher.try_recv().unwrap(),
+                RouteEvent::Removed { entry, .. } if entry.gateway() == Some(IpAddr::V4(PRIMARY))
+            ));
+            assert_eq!(gateway_for(&route().unwrap()), None);
+            assert_eq!(
+                watcher.try_recv().unwrap_err().kind(),
+                ErrorKind::WouldBlock
+            );
+            assert_eq!(
+                remove_routing_entry(SUBNET, MASK).unwrap_err().kind(),
+                ErrorKind::NotFound
+            );
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn route_watch_and_expiry() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
@@ -0,0 +1,305 @@
+use bytepack::{FromBytestream, ToBytestream};
+use des::registry;
+use inet_types::{
+    icmpv6::{Icmpv6Packet, NdpOption, ALL_NODES_MULTICAST, NDP_HOP_LIMIT, PROTO_ICMPV6},
+    iface::MacAddress,
+    ip::{Ipv6Packet, KIND_IPV6},
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    ndp::{
+        set_ndp_config, start_router_advertisements, stop_router_advertisements, AddrGenMode,
+        NdpConfig, PrefixAdvertisement, RouterAdvertisementConfig,
+    },
+    UdpSocket,
+};
+use serial_test::serial;
+
+const ROUTER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const PREFIX: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0);
+const DUPLICATE_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0xdd];
+
+const MODE_EUI64: usize = 0;
+const MODE_RANDOM: usize = 1;
+const MODE_DUPLICATE: usize = 2;
+
+static MODE: AtomicUsize = AtomicUsize::new(0);
+static DAD_PROBES: AtomicUsize = AtomicUsize::new(0);
+static ADVERTISEMENTS: AtomicUsize = AtomicUsize::new(0);
+static FINAL_ADVERTISEMENTS: AtomicUsize = AtomicUsize::new(0);
+static DUPLICATE_ID: AtomicU64 = AtomicU64::new(0);
+
+fn interface_id(addr: Ipv6Addr) -> u64 {
+    u128::from_be_bytes(addr.octets()) as u64
+}
+
+fn ipv6_addrs() -> Vec<Ipv6Addr> {
+    interface_status(&InterfaceName::new("en1").id())
+        .unwrap()
+        .addrs
+        .into_iter()
+        .filter_map(|addr| match addr {
+            InterfaceAddr::Inet6 { addr, .. } => Some(addr),
+            _ => None,
+        })
+        .collect()
+}
+
+// Observes the autoconfiguration of the client. In the duplicate modes,
+// the first duplicate address detection probe is answered by the link,
+// as if another node would use the tentative address.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        let out = match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => "rhs_out",
+            Some("rhs_in") => "lhs_out",
+            _ => todo!(),
+        };
+
+        let Some(ip) = msg.try_content::<Ipv6Packet>() else {
+            return send(msg, out);
+        };
+        if ip.next_header != PROTO_ICMPV6 {
+            return send(msg, out);
+        }
+
+        let icmp = Icmpv6Packet::read_from_slice(&mut &ip.content[..]).unwrap();
+        match &icmp {
+            Icmpv6Packet::NeighborSolicitation { target, options } if ip.src.is_unspecified() => {
+                assert!(options.is_empty());
+                let n = DAD_PROBES.fetch_add(1, SeqCst);
+                if n == 0 && MODE.load(SeqCst) != MODE_EUI64 {
+                    DUPLICATE_ID.store(interface_id(*target), SeqCst);
+                    return self.defend(*target);
+                }
+            }
+            Icmpv6Packet::RouterAdvertisement {
+                router_lifetime, ..
+            } => {
+                assert_eq!(ip.dest, ALL_NODES_MULTICAST);
+                assert_eq!(ip.hop_limit, NDP_HOP_LIMIT);
+                if *router_lifetime == 0 {
+                    FINAL_ADVERTISEMENTS.fetch_add(1, SeqCst);
+                } else {
+                    ADVERTISEMENTS.fetch_add(1, SeqCst);
+                }
+            }
+            _ => {}
+        }
+
+        send(msg, out)
+    }
+}
+
+impl Link {
+    fn defend(&mut self, target: Ipv6Addr) {
+        let icmp = Icmpv6Packet::NeighborAdvertisement {
+            router: false,
+            solicited: false,
+            overrides: true,
+            target,
+            options: vec![NdpOption::TargetLinkLayerAddress(DUPLICATE_MAC.into())],
+        };
+        let pkt = Ipv6Packet {
+            traffic_class: 0,
+            flow_label: 0,
+            next_header: PROTO_ICMPV6,
+            hop_limit: NDP_HOP_LIMIT,
+            src: target,
+            dest: ALL_NODES_MULTICAST,
+            content: icmp.to_vec().unwrap(),
+        };
+        let msg = Message::new()
+            .kind(KIND_IPV6)
+            .src(DUPLICATE_MAC)
+            .dest(MacAddress::ipv6_multicast(ALL_NODES_MULTICAST).into())
+            .content(pkt)
+            .build();
+        send(msg, "lhs_out")
+    }
+}
+
+// The router, announcing 2001:db8::/64
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv6(NetworkDevice::eth(), ROUTER)).unwrap();
+        start_router_advertisements(
+            &InterfaceName::new("en1").id(),
+            RouterAdvertisementConfig {
+                prefixes: vec![PrefixAdvertisement::new(PREFIX, 64)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // The router derives a link-local address to advertise from
+        assert!(ipv6_addrs().iter().any(|addr| addr.segments()[0] == 0xfe80));
+
+        let expected = if MODE.load(SeqCst) == MODE_DUPLICATE {
+            0
+        } else {
+            1
+        };
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = UdpSocket::bind(":::2000").await.unwrap();
+            let mut buf = [0u8; 1024];
+            for _ in 0..expected {
+                let (n, from) = sock.recv_from(&mut buf).await.unwrap();
+                assert_eq!(n, 42);
+
+                let IpAddr::V6(from) = from.ip() else {
+                    panic!("expected IPv6 peer")
+                };
+                assert_eq!(from.segments()[..4], PREFIX.segments()[..4]);
+            }
+
+            stop_router_advertisements(&InterfaceName::new("en1").id()).unwrap();
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        if MODE.load(SeqCst) == MODE_RANDOM {
+            set_ndp_config(NdpConfig {
+                addr_gen_mode: AddrGenMode::Random,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        add_interface(Interface::ethv6_autoconf(NetworkDevice::eth())).unwrap();
+
+        // Only the link-local address is assigned initially
+        let addrs = ipv6_addrs();
+        assert_eq!(addrs.len(), 1);
+        let link_local = addrs[0];
+        assert_eq!(link_local.segments()[..4], [0xfe80, 0, 0, 0]);
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            des::time::sleep(Duration::from_secs(5)).await;
+
+            let global = ipv6_addrs()
+                .into_iter()
+                .filter(|addr| addr.segments()[..4] == PREFIX.segments()[..4])
+                .collect::<Vec<_>>();
+
+            match MODE.load(SeqCst) {
+                MODE_EUI64 => {
+                    assert_eq!(global.len(), 1);
+                    assert_eq!(interface_id(global[0]), interface_id(link_local));
+                }
+                MODE_RANDOM => {
+                    assert_eq!(global.len(), 1);
+                    assert_ne!(interface_id(global[0]), DUPLICATE_ID.load(SeqCst));
+                }
+                MODE_DUPLICATE => {
+                    assert!(global.is_empty());
+                    done.store(true, SeqCst);
+                    return;
+                }
+                _ => unreachable!(),
+            }
+
+            // Packets to other hosts use the autoconfigured address
+            let sock = UdpSocket::bind(":::0").await.unwrap();
+            sock.send_to(&[42; 42], SocketAddrV6::new(ROUTER, 2000, 0, 0))
+                .await
+                .unwrap();
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(mode: usize) {
+    inet::init();
+    MODE.store(mode, SeqCst);
+    DAD_PROBES.store(0, SeqCst);
+    ADVERTISEMENTS.store(0, SeqCst);
+    FINAL_ADVERTISEMENTS.store(0, SeqCst);
+    DUPLICATE_ID.store(0, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
+
+#[test]
+#[serial]
+fn slaac_eui64() {
+    run(MODE_EUI64);
+    assert_eq!(DAD_PROBES.load(SeqCst), 1);
+    assert!(ADVERTISEMENTS.load(SeqCst) >= 1);
+    assert_eq!(FINAL_ADVERTISEMENTS.load(SeqCst), 1);
+}
+
+#[test]
+#[serial]
+fn slaac_random_regenerates_on_duplicate() {
+    run(MODE_RANDOM);
+    assert_eq!(DAD_PROBES.load(SeqCst), 2);
+}
+
+#[test]
+#[serial]
+fn slaac_eui64_duplicate() {
+    run(MODE_DUPLICATE);
+    assert_eq!(DAD_PROBES.load(SeqCst), 1);
+}
@@ -0,0 +1,156 @@
+use des::registry;
+use std::{
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{interface::*, TcpListener, TcpStream};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// 0: TcpStream::abort, 1: drop with SO_LINGER=0
+static MODE: AtomicUsize = AtomicUsize::new(0);
+
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            let mut buf = [0u8; 100];
+            stream.read_exact(&mut buf).await.unwrap();
+
+            // Simulate a crashing server, buffered data must be discarded
+            stream.write_all(&[1; 100]).await.unwrap();
+            match MODE.load(SeqCst) {
+                0 => stream.abort().unwrap(),
+                _ => {
+                    stream.set_linger(Some(Duration::ZERO)).unwrap();
+                    assert_eq!(stream.linger().unwrap(), Some(Duration::ZERO));
+                    drop(stream);
+                }
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+            stream.write_all(&[42; 100]).await.unwrap();
+
+            // Read until the connection is reset, not closed gracefully
+            let mut buf = [0u8; 1024];
+            let err = loop {
+                match stream.read(&mut buf).await {
+                    Ok(0) => panic!("connection was closed gracefully"),
+                    Ok(_) => continue,
+                    Err(e) => break e,
+                }
+            };
+            assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+
+            // Writes fail after the reset
+            assert_eq!(stream.write(&[1, 2, 3]).await.unwrap(), 0);
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(mode: usize) {
+    inet::init();
+    MODE.store(mode, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_abort_sends_rst() {
+    run(0)
+}
+
+#[test]
+#[serial]
+fn tcp_linger_zero_sends_rst() {
+    run(1)
+}
@@ -0,0 +1,193 @@
+use bytepack::FromBytestream;
+use des::registry;
+use inet_types::{
+    ip::{IpPacketRef, Ipv4Packet, ECN_CE, ECN_ECT0, ECN_NOT_ECT},
+    tcp::TcpPacket,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, CongestionAlgorithm, TcpConfig},
+    TcpListener, TcpStream,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIMIT: usize = 40 * 1024;
+const MARK_EVERY: usize = 10;
+
+fn tcp_cfg() -> TcpConfig {
+    TcpConfig {
+        ecn: true,
+        cong_ctrl: true,
+        cong_algorithm: CongestionAlgorithm::Reno,
+        ..Default::default()
+    }
+}
+
+// Acts like an AQM, marking every n-th data segment from
+// client to server as congestion experienced, instead of dropping it.
+struct Link {
+    segments: usize,
+    marked: usize,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {
+            segments: 0,
+            marked: 0,
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let mut msg = msg;
+                let ippacket = msg.content_mut::<Ipv4Packet>();
+                let tcp = TcpPacket::from_slice(&ippacket.content).unwrap();
+
+                let ecn = IpPacketRef::V4(ippacket).ecn();
+                if tcp.flags.syn {
+                    // ECN-setup SYN
+                    assert!(tcp.flags.ece && tcp.flags.cwr);
+                    assert_eq!(ecn, ECN_NOT_ECT);
+                } else if !tcp.content.is_empty() {
+                    assert_eq!(ecn, ECN_ECT0);
+                    self.segments += 1;
+                    if self.segments % MARK_EVERY == 0 {
+                        ippacket.enc = ECN_CE;
+                        self.marked += 1;
+                    }
+                } else {
+                    assert_eq!(ecn, ECN_NOT_ECT);
+                }
+
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+
+    fn at_sim_end(&mut self) {
+        assert!(self.marked > 0);
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            let mut buf = [0u8; 1024];
+            let mut acc = 0;
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                for (i, byte) in buf[..n].iter().enumerate() {
+                    assert_eq!(*byte, ((acc + i) % 251) as u8);
+                }
+                acc += n;
+            }
+
+            assert_eq!(acc, LIMIT);
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            stream.write_all(&data).await.unwrap();
+
+            // CE marks are echoed and reduce the window, without any retransmission
+            let info = stream.info().unwrap();
+            assert!(info.ecn);
+            assert!(info.ecn_reductions > 0);
+            assert_eq!(info.retransmits, 0);
+
+            done.store(true, SeqCst);
+            drop(stream);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn tcp_ecn_without_loss() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
@@ -0,0 +1,171 @@
+use bytepack::FromBytestream;
+use des::registry;
+use inet_types::{
+    ip::Ipv4Packet,
+    tcp::{TcpOption, TcpPacket},
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, TcpConfig},
+    TcpListener, TcpStream,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const REQUESTS: usize = 3;
+
+// Records all SYNs from client to server
+struct Link {
+    syns: Vec<TcpPacket>,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self { syns: Vec::new() }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let ippacket = msg.content::<Ipv4Packet>();
+                let tcp = TcpPacket::from_slice(&ippacket.content).unwrap();
+                if tcp.flags.syn {
+                    self.syns.push(tcp);
+                }
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+
+    fn at_sim_end(&mut self) {
+        assert_eq!(self.syns.len(), REQUESTS);
+
+        // The first SYN requests a cookie, all others use it
+        assert!(self.syns[0].content.is_empty());
+        assert!(self.syns[0]
+            .options
+            .contains(&TcpOption::FastOpenCookie(Vec::new())));
+
+        for syn in &self.syns[1..] {
+            assert_eq!(syn.content, b"request");
+            assert!(syn.options.iter().any(
+                |option| matches!(option, TcpOption::FastOpenCookie(cookie) if !cookie.is_empty())
+            ));
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(TcpConfig {
+            fast_open: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            for _ in 0..REQUESTS {
+                let (mut stream, _) = list.accept().await.unwrap();
+
+                let mut buf = [0u8; 7];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"request");
+                stream.write_all(b"response").await.unwrap();
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            for _ in 0..REQUESTS {
+                let mut stream = TcpStream::connect_with_data("69.0.0.100:2000", b"request")
+                    .await
+                    .unwrap();
+
+                let mut buf = [0u8; 8];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"response");
+                drop(stream);
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn tcp_fast_open() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
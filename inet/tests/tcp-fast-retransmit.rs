@@ -0,0 +1,215 @@
+use bytepack::FromBytestream;
+use des::registry;
+use inet_types::{ip::Ipv4Packet, tcp::TcpPacket};
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, CongestionAlgorithm, TcpConfig},
+    TcpListener, TcpStream,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIMIT: usize = 40 * 1024;
+const DROP_SEGMENT: usize = 10;
+
+static SACK: AtomicBool = AtomicBool::new(false);
+static CONG_CTRL: AtomicBool = AtomicBool::new(false);
+
+fn tcp_cfg() -> TcpConfig {
+    TcpConfig {
+        sack: SACK.load(SeqCst),
+        cong_ctrl: CONG_CTRL.load(SeqCst),
+        cong_algorithm: CongestionAlgorithm::NewReno,
+        ..Default::default()
+    }
+}
+
+// Drops a single data segment from client to server, and records
+// the time until the segment was retransmitted.
+struct Link {
+    segments: usize,
+    dropped: Option<(u32, SimTime)>,
+    recovered: Option<SimTime>,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {
+            segments: 0,
+            dropped: None,
+            recovered: None,
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let ippacket = msg.content::<Ipv4Packet>();
+                let tcp = TcpPacket::from_slice(&ippacket.content).unwrap();
+                if !tcp.content.is_empty() {
+                    self.segments += 1;
+                    match self.dropped {
+                        None if self.segments == DROP_SEGMENT => {
+                            tracing::info!("dropping segment {}", tcp.seq_no);
+                            self.dropped = Some((tcp.seq_no, SimTime::now()));
+                            return;
+                        }
+                        Some((seq_no, _)) if seq_no == tcp.seq_no && self.recovered.is_none() => {
+                            self.recovered = Some(SimTime::now());
+                        }
+                        _ => {}
+                    }
+                }
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+
+    fn at_sim_end(&mut self) {
+        let (_, dropped) = self.dropped.expect("no segment was dropped");
+        let recovered = self.recovered.expect("segment was never retransmitted");
+
+        // The RTO is at least 500ms, fast retransmit needs about one RTT
+        let dur = recovered - dropped;
+        assert!(
+            dur < Duration::from_millis(250),
+            "recovery took {dur:?}, expected fast retransmit"
+        );
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            let mut buf = [0u8; 1024];
+            let mut acc = 0;
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                for (i, byte) in buf[..n].iter().enumerate() {
+                    assert_eq!(*byte, ((acc + i) % 251) as u8);
+                }
+                acc += n;
+            }
+
+            assert_eq!(acc, LIMIT);
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            stream.write_all(&data).await.unwrap();
+
+            done.store(true, SeqCst);
+            drop(stream);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_fast_retransmit() {
+    SACK.store(false, SeqCst);
+    CONG_CTRL.store(false, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn tcp_fast_recovery_newreno() {
+    SACK.store(false, SeqCst);
+    CONG_CTRL.store(true, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn tcp_fast_recovery_sack() {
+    SACK.store(true, SeqCst);
+    CONG_CTRL.store(true, SeqCst);
+    run();
+}
@@ -0,0 +1,167 @@
+use des::registry;
+use std::{
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use des::prelude::*;
+use inet::{interface::*, TcpListener, TcpStream};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const REQUEST: usize = 4000;
+const RESPONSE: usize = 6000;
+
+// 0: TcpStream::shutdown, 1: AsyncWriteExt::shutdown, 2: OwnedWriteHalf
+static MODE: AtomicUsize = AtomicUsize::new(0);
+
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            // The request ends, once the client half-closed the connection
+            let mut request = Vec::new();
+            stream.read_to_end(&mut request).await.unwrap();
+            assert_eq!(request.len(), REQUEST);
+            assert!(request.iter().all(|&b| b == 42));
+
+            // The response can still be send
+            stream.write_all(&[7; RESPONSE]).await.unwrap();
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+
+            let mut response = Vec::new();
+            match MODE.load(SeqCst) {
+                0 => {
+                    stream.write_all(&[42; REQUEST]).await.unwrap();
+                    stream.shutdown(Shutdown::Write).unwrap();
+                    stream.read_to_end(&mut response).await.unwrap();
+                }
+                1 => {
+                    stream.write_all(&[42; REQUEST]).await.unwrap();
+                    AsyncWriteExt::shutdown(&mut stream).await.unwrap();
+                    stream.read_to_end(&mut response).await.unwrap();
+                }
+                _ => {
+                    let (mut rx, mut tx) = stream.into_split();
+                    tx.write_all(&[42; REQUEST]).await.unwrap();
+                    tx.shutdown().await.unwrap();
+                    rx.read_to_end(&mut response).await.unwrap();
+                }
+            }
+
+            assert_eq!(response.len(), RESPONSE);
+            assert!(response.iter().all(|&b| b == 7));
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(mode: usize) {
+    inet::init();
+    MODE.store(mode, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_half_close_shutdown_write() {
+    run(0)
+}
+
+#[test]
+#[serial]
+fn tcp_half_close_poll_shutdown() {
+    run(1)
+}
+
+#[test]
+#[serial]
+fn tcp_half_close_owned_write_half() {
+    run(2)
+}
@@ -0,0 +1,158 @@
+use bytepack::FromBytestream;
+use des::registry;
+use inet_types::{ip::Ipv4Packet, tcp::TcpPacket};
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{CongestionAlgorithm, TcpState},
+    TcpListener, TcpSocket,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIMIT: usize = 20 * 1024;
+const DROP_SEGMENT: usize = 5;
+
+// Drops a single data segment from client to server
+struct Link {
+    segments: usize,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self { segments: 0 }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => {
+                let ippacket = msg.content::<Ipv4Packet>();
+                let tcp = TcpPacket::from_slice(&ippacket.content).unwrap();
+                if !tcp.content.is_empty() {
+                    self.segments += 1;
+                    if self.segments == DROP_SEGMENT {
+                        return;
+                    }
+                }
+                send(msg, "rhs_out")
+            }
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            let mut buf = vec![0u8; LIMIT];
+            stream.read_exact(&mut buf).await.unwrap();
+
+            let info = stream.info().unwrap();
+            assert_eq!(info.state, TcpState::Established);
+            assert_eq!(info.bytes_sent, 0);
+            assert_eq!(info.cwnd, None);
+        });
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = TcpSocket::new_v4().unwrap();
+            sock.set_congestion_control(Some(CongestionAlgorithm::Reno))
+                .unwrap();
+            let mut stream = sock
+                .connect("69.0.0.100:2000".parse().unwrap())
+                .await
+                .unwrap();
+
+            let info = stream.info().unwrap();
+            assert_eq!(info.state, TcpState::Established);
+            assert_eq!(info.bytes_sent, 0);
+            assert!(info.cwnd.is_some() && info.ssthresh.is_some());
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            stream.write_all(&data).await.unwrap();
+
+            // Once the server closes the connection, all data was acknowledged
+            let mut buf = [0u8; 64];
+            assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+
+            let info = stream.info().unwrap();
+            tracing::info!("{info:#?}");
+            assert_eq!(info.bytes_acked, LIMIT as u64);
+            assert_eq!(info.bytes_sent, LIMIT as u64 + info.bytes_retransmitted);
+            assert!(info.retransmits >= 1);
+            assert!(info.bytes_retransmitted >= 1);
+            assert!(info.rtt > Duration::ZERO);
+            assert!(info.rto >= info.rtt);
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn tcp_info_statistics() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
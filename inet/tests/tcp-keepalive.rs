@@ -0,0 +1,166 @@
+use des::registry;
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, KeepaliveConfig, TcpConfig},
+    TcpListener, TcpStream,
+};
+use serial_test::serial;
+use std::io::ErrorKind;
+use tokio::io::AsyncReadExt;
+
+const IDLE: Duration = Duration::from_secs(10);
+const INTERVAL: Duration = Duration::from_secs(2);
+const PROBES: u32 = 3;
+
+static PARTITION: AtomicBool = AtomicBool::new(false);
+
+fn tcp_cfg() -> TcpConfig {
+    TcpConfig {
+        keepalive: Some(KeepaliveConfig {
+            idle: IDLE,
+            interval: INTERVAL,
+            probes: PROBES,
+        }),
+        ..Default::default()
+    }
+}
+
+// Once partitioned, the link drops all packets after 1s, so that the
+// connection is established, but the peer goes silent afterwards.
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        if PARTITION.load(SeqCst) && SimTime::now() > SimTime::from_duration(Duration::from_secs(1))
+        {
+            return;
+        }
+
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            // Keep the stream alive, without sending any data
+            let mut buf = [0u8; 64];
+            let _ = stream.read(&mut buf).await;
+        });
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+            assert_eq!(
+                stream.keepalive().unwrap(),
+                Some(tcp_cfg().keepalive.unwrap())
+            );
+
+            let mut buf = [0u8; 64];
+            let result = stream.read(&mut buf).await;
+
+            if PARTITION.load(SeqCst) {
+                // The connection is aborted after all probes remain unanswered
+                let err = result.unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+                let limit = IDLE + INTERVAL * PROBES;
+                let now = SimTime::now().as_secs_f64();
+                assert!(now >= limit.as_secs_f64());
+                assert!(now < limit.as_secs_f64() + 2.0);
+                done.store(true, SeqCst);
+            } else {
+                unreachable!("the connection should stay alive: {result:?}");
+            }
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        // A healthy idle connection must not be aborted
+        assert_eq!(self.done.load(SeqCst), PARTITION.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_keepalive_idle_peer() {
+    PARTITION.store(false, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn tcp_keepalive_dead_peer() {
+    PARTITION.store(true, SeqCst);
+    run();
+}
@@ -0,0 +1,162 @@
+use des::registry;
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, TcpConfig},
+    TcpListener, TcpStream,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const ACK_DELAY: Duration = Duration::from_millis(200);
+const REQUEST: usize = 200;
+
+static NODELAY: AtomicBool = AtomicBool::new(true);
+
+fn tcp_cfg() -> TcpConfig {
+    TcpConfig {
+        nodelay: true,
+        ack_delay: Some(ACK_DELAY),
+        ..Default::default()
+    }
+}
+
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        tokio::spawn(async move {
+            let list = TcpListener::bind("0.0.0.0:2000").await.unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            // Respond once the full request was received
+            let mut buf = [0u8; REQUEST];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(b"response").await.unwrap();
+
+            let _ = stream.read(&mut buf).await;
+        });
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+            stream.set_nodelay(NODELAY.load(SeqCst)).unwrap();
+            assert_eq!(stream.nodelay().unwrap(), NODELAY.load(SeqCst));
+
+            // A request written in two parts, the classic write-write-read pattern
+            let t0 = SimTime::now();
+            stream.write_all(&[1; REQUEST / 2]).await.unwrap();
+            stream.write_all(&[2; REQUEST / 2]).await.unwrap();
+
+            let mut buf = [0u8; 8];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"response");
+
+            let latency = SimTime::now() - t0;
+            tracing::info!("request took {latency:?}");
+            if NODELAY.load(SeqCst) {
+                // Two one-way delays of 60ms each
+                assert!(latency < ACK_DELAY, "latency {latency:?}");
+            } else {
+                // The second write waits for the delayed ACK of the first one
+                assert!(
+                    latency > ACK_DELAY + Duration::from_millis(120),
+                    "latency {latency:?}"
+                );
+            }
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(10.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_nodelay_write_write_read() {
+    NODELAY.store(true, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn tcp_nagle_delayed_ack_write_write_read() {
+    NODELAY.store(false, SeqCst);
+    run();
+}
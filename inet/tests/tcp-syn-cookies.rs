@@ -0,0 +1,177 @@
+use des::{registry, time::sleep};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, TcpConfig},
+    TcpSocket, TcpStream,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const CLIENTS: usize = 4;
+
+static SYN_COOKIES: AtomicBool = AtomicBool::new(false);
+
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(TcpConfig {
+            syn_cookies: SYN_COOKIES.load(SeqCst),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = TcpSocket::new_v4().unwrap();
+            sock.bind("0.0.0.0:2000".parse().unwrap()).unwrap();
+            let list = sock.listen(1).unwrap();
+
+            // Let the SYN queue overflow, before accepting any connection
+            sleep(Duration::from_secs(1)).await;
+
+            let stats = list.stats().unwrap();
+            assert!(stats.syn_received as usize >= CLIENTS);
+            if !SYN_COOKIES.load(SeqCst) {
+                assert!(stats.syn_dropped as usize >= CLIENTS - 1);
+                assert_eq!(stats.syn_cookies_sent, 0);
+                done.store(true, SeqCst);
+                return;
+            }
+
+            assert_eq!(stats.syn_dropped, 0);
+            assert_eq!(stats.syn_cookies_sent as usize, CLIENTS - 1);
+
+            let mut seen = Vec::new();
+            for _ in 0..CLIENTS {
+                let (mut stream, _) = list.accept().await.unwrap();
+                let mut buf = [0u8; 100];
+                stream.read_exact(&mut buf).await.unwrap();
+                assert!(buf.iter().all(|b| *b == buf[0]));
+                seen.push(buf[0]);
+            }
+            seen.sort();
+            assert_eq!(seen, (0..CLIENTS as u8).collect::<Vec<_>>());
+
+            let stats = list.stats().unwrap();
+            assert_eq!(stats.syn_cookies_validated as usize, CLIENTS - 1);
+            assert_eq!(stats.syn_cookies_rejected, 0);
+
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        for i in 0..CLIENTS {
+            let done = self.done.clone();
+            tokio::spawn(async move {
+                // Without SYN cookies, connections may be refused
+                let Ok(mut stream) = TcpStream::connect("69.0.0.100:2000").await else {
+                    return;
+                };
+                stream.write_all(&[i as u8; 100]).await.unwrap();
+                done.fetch_add(1, SeqCst);
+
+                sleep(Duration::from_secs(5)).await;
+                drop(stream);
+            });
+        }
+    }
+
+    async fn at_sim_end(&mut self) {
+        if SYN_COOKIES.load(SeqCst) {
+            assert_eq!(self.done.load(SeqCst), CLIENTS);
+        }
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(30.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
+
+#[test]
+#[serial]
+fn tcp_syn_cookies_accept_beyond_backlog() {
+    SYN_COOKIES.store(true, SeqCst);
+    run();
+}
+
+#[test]
+#[serial]
+fn tcp_syn_queue_overflow_drops() {
+    SYN_COOKIES.store(false, SeqCst);
+    run();
+}
@@ -0,0 +1,170 @@
+use des::registry;
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    tcp::{set_tcp_cfg, CongestionAlgorithm, TcpConfig},
+    TcpSocket,
+};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIMIT: usize = 1_000_000;
+const BUFFER_SIZE: u32 = 1 << 18;
+
+fn tcp_cfg() -> TcpConfig {
+    TcpConfig {
+        window_scaling: true,
+        timestamps: true,
+        ..Default::default()
+    }
+}
+
+struct Link {}
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => send(msg, "lhs_out"),
+            _ => todo!(),
+        }
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            // A receive buffer larger than 64KiB requires window scaling
+            let sock = TcpSocket::new_v4().unwrap();
+            sock.set_recv_buffer_size(BUFFER_SIZE).unwrap();
+            sock.bind("0.0.0.0:2000".parse().unwrap()).unwrap();
+            let list = sock.listen(1).unwrap();
+
+            let (mut stream, _) = list.accept().await.unwrap();
+            tracing::info!("Established stream");
+
+            let mut buf = [0u8; 4096];
+            let mut acc = 0;
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                for (i, byte) in buf[..n].iter().enumerate() {
+                    assert_eq!(*byte, ((acc + i) % 251) as u8);
+                }
+                acc += n;
+            }
+
+            assert_eq!(acc, LIMIT);
+            tracing::info!("Server done");
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+        set_tcp_cfg(tcp_cfg()).unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            // The default receive buffer requires no scaling on our side,
+            // yet the peer may still scale its window
+            let sock = TcpSocket::new_v4().unwrap();
+            sock.set_send_buffer_size(BUFFER_SIZE).unwrap();
+            sock.set_congestion_control(Some(CongestionAlgorithm::Reno))
+                .unwrap();
+
+            let mut stream = sock
+                .connect("69.0.0.100:2000".parse().unwrap())
+                .await
+                .unwrap();
+            tracing::info!("Established stream");
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let mut send_window = 0;
+            for chunk in data.chunks(1 << 16) {
+                stream.write_all(chunk).await.unwrap();
+                send_window = send_window.max(stream.info().unwrap().send_window);
+            }
+            assert!(send_window > u16::MAX as u32);
+
+            tracing::info!("Client done");
+            done.store(true, SeqCst);
+            drop(stream);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn tcp_window_scaling_1m() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
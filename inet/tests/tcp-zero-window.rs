@@ -0,0 +1,165 @@
+use bytepack::FromBytestream;
+use des::{registry, time::sleep};
+use inet_types::{ip::Ipv4Packet, tcp::TcpPacket};
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Arc,
+};
+
+use des::prelude::*;
+use inet::{interface::*, TcpSocket, TcpStream};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const LIMIT: usize = 16 * 1024;
+const BUFFER_SIZE: u32 = 4096;
+
+// Drops the first window update of the server, after it
+// advertised a zero window.
+struct Link {
+    zero_window: bool,
+    dropped: bool,
+}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {
+            zero_window: false,
+            dropped: false,
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => send(msg, "rhs_out"),
+            Some("rhs_in") => {
+                let ippacket = msg.content::<Ipv4Packet>();
+                let tcp = TcpPacket::from_slice(&ippacket.content).unwrap();
+                if tcp.window == 0 {
+                    self.zero_window = true;
+                } else if self.zero_window && !self.dropped {
+                    tracing::info!("dropping window update {}", tcp.window);
+                    self.dropped = true;
+                    return;
+                }
+                send(msg, "lhs_out")
+            }
+            _ => todo!(),
+        }
+    }
+
+    fn at_sim_end(&mut self) {
+        assert!(self.dropped, "no window update was dropped");
+    }
+}
+
+struct TcpServer {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 100),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let sock = TcpSocket::new_v4().unwrap();
+            sock.set_recv_buffer_size(BUFFER_SIZE).unwrap();
+            sock.bind("0.0.0.0:2000".parse().unwrap()).unwrap();
+            let list = sock.listen(1).unwrap();
+            let (mut stream, _) = list.accept().await.unwrap();
+
+            // A slow reader, that lets the receive buffer fill up
+            sleep(Duration::from_secs(1)).await;
+
+            let mut buf = [0u8; 1024];
+            let mut acc = 0;
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                for (i, byte) in buf[..n].iter().enumerate() {
+                    assert_eq!(*byte, ((acc + i) % 251) as u8);
+                }
+                acc += n;
+                sleep(Duration::from_millis(100)).await;
+            }
+
+            assert_eq!(acc, LIMIT);
+            done.store(true, SeqCst);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct TcpClient {
+    done: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::ethv4(
+            NetworkDevice::eth(),
+            Ipv4Addr::new(69, 0, 0, 200),
+        ))
+        .unwrap();
+
+        let done = self.done.clone();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect("69.0.0.100:2000").await.unwrap();
+
+            let data = (0..LIMIT).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            stream.write_all(&data).await.unwrap();
+
+            done.store(true, SeqCst);
+            drop(stream);
+        });
+    }
+
+    async fn at_sim_end(&mut self) {
+        assert!(self.done.load(SeqCst));
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+#[test]
+#[serial]
+fn tcp_zero_window_lost_window_update() {
+    inet::init();
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(60.0.into()).build(app);
+    let _ = rt.run().unwrap();
+}
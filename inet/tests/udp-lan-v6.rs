@@ -1,7 +1,7 @@
 use des::{prelude::*, registry, time::sleep};
 use inet::{
-    arp::arpa,
     interface::{add_interface, Interface, NetworkDevice},
+    ndp::neighbors,
     UdpSocket,
 };
 use inet_types::ip::Ipv6Packet;
@@ -82,7 +82,7 @@ impl AsyncModule for Node {
     }
 
     async fn at_sim_end(&mut self) {
-        for entry in arpa().unwrap() {
+        for entry in neighbors().unwrap() {
             tracing::debug!("{entry}")
         }
         for h in self.handles.drain(..) {
@@ -0,0 +1,202 @@
+use bytepack::FromBytestream;
+use des::registry;
+use inet_types::{
+    icmp::{IcmpDestinationUnreachableCode, IcmpPacket, IcmpType, PROTO_ICMP},
+    icmpv6::{Icmpv6Packet, Icmpv6ParameterProblemCode, PROTO_ICMPV6},
+    ip::{IpPacket, Ipv4Flags, Ipv4Packet, Ipv6Packet},
+};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering::SeqCst};
+
+use des::prelude::*;
+use inet::{
+    interface::*,
+    socket::{set_unknown_proto_passthrough, unknown_proto_dropped, RawIpSocket},
+};
+use serial_test::serial;
+
+const PROTO: u8 = 253;
+
+const CLIENT_V4: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 1);
+const SERVER_V4: Ipv4Addr = Ipv4Addr::new(192, 168, 0, 2);
+const CLIENT_V6: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+const SERVER_V6: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+
+static PASSTHROUGH: AtomicBool = AtomicBool::new(false);
+static PROTO_UNREACHABLE_V4: AtomicUsize = AtomicUsize::new(0);
+static PROTO_UNREACHABLE_V6: AtomicUsize = AtomicUsize::new(0);
+static PASSED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(u64::MAX);
+
+// Counts the errors send by the server.
+struct Link {}
+
+impl Module for Link {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        let out = match msg.header().last_gate.as_ref().map(|v| v.name()) {
+            Some("lhs_in") => "rhs_out",
+            Some("rhs_in") => "lhs_out",
+            _ => todo!(),
+        };
+
+        if let Some(ip) = msg.try_content::<Ipv4Packet>() {
+            if ip.proto == PROTO_ICMP {
+                let icmp = IcmpPacket::read_from_slice(&mut &ip.content[..]).unwrap();
+                if let IcmpType::DestinationUnreachable { code, .. } = icmp.typ {
+                    assert_eq!(code, IcmpDestinationUnreachableCode::ProtocolUnreachable);
+                    assert_eq!(ip.dest, CLIENT_V4);
+                    PROTO_UNREACHABLE_V4.fetch_add(1, SeqCst);
+                }
+            }
+        }
+        if let Some(ip) = msg.try_content::<Ipv6Packet>() {
+            if ip.next_header == PROTO_ICMPV6 {
+                let icmp = Icmpv6Packet::read_from_slice(&mut &ip.content[..]).unwrap();
+                if let Icmpv6Packet::ParameterProblem { code, pointer, .. } = icmp {
+                    assert_eq!(code, Icmpv6ParameterProblemCode::UnrecognizedNextHeader);
+                    assert_eq!(pointer, 6);
+                    assert_eq!(ip.dest, CLIENT_V6);
+                    PROTO_UNREACHABLE_V6.fetch_add(1, SeqCst);
+                }
+            }
+        }
+
+        send(msg, out)
+    }
+}
+
+struct TcpServer {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpServer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::eth_mixed(
+            "en0",
+            NetworkDevice::eth(),
+            (SERVER_V4, Ipv4Addr::new(255, 255, 255, 0)),
+            (SERVER_V6, 64),
+        ))
+        .unwrap();
+        set_unknown_proto_passthrough(PASSTHROUGH.load(SeqCst)).unwrap();
+
+        tokio::spawn(async move {
+            des::time::sleep(Duration::from_secs(5)).await;
+            DROPPED.store(unknown_proto_dropped().unwrap(), SeqCst);
+        });
+    }
+
+    async fn handle_message(&mut self, msg: Message) {
+        let proto = match (
+            msg.try_content::<Ipv4Packet>(),
+            msg.try_content::<Ipv6Packet>(),
+        ) {
+            (Some(ip), _) => ip.proto,
+            (_, Some(ip)) => ip.next_header,
+            _ => return,
+        };
+        assert_eq!(proto, PROTO);
+        PASSED.fetch_add(1, SeqCst);
+    }
+}
+
+struct TcpClient {}
+
+#[async_trait::async_trait]
+impl AsyncModule for TcpClient {
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn at_sim_start(&mut self, _: usize) {
+        add_interface(Interface::eth_mixed(
+            "en0",
+            NetworkDevice::eth(),
+            (CLIENT_V4, Ipv4Addr::new(255, 255, 255, 0)),
+            (CLIENT_V6, 64),
+        ))
+        .unwrap();
+
+        tokio::spawn(async move {
+            let v4 = RawIpSocket::new_v4().unwrap();
+            v4.try_send(IpPacket::V4(Ipv4Packet {
+                dscp: 0,
+                enc: 0,
+                identification: 1,
+                flags: Ipv4Flags {
+                    df: false,
+                    mf: false,
+                },
+                fragment_offset: 0,
+                ttl: 64,
+                proto: PROTO,
+                src: CLIENT_V4,
+                dest: SERVER_V4,
+                content: vec![42; 16],
+            }))
+            .unwrap();
+
+            let v6 = RawIpSocket::new_v6().unwrap();
+            v6.try_send(IpPacket::V6(Ipv6Packet {
+                traffic_class: 0,
+                flow_label: 0,
+                next_header: PROTO,
+                hop_limit: 64,
+                src: CLIENT_V6,
+                dest: SERVER_V6,
+                content: vec![42; 16],
+            }))
+            .unwrap();
+        });
+    }
+}
+
+struct Main;
+impl Module for Main {
+    fn new() -> Main {
+        Main
+    }
+}
+
+fn run(passthrough: bool) {
+    inet::init();
+    PASSTHROUGH.store(passthrough, SeqCst);
+    PROTO_UNREACHABLE_V4.store(0, SeqCst);
+    PROTO_UNREACHABLE_V6.store(0, SeqCst);
+    PASSED.store(0, SeqCst);
+    DROPPED.store(u64::MAX, SeqCst);
+
+    let app = NetworkApplication::new(
+        NdlApplication::new("tests/tcp.ndl", registry![Link, TcpServer, TcpClient, Main])
+            .map_err(|e| println!("{e}"))
+            .unwrap(),
+    );
+    let rt = Builder::seeded(123).max_time(100.0.into()).build(app);
+    let _ = rt.run();
+}
+
+#[test]
+#[serial]
+fn unknown_proto_is_unreachable() {
+    run(false);
+    assert_eq!(PROTO_UNREACHABLE_V4.load(SeqCst), 1);
+    assert_eq!(PROTO_UNREACHABLE_V6.load(SeqCst), 1);
+    assert_eq!(PASSED.load(SeqCst), 0);
+    assert_eq!(DROPPED.load(SeqCst), 2);
+}
+
+#[test]
+#[serial]
+fn unknown_proto_passthrough() {
+    run(true);
+    assert_eq!(PROTO_UNREACHABLE_V4.load(SeqCst), 0);
+    assert_eq!(PROTO_UNREACHABLE_V6.load(SeqCst), 0);
+    assert_eq!(PASSED.load(SeqCst), 2);
+    assert_eq!(DROPPED.load(SeqCst), 0);
+}